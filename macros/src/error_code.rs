@@ -0,0 +1,122 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::ParseStream;
+use syn::{Data, DeriveInput, Fields, Ident, LitInt, LitStr, Token};
+
+struct CodeAttr {
+    code: LitStr,
+    http: Option<LitInt>,
+    msg: Option<LitStr>,
+}
+
+fn parse_code_attr(input: ParseStream) -> syn::Result<CodeAttr> {
+    let code: LitStr = input.parse()?;
+    let mut attr = CodeAttr { code, http: None, msg: None };
+    while !input.is_empty() {
+        input.parse::<Token![,]>()?;
+        if input.is_empty() {
+            break;
+        }
+        let key: Ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        match key.to_string().as_str() {
+            "http" => {
+                let http: LitInt = input.parse()?;
+                http.base10_parse::<u16>()?;
+                attr.http = Some(http);
+            }
+            "msg" => attr.msg = Some(input.parse()?),
+            _ => return Err(syn::Error::new_spanned(key, "expected `http` or `msg`")),
+        }
+    }
+    Ok(attr)
+}
+
+pub fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&input.generics, "derive(ErrorCode) does not support generic types"));
+    }
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => return Err(syn::Error::new_spanned(name, "derive(ErrorCode) only supports enums")),
+    };
+
+    let mut codes = vec![];
+    let mut https = vec![];
+    let mut msgs = vec![];
+    let mut entries = vec![];
+    for variant in &data.variants {
+        let attr = variant
+            .attrs
+            .iter()
+            .find(|attr| attr.path().is_ident("code"))
+            .ok_or_else(|| syn::Error::new_spanned(&variant.ident, "missing #[code(\"...\")] attribute"))?;
+        let CodeAttr { code, http, msg } = attr.parse_args_with(parse_code_attr)?;
+        let ident = &variant.ident;
+        let pattern = match &variant.fields {
+            Fields::Named(_) => quote!(Self::#ident { .. }),
+            Fields::Unnamed(_) => quote!(Self::#ident(..)),
+            Fields::Unit => quote!(Self::#ident),
+        };
+        let http = match http {
+            Some(http) => quote!(Some(#http)),
+            None => quote!(None),
+        };
+        let msg = match msg {
+            Some(msg) => quote!(#msg),
+            None => quote!(#code),
+        };
+        codes.push(quote!(#pattern => #code));
+        https.push(quote!(#pattern => #http));
+        msgs.push(quote!(#pattern => #msg));
+        entries.push(quote! {
+            ::errlog::__private::inventory::submit! {
+                ::errlog::CodeEntry {
+                    code: #code,
+                    http: #http,
+                    msg: #msg,
+                    type_name: concat!(module_path!(), "::", stringify!(#name)),
+                }
+            }
+        });
+    }
+
+    Ok(quote! {
+        impl ::errlog::ErrorCode for #name {
+            fn code(&self) -> &'static str {
+                match *self {
+                    #(#codes,)*
+                }
+            }
+
+            fn http_status(&self) -> Option<u16> {
+                match *self {
+                    #(#https,)*
+                }
+            }
+
+            fn message(&self) -> &'static str {
+                match *self {
+                    #(#msgs,)*
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for #name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                f.write_str(::errlog::ErrorCode::message(self))
+            }
+        }
+
+        impl ::std::error::Error for #name {}
+
+        const _: () = {
+            #(#entries)*
+
+            ::errlog::__private::inventory::submit! {
+                ::errlog::__private::CodeLookup(::errlog::__private::lookup_code::<#name>)
+            }
+        };
+    })
+}
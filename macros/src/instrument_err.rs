@@ -0,0 +1,52 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::ItemFn;
+
+use crate::util;
+
+pub fn expand(attr: TokenStream, func: ItemFn) -> syn::Result<TokenStream> {
+    if !attr.is_empty() {
+        return Err(syn::Error::new_spanned(attr, "instrument_err does not take any argument"));
+    }
+    let ItemFn { attrs, vis, sig, block } = func;
+    let name = sig.ident.to_string();
+    let ret = util::result_type(&sig, "instrument_err")?;
+    let ret = quote!(#ret);
+
+    let call = if sig.asyncness.is_some() {
+        let body = util::async_body(&block, &ret, quote!(move));
+        quote! {
+            ::errlog::tracing::Instrument::instrument(#body, __errlog_span.clone()).await
+        }
+    } else {
+        quote! {
+            __errlog_span.in_scope(|| -> #ret #block)
+        }
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            let __errlog_span = ::errlog::tracing::info_span!(
+                #name,
+                error = ::errlog::tracing::field::Empty,
+                error.code = ::errlog::tracing::field::Empty,
+                error.location = ::errlog::tracing::field::Empty,
+            );
+            let __errlog_result: #ret = #call;
+            if let Err(__errlog_err) = &__errlog_result {
+                let __errlog_chain = ::errlog::__private::record_err(
+                    &__errlog_span,
+                    __errlog_err,
+                    ::errlog::__callsite!(),
+                );
+                if !::errlog::__private::SILENT {
+                    __errlog_span.in_scope(|| {
+                        ::errlog::tracing::error!("{}", __errlog_chain);
+                    });
+                }
+            }
+            __errlog_result
+        }
+    })
+}
@@ -0,0 +1,61 @@
+//! Procedural macros for errlog, use them through the re-exports of errlog
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, ItemFn};
+
+mod error_code;
+mod instrument_err;
+mod log_slow;
+mod retry;
+mod test;
+mod util;
+
+/// See `errlog::instrument_err` for documentation
+#[proc_macro_attribute]
+pub fn instrument_err(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    match instrument_err::expand(attr.into(), func) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// See `errlog::log_slow` for documentation
+#[proc_macro_attribute]
+pub fn log_slow(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    match log_slow::expand(attr.into(), func) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// See `errlog::retry` for documentation
+#[proc_macro_attribute]
+pub fn retry(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    match retry::expand(attr.into(), func) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// See `errlog::test` for documentation
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let func = parse_macro_input!(item as ItemFn);
+    match test::expand(attr.into(), func) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// See `errlog::ErrorCode` for documentation
+#[proc_macro_derive(ErrorCode, attributes(code))]
+pub fn derive_error_code(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    match error_code::expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
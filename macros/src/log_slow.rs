@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::Parser;
+use syn::{FnArg, Ident, ItemFn, LitStr, Pat};
+
+use crate::util;
+
+pub fn expand(attr: TokenStream, func: ItemFn) -> syn::Result<TokenStream> {
+    let mut threshold = None;
+    let mut level = quote!(::errlog::tracing::Level::WARN);
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("threshold") {
+            let lit: LitStr = meta.value()?.parse()?;
+            threshold = Some(util::parse_duration(&lit)?);
+            Ok(())
+        } else if meta.path.is_ident("level") {
+            let ident: Ident = meta.value()?.parse()?;
+            level = util::parse_level(&ident)?;
+            Ok(())
+        } else {
+            Err(meta.error("expected `threshold` or `level`"))
+        }
+    });
+    parser.parse2(attr)?;
+    let threshold = util::duration_tokens(threshold.unwrap_or(Duration::from_millis(100)));
+
+    let ItemFn { attrs, vis, sig, block } = func;
+    let name = sig.ident.to_string();
+    let ret = util::return_type(&sig);
+    let call = util::invoke_body(&sig, &block);
+
+    let mut args = vec![];
+    for input in &sig.inputs {
+        match input {
+            FnArg::Receiver(_) => {}
+            FnArg::Typed(arg) => {
+                if let Pat::Ident(pat) = &*arg.pat {
+                    let ident = &pat.ident;
+                    let label = format!("{} = ", ident);
+                    args.push(quote! {
+                        __errlog_args.push(format!("{}{}", #label, (&::errlog::__private::ArgWrap(&#ident)).summary()));
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            #[allow(unused_imports)]
+            use ::errlog::__private::{DebugArg as _, OpaqueArg as _};
+            let __errlog_args = if !::errlog::__private::SILENT && ::errlog::tracing::enabled!(#level) {
+                let mut __errlog_args: Vec<String> = vec![];
+                #(#args)*
+                Some(__errlog_args.join(", "))
+            } else {
+                None
+            };
+            let __errlog_start = ::std::time::Instant::now();
+            let __errlog_result: #ret = #call;
+            let __errlog_elapsed = __errlog_start.elapsed();
+            let __errlog_threshold = #threshold;
+            if __errlog_elapsed > __errlog_threshold {
+                if let Some(__errlog_args) = __errlog_args {
+                    ::errlog::tracing::event!(
+                        #level,
+                        function = #name,
+                        elapsed_ms = __errlog_elapsed.as_millis() as u64,
+                        "{}({}) took {:?}, exceeding {:?}",
+                        #name,
+                        __errlog_args,
+                        __errlog_elapsed,
+                        __errlog_threshold,
+                    );
+                }
+            }
+            __errlog_result
+        }
+    })
+}
@@ -0,0 +1,71 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::parse::Parser;
+use syn::{ItemFn, LitInt, LitStr, Path};
+
+use crate::util;
+
+/// Parse `exp(100ms)`, `fixed(100ms)` or a bare `100ms`
+fn parse_backoff(lit: &LitStr) -> syn::Result<TokenStream> {
+    let value = lit.value();
+    let value = value.trim();
+    let (kind, duration) = match value.split_once('(') {
+        Some((kind, rest)) => match rest.strip_suffix(')') {
+            Some(duration) => (kind.trim(), duration),
+            None => return Err(syn::Error::new_spanned(lit, "missing `)` in backoff")),
+        },
+        None => ("fixed", value),
+    };
+    let duration = util::duration_tokens(util::parse_duration(&LitStr::new(duration, lit.span()))?);
+    match kind {
+        "fixed" => Ok(quote!(::errlog::retry::Backoff::Fixed(#duration))),
+        "exp" => Ok(quote!(::errlog::retry::Backoff::Exponential(#duration))),
+        _ => Err(syn::Error::new_spanned(lit, "expected a backoff such as \"exp(100ms)\" or \"fixed(100ms)\"")),
+    }
+}
+
+pub fn expand(attr: TokenStream, func: ItemFn) -> syn::Result<TokenStream> {
+    let mut times = 3u32;
+    let mut backoff = quote!(::errlog::retry::Backoff::Fixed(::std::time::Duration::ZERO));
+    let mut retry_if = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("times") {
+            let lit: LitInt = meta.value()?.parse()?;
+            times = lit.base10_parse()?;
+            if times == 0 {
+                return Err(syn::Error::new_spanned(lit, "times must be at least 1"));
+            }
+            Ok(())
+        } else if meta.path.is_ident("backoff") {
+            let lit: LitStr = meta.value()?.parse()?;
+            backoff = parse_backoff(&lit)?;
+            Ok(())
+        } else if meta.path.is_ident("retry_if") {
+            let path: Path = meta.value()?.parse()?;
+            retry_if = Some(quote!(.retry_if(#path)));
+            Ok(())
+        } else {
+            Err(meta.error("expected `times`, `backoff` or `retry_if`"))
+        }
+    });
+    parser.parse2(attr)?;
+
+    let ItemFn { attrs, vis, sig, block } = func;
+    let ret = util::result_type(&sig, "retry")?;
+    let policy = quote!(::errlog::retry::Policy::new(#times, #backoff)#retry_if);
+    // the calls are located at the attribute, which the logged attempts point to
+    let body = match sig.asyncness {
+        Some(_) => {
+            let body = util::async_body(&block, &quote!(#ret), quote!());
+            quote!(::errlog::retry_with_log_async(#policy, || #body).await)
+        }
+        None => quote!(::errlog::retry_with_log(#policy, || -> #ret #block)),
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            #body
+        }
+    })
+}
@@ -0,0 +1,33 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::ItemFn;
+
+use crate::util;
+
+pub fn expand(attr: TokenStream, func: ItemFn) -> syn::Result<TokenStream> {
+    if !attr.is_empty() {
+        return Err(syn::Error::new_spanned(attr, "errlog::test takes no arguments"));
+    }
+    if !func.sig.inputs.is_empty() {
+        return Err(syn::Error::new_spanned(&func.sig.inputs, "test functions take no arguments"));
+    }
+
+    let ItemFn { attrs, vis, mut sig, block } = func;
+    let ret = util::return_type(&sig);
+    let call = if sig.asyncness.take().is_some() {
+        let body = util::async_body(&block, &ret, quote!());
+        quote!(::errlog::__private::block_on(#body))
+    } else {
+        quote!((|| -> #ret #block)())
+    };
+
+    Ok(quote! {
+        #[::core::prelude::v1::test]
+        #(#attrs)*
+        #vis #sig {
+            let __errlog_guard = ::errlog::__private::TestGuard::new(::errlog::test::capture());
+            let __errlog_result: #ret = #call;
+            __errlog_guard.finish(__errlog_result)
+        }
+    })
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Block, Ident, LitStr, ReturnType, Signature, Type};
+
+/// The return type of the function, `()` if absent
+pub fn return_type(sig: &Signature) -> TokenStream {
+    match &sig.output {
+        ReturnType::Type(_, ty) => quote!(#ty),
+        ReturnType::Default => quote!(()),
+    }
+}
+
+/// The return type of a function that must return a `Result`
+pub fn result_type<'a>(sig: &'a Signature, attr: &str) -> syn::Result<&'a Type> {
+    match &sig.output {
+        ReturnType::Type(_, ty) => Ok(ty),
+        ReturnType::Default => Err(syn::Error::new_spanned(
+            sig,
+            format!("{} requires a function returning errlog::Result", attr),
+        )),
+    }
+}
+
+/// An async block running the function body
+///
+/// The dead `if false` branch pins the return type of the block, otherwise `?` in the body
+/// cannot infer its error type.
+pub fn async_body(block: &Block, ret: &TokenStream, capture: TokenStream) -> TokenStream {
+    quote! {
+        async #capture {
+            #[allow(unreachable_code)]
+            if false {
+                let __errlog_fake_return: #ret = loop {};
+                return __errlog_fake_return;
+            }
+            #block
+        }
+    }
+}
+
+/// An expression evaluating the function body, awaited if the function is async
+pub fn invoke_body(sig: &Signature, block: &Block) -> TokenStream {
+    let ret = return_type(sig);
+    if sig.asyncness.is_some() {
+        let body = async_body(block, &ret, quote!());
+        quote!(#body.await)
+    } else {
+        quote!((|| -> #ret #block)())
+    }
+}
+
+/// Map `TRACE`, `DEBUG`, `INFO`, `WARN` and `ERROR` to the tracing level
+pub fn parse_level(level: &Ident) -> syn::Result<TokenStream> {
+    match level.to_string().as_str() {
+        "TRACE" | "DEBUG" | "INFO" | "WARN" | "ERROR" => Ok(quote!(::errlog::tracing::Level::#level)),
+        _ => Err(syn::Error::new_spanned(level, "expected one of TRACE, DEBUG, INFO, WARN, ERROR")),
+    }
+}
+
+/// Parse durations such as `250ms`, `1.5s` or `2m`
+pub fn parse_duration(lit: &LitStr) -> syn::Result<Duration> {
+    let value = lit.value();
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| syn::Error::new_spanned(lit, "expected a duration such as \"250ms\""))?;
+    let secs = match unit.trim() {
+        "ns" => number / 1e9,
+        "us" => number / 1e6,
+        "ms" => number / 1e3,
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(syn::Error::new_spanned(lit, "expected a unit of ns, us, ms, s, m or h")),
+    };
+    Ok(Duration::from_secs_f64(secs))
+}
+
+/// Tokens building the duration at runtime
+pub fn duration_tokens(duration: Duration) -> TokenStream {
+    let nanos = duration.as_nanos() as u64;
+    quote!(::std::time::Duration::from_nanos(#nanos))
+}
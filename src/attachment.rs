@@ -0,0 +1,67 @@
+//! Typed values attached to errors
+
+use std::fmt;
+
+use crate::{Error, Result};
+
+/// A value attached by [Attach], rendering as an empty string like [Code](crate::Code)
+struct Attachment<A>(A);
+
+impl<A> fmt::Display for Attachment<A> {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+impl<A> fmt::Debug for Attachment<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Attachment<{}>", std::any::type_name::<A>())
+    }
+}
+
+/// Attach any value to an error, to read it back upstream with [get_attachment]
+///
+///     use errlog::{Attach, Result};
+///
+///     struct RequestSnapshot {
+///         path: String,
+///         retries: u32,
+///     }
+///
+///     fn handle() -> Result<()> {
+///         Err(errlog::Error::msg("upstream timed out"))
+///             .attach(RequestSnapshot { path: "/orders".to_string(), retries: 2 })
+///     }
+///
+///     let err = handle().unwrap_err();
+///     let snapshot = errlog::get_attachment::<RequestSnapshot>(&err).unwrap();
+///     assert_eq!((snapshot.path.as_str(), snapshot.retries), ("/orders", 2));
+///
+/// Unlike downcasting the root error, the value can be attached at any level of the chain, and
+/// any number of values of different types can be attached.
+pub trait Attach: Sized {
+    type Output;
+
+    fn attach<A: Send + Sync + 'static>(self, value: A) -> Self::Output;
+}
+
+impl Attach for Error {
+    type Output = Error;
+
+    fn attach<A: Send + Sync + 'static>(self, value: A) -> Error {
+        crate::__private::context(self, Attachment(value))
+    }
+}
+
+impl<T, E: Into<Error>> Attach for std::result::Result<T, E> {
+    type Output = Result<T>;
+
+    fn attach<A: Send + Sync + 'static>(self, value: A) -> Result<T> {
+        self.map_err(|err| err.into().attach(value))
+    }
+}
+
+/// The outermost value of type `A` attached to the error
+pub fn get_attachment<A: Send + Sync + 'static>(err: &Error) -> Option<&A> {
+    err.downcast_ref::<Attachment<A>>().map(|attachment| &attachment.0)
+}
@@ -0,0 +1,211 @@
+//! Error budgets: in-process alarms on the rate of warnings and errors of a scope
+//!
+//!     use std::sync::mpsc;
+//!     use std::time::{Duration, UNIX_EPOCH};
+//!
+//!     use errlog::clock::MockClock;
+//!     use errlog::logger;
+//!
+//!     let clock = MockClock::new(UNIX_EPOCH);
+//!     let _guard = logger::builder().clock(clock.clone()).set_default();
+//!     let (sender, alarm) = mpsc::channel();
+//!     let budget = errlog::budget("payment", 2, Duration::from_secs(60), move || {
+//!         let _ = sender.send(());
+//!     });
+//!
+//!     for _ in 0..3 {
+//!         errlog::logmsg!(WARN, scope = "payment", "card declined");
+//!     }
+//!     # #[cfg(not(feature = "silent"))]
+//!     alarm.recv_timeout(Duration::from_secs(5)).unwrap();
+//!     # #[cfg(not(feature = "silent"))]
+//!     assert_eq!(budget.count(), 3);
+//!
+//!     clock.advance(Duration::from_secs(60));
+//!     errlog::logmsg!(WARN, scope = "payment", "card declined");
+//!     # #[cfg(not(feature = "silent"))]
+//!     assert_eq!(budget.count(), 1);
+//!
+//! Events count against the budget named by their `scope` field. The [logger](crate::logger)
+//! tracks budgets on its own, timing the windows with its [clock](crate::logger::Builder::clock),
+//! other subscribers need the [layer].
+
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use tracing::field::{Field, Visit};
+use tracing::{Dispatch, Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context as LayerContext, Layer};
+
+use crate::clock::{Clock, SystemClock};
+
+/// The field naming the budget an event counts against
+pub const SCOPE_FIELD: &str = "scope";
+
+struct Window {
+    /// Set by the first event
+    start: Option<SystemTime>,
+    count: usize,
+    fired: bool,
+}
+
+struct Inner {
+    scope: String,
+    limit: usize,
+    period: Duration,
+    window: Mutex<Window>,
+    on_exceeded: Box<dyn Fn() + Send + Sync>,
+}
+
+/// A registered budget, see [budget]
+#[derive(Clone)]
+pub struct Budget(Arc<Inner>);
+
+impl Budget {
+    pub fn scope(&self) -> &str {
+        &self.0.scope
+    }
+
+    /// How many events were counted in the current window
+    pub fn count(&self) -> usize {
+        self.0.window.lock().unwrap_or_else(|err| err.into_inner()).count
+    }
+
+    /// Count one event at `now`, telling whether it is the one exceeding the budget
+    fn hit(&self, now: SystemTime) -> Option<usize> {
+        let mut window = self.0.window.lock().unwrap_or_else(|err| err.into_inner());
+        // a clock set back starts a new window too
+        let ended = window.start.is_none_or(|start| now.duration_since(start).map_or(true, |elapsed| elapsed >= self.0.period));
+        if ended {
+            *window = Window { start: Some(now), count: 0, fired: false };
+        }
+        window.count += 1;
+        if window.count > self.0.limit && !window.fired {
+            window.fired = true;
+            return Some(window.count);
+        }
+        None
+    }
+}
+
+/// The budgets still having a handle
+static BUDGETS: RwLock<Vec<Weak<Inner>>> = RwLock::new(Vec::new());
+
+/// Register a budget of `limit` `WARN` and `ERROR` events of `scope` per `window`
+///
+/// The first event exceeding the budget calls `on_exceeded` once and logs a summary at `ERROR`,
+/// then the budget is armed again when the window ends. Since subscribers cannot log while they
+/// handle an event, both happen on a short-lived thread, so `on_exceeded` may log itself.
+/// Events count against the budget as long as the returned handle or a clone of it is kept.
+pub fn budget(
+    scope: &str,
+    limit: usize,
+    window: Duration,
+    on_exceeded: impl Fn() + Send + Sync + 'static,
+) -> Budget {
+    let budget = Budget(Arc::new(Inner {
+        scope: scope.to_string(),
+        limit,
+        period: window,
+        window: Mutex::new(Window { start: None, count: 0, fired: false }),
+        on_exceeded: Box::new(on_exceeded),
+    }));
+    let mut budgets = BUDGETS.write().unwrap_or_else(|err| err.into_inner());
+    budgets.retain(|budget| budget.strong_count() > 0);
+    budgets.push(Arc::downgrade(&budget.0));
+    budget
+}
+
+#[derive(Default)]
+struct ScopeVisitor(Option<String>);
+
+impl Visit for ScopeVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == SCOPE_FIELD {
+            self.0 = Some(format!("{:?}", value).trim_matches('"').to_string());
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == SCOPE_FIELD {
+            self.0 = Some(value.to_string());
+        }
+    }
+}
+
+/// The layer counting events against the registered budgets
+pub struct BudgetLayer {
+    dispatch: Mutex<Option<tracing::dispatcher::WeakDispatch>>,
+    clock: Arc<dyn Clock>,
+}
+
+/// A layer counting events against the registered budgets
+pub fn layer() -> BudgetLayer {
+    BudgetLayer { dispatch: Mutex::default(), clock: Arc::new(SystemClock) }
+}
+
+impl BudgetLayer {
+    /// Time the windows of the budgets with `clock`, the system clock by default
+    pub fn clock(self, clock: impl Clock) -> Self {
+        self.shared_clock(Arc::new(clock))
+    }
+
+    pub(crate) fn shared_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+impl<S: Subscriber> Layer<S> for BudgetLayer {
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        *self.dispatch.lock().unwrap_or_else(|err| err.into_inner()) = Some(subscriber.downgrade());
+    }
+
+    fn on_event(&self, event: &Event<'_>, _: LayerContext<'_, S>) {
+        if *event.metadata().level() > Level::WARN {
+            return;
+        }
+        if event.fields().all(|field| field.name() != SCOPE_FIELD) {
+            return;
+        }
+        let mut visitor = ScopeVisitor::default();
+        event.record(&mut visitor);
+        let Some(scope) = visitor.0 else { return };
+        let budgets: Vec<Budget> = BUDGETS
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .filter_map(|budget| budget.upgrade().map(Budget))
+            .filter(|budget| budget.0.scope == scope)
+            .collect();
+        if budgets.is_empty() {
+            return;
+        }
+        let now = self.clock.now();
+        for budget in budgets {
+            if let Some(count) = budget.hit(now) {
+                let dispatch = self.dispatch.lock().unwrap_or_else(|err| err.into_inner()).clone();
+                let _ = std::thread::Builder::new().name("errlog-budget".to_string()).spawn(move || {
+                    let alarm = || {
+                        if !crate::__private::SILENT {
+                            tracing::error!(
+                                budget = budget.0.scope.as_str(),
+                                count,
+                                limit = budget.0.limit,
+                                "error budget of {} exceeded: {} events within {:?}",
+                                budget.0.scope,
+                                count,
+                                budget.0.period,
+                            );
+                        }
+                        (budget.0.on_exceeded)();
+                    };
+                    match dispatch.and_then(|dispatch| dispatch.upgrade()) {
+                        Some(dispatch) => tracing::dispatcher::with_default(&dispatch, alarm),
+                        None => alarm(),
+                    }
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! A single zip file gathering what a bug report needs
+//!
+//!     use errlog::logger::MemoryWriter;
+//!
+//!     # let dir = std::env::temp_dir().join(format!("errlog-bundle-{}", std::process::id()));
+//!     # std::fs::create_dir_all(&dir).unwrap();
+//!     let output = MemoryWriter::new();
+//!     errlog::support_bundle(dir.join("support.zip"))
+//!         .file("/var/log/app.log")
+//!         .dir(dir.join("crashes"))
+//!         .text("recent.log", &output.contents())
+//!         .write()
+//!         .unwrap();
+//!     # assert!(std::fs::read(dir.join("support.zip")).unwrap().starts_with(b"PK"));
+//!     # std::fs::remove_dir_all(&dir).unwrap();
+//!
+//! The bundle always holds a `manifest.txt` with the creation time, the errlog version, the
+//! platform, the executable and its arguments, and the entries that could not be read, so a
+//! missing log file does not prevent the bundle from being written.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{Context, Result};
+
+enum Source {
+    File(PathBuf),
+    Dir(PathBuf),
+    Text(String, String),
+}
+
+/// The contents of a support bundle, see [support_bundle]
+pub struct SupportBundle {
+    path: PathBuf,
+    sources: Vec<Source>,
+}
+
+/// Start a support bundle written to `path`
+pub fn support_bundle(path: impl AsRef<Path>) -> SupportBundle {
+    SupportBundle { path: path.as_ref().to_path_buf(), sources: vec![] }
+}
+
+impl SupportBundle {
+    /// Add a file, such as a log file, under `files/`
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.sources.push(Source::File(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Add the files of a directory, such as the crash reports, under the name of the directory
+    pub fn dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.sources.push(Source::Dir(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Add `contents` as the file `name`, such as the records kept by a
+    /// [MemoryWriter](crate::logger::MemoryWriter)
+    pub fn text(mut self, name: &str, contents: &str) -> Self {
+        self.sources.push(Source::Text(name.to_string(), contents.to_string()));
+        self
+    }
+
+    /// Write the zip file
+    pub fn write(self) -> Result<()> {
+        let mut zip = Zip::default();
+        let mut missing = vec![];
+        for source in &self.sources {
+            match source {
+                Source::File(path) => {
+                    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                    match fs::read(path) {
+                        Ok(data) => zip.add(&format!("files/{}", name), &data),
+                        Err(err) => missing.push(format!("{}: {}", path.display(), err)),
+                    }
+                }
+                Source::Dir(path) => {
+                    let root = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                    add_dir(&mut zip, path, &root, &mut missing);
+                }
+                Source::Text(name, contents) => zip.add(name, contents.as_bytes()),
+            }
+        }
+        zip.add("manifest.txt", manifest(&missing).as_bytes());
+        let path = &self.path;
+        fs::write(path, zip.finish()).with_context(|| format!("failed to write the support bundle {}", path.display()))
+    }
+}
+
+fn add_dir(zip: &mut Zip, dir: &Path, prefix: &str, missing: &mut Vec<String>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => return missing.push(format!("{}: {}", dir.display(), err)),
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths {
+        let name = format!("{}/{}", prefix, path.file_name().unwrap_or_default().to_string_lossy());
+        if path.is_dir() {
+            add_dir(zip, &path, &name, missing);
+            continue;
+        }
+        match fs::read(&path) {
+            Ok(data) => zip.add(&name, &data),
+            Err(err) => missing.push(format!("{}: {}", path.display(), err)),
+        }
+    }
+}
+
+fn manifest(missing: &[String]) -> String {
+    let mut manifest = format!(
+        "created: {}\nerrlog: {}\nos: {}\narch: {}\nexecutable: {}\nargs: {}\n",
+        crate::logger::rfc3339(SystemTime::now()),
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::env::current_exe().map(|exe| exe.display().to_string()).unwrap_or_default(),
+        crate::report::args().into_iter().skip(1).collect::<Vec<_>>().join(" "),
+    );
+    for entry in missing {
+        manifest.push_str(&format!("missing: {}\n", entry));
+    }
+    manifest
+}
+
+/// A zip archive of deflated entries, without timestamps nor zip64 support
+#[derive(Default)]
+struct Zip {
+    data: Vec<u8>,
+    directory: Vec<u8>,
+    entries: u16,
+}
+
+impl Zip {
+    fn add(&mut self, name: &str, contents: &[u8]) {
+        let crc = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC).checksum(contents);
+        let compressed = miniz_oxide::deflate::compress_to_vec(contents, 6);
+        let offset = self.data.len() as u32;
+        // version, UTF-8 names, deflate, 1980-01-01 00:00
+        let common = [&20u16.to_le_bytes()[..], &0x0800u16.to_le_bytes(), &8u16.to_le_bytes(), &0u16.to_le_bytes(), &0x21u16.to_le_bytes()]
+            .concat();
+        let sizes = [
+            &crc.to_le_bytes()[..],
+            &(compressed.len() as u32).to_le_bytes(),
+            &(contents.len() as u32).to_le_bytes(),
+            &(name.len() as u16).to_le_bytes(),
+            &0u16.to_le_bytes(),
+        ]
+        .concat();
+        self.data.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        self.data.extend_from_slice(&common);
+        self.data.extend_from_slice(&sizes);
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(&compressed);
+        self.directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+        self.directory.extend_from_slice(&20u16.to_le_bytes());
+        self.directory.extend_from_slice(&common);
+        self.directory.extend_from_slice(&sizes);
+        // comment, disk, attributes
+        self.directory.extend_from_slice(&[0; 10]);
+        self.directory.extend_from_slice(&offset.to_le_bytes());
+        self.directory.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let offset = self.data.len() as u32;
+        let size = self.directory.len() as u32;
+        self.data.append(&mut self.directory);
+        self.data.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        self.data.extend_from_slice(&[0; 4]);
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&size.to_le_bytes());
+        self.data.extend_from_slice(&offset.to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes());
+        self.data
+    }
+}
@@ -0,0 +1,103 @@
+//! Whether failures are worth retrying
+
+use std::fmt;
+use std::io::ErrorKind;
+
+use crate::{Error, Result};
+
+/// Whether retrying the failed operation may succeed, see [classify]
+///
+/// Like [Severity](crate::Severity), it renders as an empty string once attached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    /// The failure may go away, such as a timeout or a refused connection
+    Transient,
+    /// Retrying fails the same way, such as a missing file or an invalid input
+    Permanent,
+    /// Nothing tells
+    Unknown,
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Classify the error of a result from where the failure is understood
+///
+///     use errlog::{Class, ClassExt, Result};
+///
+///     fn charge(card: &str) -> Result<()> {
+///         Err(errlog::Error::msg(format!("card {} declined", card))).with_class(Class::Permanent)
+///     }
+///
+///     assert_eq!(errlog::classify(&charge("visa").unwrap_err()), Class::Permanent);
+pub trait ClassExt<T> {
+    fn with_class(self, class: Class) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> ClassExt<T> for std::result::Result<T, E> {
+    fn with_class(self, class: Class) -> Result<T> {
+        self.map_err(|err| crate::__private::context(err.into(), class))
+    }
+}
+
+/// The outermost class attached with [ClassExt], or the one inferred from the chain
+///
+///     use errlog::{Class, Context};
+///     use std::io::{Error, ErrorKind};
+///
+///     let err = Err::<(), _>(Error::from(ErrorKind::TimedOut)).context("failed to fetch").unwrap_err();
+///     assert_eq!(errlog::classify(&err), Class::Transient);
+///     assert_eq!(errlog::classify(&errlog::Error::msg("bad input")), Class::Unknown);
+///
+/// Timeouts, interruptions, connection failures and early EOFs of io errors are transient, while missing
+/// files, denied permissions and invalid data are permanent. With the `reqwest` feature, timeouts,
+/// connection failures and the `429` and `5xx` statuses of requests are transient, the other
+/// `4xx` statuses permanent. With the `tokio` feature, elapsed timeouts are transient.
+pub fn classify(err: &Error) -> Class {
+    if let Some(class) = err.downcast_ref::<Class>() {
+        return *class;
+    }
+    crate::chain(err).map(infer).find(|class| *class != Class::Unknown).unwrap_or(Class::Unknown)
+}
+
+pub(crate) fn infer(cause: &(dyn std::error::Error + 'static)) -> Class {
+    if let Some(err) = cause.downcast_ref::<std::io::Error>() {
+        return match err.kind() {
+            ErrorKind::TimedOut
+            | ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::UnexpectedEof => Class::Transient,
+            ErrorKind::NotFound
+            | ErrorKind::PermissionDenied
+            | ErrorKind::AlreadyExists
+            | ErrorKind::InvalidInput
+            | ErrorKind::InvalidData
+            | ErrorKind::Unsupported => Class::Permanent,
+            _ => Class::Unknown,
+        };
+    }
+    #[cfg(feature = "reqwest")]
+    if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+        if err.is_timeout() || err.is_connect() {
+            return Class::Transient;
+        }
+        return match err.status() {
+            Some(status) if status.as_u16() == 429 || status.is_server_error() => Class::Transient,
+            Some(status) if status.is_client_error() => Class::Permanent,
+            _ => Class::Unknown,
+        };
+    }
+    #[cfg(feature = "tokio")]
+    if cause.is::<tokio::time::error::Elapsed>() {
+        return Class::Transient;
+    }
+    Class::Unknown
+}
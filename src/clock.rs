@@ -0,0 +1,60 @@
+//! Time sources for the timestamps written by the [logger](crate::logger)
+//!
+//!     use std::time::{Duration, UNIX_EPOCH};
+//!
+//!     use errlog::clock::MockClock;
+//!     use errlog::logger::{self, MemoryWriter};
+//!
+//!     let clock = MockClock::new(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+//!     let output = MemoryWriter::new();
+//!     let _guard = logger::builder().clock(clock.clone()).writer(output.clone()).set_default();
+//!
+//!     tracing::info!("first");
+//!     clock.advance(Duration::from_millis(1500));
+//!     tracing::info!("second");
+//!
+//!     let lines: Vec<String> = output.contents().lines().map(String::from).collect();
+//!     assert!(lines[0].starts_with("2023-11-14T22:13:20.000000Z"));
+//!     assert!(lines[1].starts_with("2023-11-14T22:13:21.500000Z"));
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// A source of the current time
+pub trait Clock: Send + Sync + 'static {
+    fn now(&self) -> SystemTime;
+}
+
+/// The wall clock, [SystemTime::now]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock which only moves when told to, clones share the same time
+#[derive(Debug, Clone)]
+pub struct MockClock(Arc<Mutex<SystemTime>>);
+
+impl MockClock {
+    pub fn new(start: SystemTime) -> Self {
+        MockClock(Arc::new(Mutex::new(start)))
+    }
+
+    pub fn set(&self, time: SystemTime) {
+        *self.0.lock().unwrap_or_else(|err| err.into_inner()) = time;
+    }
+
+    pub fn advance(&self, delta: Duration) {
+        *self.0.lock().unwrap_or_else(|err| err.into_inner()) += delta;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
@@ -0,0 +1,105 @@
+//! Stable error codes attached to errors
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use crate::Error;
+
+/// A stable, machine-readable error code attached to an error
+///
+/// Attach it as a context and read it back with [error_code]
+///
+///     use errlog::{Code, Context, Result};
+///
+///     fn find_user() -> Result<()> {
+///         Err(errlog::Error::msg("no such user")).context(Code("E1001"))
+///     }
+///
+///     assert_eq!(errlog::error_code(&find_user().unwrap_err()), Some("E1001"));
+///
+/// The code renders as an empty string, so it never shows up in [backtrace_anyhow](crate::backtrace_anyhow).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Code(pub &'static str);
+
+impl fmt::Display for Code {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Error types carrying a stable error code, usually implemented by `#[derive(ErrorCode)]`
+///
+/// The derive macro takes a `#[code(...)]` attribute on every variant, with the code first and
+/// optional `http` status and `msg` text, then generates this trait, `Display` (the `msg` text, or
+/// the code if absent) and `std::error::Error`, and registers every variant into [code_catalog]
+///
+///     use errlog::ErrorCode;
+///
+///     #[derive(Debug, ErrorCode)]
+///     enum UserError {
+///         #[code("E1001", http = 404, msg = "user not found")]
+///         NotFound { id: u64 },
+///         #[code("E1002", http = 409)]
+///         Conflict(String),
+///     }
+///
+///     let err = errlog::Error::new(UserError::NotFound { id: 42 });
+///     assert_eq!(errlog::error_code(&err), Some("E1001"));
+///     assert_eq!(err.to_string(), "user not found");
+///     assert!(errlog::code_catalog().any(|entry| entry.code == "E1002" && entry.http == Some(409)));
+pub trait ErrorCode {
+    /// The stable error code such as `E1001`
+    fn code(&self) -> &'static str;
+
+    /// The HTTP status suggested for this error
+    fn http_status(&self) -> Option<u16> {
+        None
+    }
+
+    /// The human readable message of this error
+    fn message(&self) -> &'static str;
+}
+
+/// One entry of the error code catalog
+#[derive(Debug)]
+pub struct CodeEntry {
+    pub code: &'static str,
+    pub http: Option<u16>,
+    pub msg: &'static str,
+    /// Full path of the error type defining the code
+    pub type_name: &'static str,
+}
+
+inventory::collect!(CodeEntry);
+
+/// Iterate over all error codes registered by `#[derive(ErrorCode)]` in the program
+pub fn code_catalog() -> impl Iterator<Item = &'static CodeEntry> {
+    inventory::iter::<CodeEntry>.into_iter()
+}
+
+#[doc(hidden)]
+pub struct CodeLookup(pub for<'a> fn(&'a (dyn StdError + 'static)) -> Option<&'a dyn ErrorCode>);
+
+inventory::collect!(CodeLookup);
+
+#[doc(hidden)]
+pub fn lookup_code<'a, T: ErrorCode + StdError + 'static>(err: &'a (dyn StdError + 'static)) -> Option<&'a dyn ErrorCode> {
+    err.downcast_ref::<T>().map(|err| err as &dyn ErrorCode)
+}
+
+/// Find the first error in the chain implementing [ErrorCode] through `#[derive(ErrorCode)]`
+pub fn find_error_code(err: &Error) -> Option<&dyn ErrorCode> {
+    crate::chain(err)
+        .find_map(|cause| inventory::iter::<CodeLookup>.into_iter().find_map(|lookup| (lookup.0)(cause)))
+}
+
+/// Get the error code of the error chain
+///
+/// An explicitly attached [Code] wins, otherwise the code of the first error in the chain deriving
+/// [ErrorCode] is returned.
+pub fn error_code(err: &Error) -> Option<&'static str> {
+    if let Some(code) = err.downcast_ref::<Code>() {
+        return Some(code.0);
+    }
+    find_error_code(err).map(|code| code.code())
+}
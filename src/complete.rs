@@ -0,0 +1,68 @@
+//! Guards telling when work is abandoned halfway
+
+use std::panic::Location;
+
+/// Logs a warning when dropped before [done](MustComplete::done), see [must_complete]
+#[must_use = "the warning is logged as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct MustComplete {
+    name: String,
+    location: &'static Location<'static>,
+    done: bool,
+}
+
+impl MustComplete {
+    /// Mark the work as completed
+    pub fn done(mut self) {
+        self.done = true;
+    }
+}
+
+impl Drop for MustComplete {
+    fn drop(&mut self) {
+        if self.done || crate::__private::SILENT {
+            return;
+        }
+        let reason = match std::thread::panicking() {
+            true => "abandoned by a panic",
+            false => "dropped without completing",
+        };
+        tracing::warn!(
+            must_complete = self.name.as_str(),
+            "{}:{} => {} {}",
+            self.location.file(),
+            self.location.line(),
+            self.name,
+            reason,
+        );
+    }
+}
+
+/// Create a guard logging a `WARN` if dropped before [done](MustComplete::done) is called
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::Result;
+///
+///     fn flush(entries: &[u8]) -> Result<()> {
+///         let task = errlog::must_complete("flush wal");
+///         if entries.is_empty() {
+///             return Ok(());
+///         }
+///         task.done();
+///         Ok(())
+///     }
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///     flush(b"entry").unwrap();
+///     assert!(output.contents().is_empty());
+///     flush(b"").unwrap();
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("flush wal dropped without completing"));
+///
+/// The event carries the location where the guard was created and the `must_complete` field,
+/// catching early returns and `?` which skip an acknowledgement or a cleanup step.
+#[track_caller]
+pub fn must_complete(name: &str) -> MustComplete {
+    MustComplete { name: name.to_string(), location: Location::caller(), done: false }
+}
@@ -0,0 +1,166 @@
+//! Diagnostic context of the current thread, like the MDC of log4j
+//!
+//!     use errlog::logger::{self, MemoryWriter};
+//!     use errlog::{context, Context};
+//!
+//!     let output = MemoryWriter::new();
+//!     let _guard = logger::builder().writer(output.clone()).set_default();
+//!
+//!     fn charge() -> errlog::Result<()> {
+//!         Err(errlog::Error::msg("card declined")).context("failed to charge")
+//!     }
+//!
+//!     let _order = context::push("order_id", 42);
+//!     errlog::logmsg!(INFO, "charging");
+//!     let err = errlog::wraperr!(charge()).unwrap_err();
+//!
+//!     assert_eq!(context::fields(&err), Some(&[("order_id".to_string(), "42".to_string())][..]));
+//!     # #[cfg(not(feature = "silent"))]
+//!     assert!(output.contents().lines().all(|line| line.contains("order_id=42")));
+//!
+//! The entries are added as fields to every event the [logger](crate::logger) records on this
+//! thread while the guard lives, unless the event has a field of the same name, and
+//! [wraperr](crate::wraperr) attaches them to the errors it wraps.
+//!
+//! Other threads start with an empty context, carry it over with [snapshot] and [attach], or
+//! spawn them with [spawn_with_context].
+
+use std::cell::RefCell;
+use std::fmt;
+
+use crate::Error;
+
+thread_local! {
+    static STACK: RefCell<Vec<(String, String)>> = const { RefCell::new(vec![]) };
+}
+
+/// Removes the entry and the ones pushed after it when dropped, see [push]
+#[must_use = "the entry is removed when the guard is dropped"]
+pub struct ContextGuard {
+    depth: usize,
+    // the entry belongs to the thread which pushed it
+    _thread: std::marker::PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        STACK.with(|stack| stack.borrow_mut().truncate(self.depth));
+    }
+}
+
+/// Add the entry `key = value` to the context of this thread until the guard is dropped
+///
+/// Entries stack, a key pushed again hides the outer value until the inner guard is dropped.
+pub fn push(key: &str, value: impl fmt::Display) -> ContextGuard {
+    STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        stack.push((key.to_string(), value.to_string()));
+        ContextGuard { depth: stack.len() - 1, _thread: std::marker::PhantomData }
+    })
+}
+
+/// The entries of a thread, taken with [snapshot] to [attach] them on another thread
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot(pub(crate) Vec<(String, String)>);
+
+/// Take the entries of this thread
+pub fn snapshot() -> Snapshot {
+    STACK.with(|stack| Snapshot(stack.borrow().clone()))
+}
+
+/// Add the entries of `snapshot` to the context of this thread until the guard is dropped
+///
+///     use errlog::context;
+///
+///     let _request = context::push("request_id", "r-17");
+///     let snapshot = context::snapshot();
+///     let handles: Vec<_> = (0..2)
+///         .map(|_| {
+///             let snapshot = snapshot.clone();
+///             std::thread::spawn(move || {
+///                 let _context = context::attach(snapshot);
+///                 // events and errors of this thread now carry request_id = r-17
+///             })
+///         })
+///         .collect();
+///     # for handle in handles { handle.join().unwrap(); }
+///
+/// This is what a closure handed to a rayon pool does, and what [spawn_with_context] does for
+/// a new thread.
+pub fn attach(snapshot: Snapshot) -> ContextGuard {
+    STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        let depth = stack.len();
+        stack.extend(snapshot.0);
+        ContextGuard { depth, _thread: std::marker::PhantomData }
+    })
+}
+
+/// Spawn a thread running `f` with the context of this thread
+///
+///     use errlog::context::{self, spawn_with_context};
+///     use errlog::logger::{self, MemoryWriter};
+///
+///     let output = MemoryWriter::new();
+///     let (dispatch, _guard) = logger::builder().writer(output.clone()).build();
+///     let _order = context::push("order_id", 42);
+///     spawn_with_context(move || {
+///         errlog::tracing::dispatcher::with_default(&dispatch, || errlog::logmsg!(WARN, "retrying"))
+///     })
+///     .join()
+///     .unwrap();
+///
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("order_id=42"));
+pub fn spawn_with_context<F, T>(f: F) -> std::thread::JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let snapshot = snapshot();
+    std::thread::spawn(move || {
+        let _context = attach(snapshot);
+        f()
+    })
+}
+
+/// The entries of this thread, outermost first, with the innermost value of each key
+pub(crate) fn entries() -> Vec<(String, String)> {
+    STACK.with(|stack| {
+        let stack = stack.borrow();
+        let mut entries: Vec<(String, String)> = vec![];
+        for (key, value) in stack.iter() {
+            match entries.iter_mut().find(|(name, _)| name == key) {
+                Some(entry) => entry.1 = value.clone(),
+                None => entries.push((key.clone(), value.clone())),
+            }
+        }
+        entries
+    })
+}
+
+/// The context entries attached to an error by [wraperr](crate::wraperr)
+///
+/// Like [Code](crate::Code), it renders as an empty string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fields(pub Vec<(String, String)>);
+
+impl fmt::Display for Fields {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// The outermost context entries attached to the error
+pub fn fields(err: &Error) -> Option<&[(String, String)]> {
+    err.downcast_ref::<Fields>().map(|fields| fields.0.as_slice())
+}
+
+/// Attach the entries of this thread to `err`, unless there are none or it already carries them
+pub(crate) fn wrap(err: Error) -> Error {
+    let entries = entries();
+    if entries.is_empty() || fields(&err) == Some(entries.as_slice()) {
+        return err;
+    }
+    crate::__private::context(err, Fields(entries))
+}
@@ -0,0 +1,231 @@
+//! Database error enrichment
+//!
+//! [wrap_query_err] adds the statement context to a failed query, logs it and classifies the
+//! failure, so callers can tell a unique violation (don't retry) from a lost connection (retry).
+//! The `sqlx` and `diesel` features teach the classification about the errors of those crates,
+//! otherwise it relies on `io::Error`s in the chain.
+
+use std::fmt;
+use std::panic::Location;
+
+use crate::Error;
+
+/// The class of a database failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbErrorClass {
+    UniqueViolation,
+    ForeignKeyViolation,
+    NotNullViolation,
+    CheckViolation,
+    /// Serialization failure or deadlock, the transaction can be replayed
+    SerializationFailure,
+    /// The connection was lost or could not be acquired
+    ConnectionLost,
+    /// The query returned no row
+    NotFound,
+    Other,
+}
+
+impl DbErrorClass {
+    /// Whether running the statement again may succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, DbErrorClass::SerializationFailure | DbErrorClass::ConnectionLost)
+    }
+}
+
+impl fmt::Display for DbErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DbErrorClass::UniqueViolation => "unique violation",
+            DbErrorClass::ForeignKeyViolation => "foreign key violation",
+            DbErrorClass::NotNullViolation => "not null violation",
+            DbErrorClass::CheckViolation => "check violation",
+            DbErrorClass::SerializationFailure => "serialization failure",
+            DbErrorClass::ConnectionLost => "connection lost",
+            DbErrorClass::NotFound => "not found",
+            DbErrorClass::Other => "database error",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What is known about a database failure
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbErrorInfo {
+    pub class: DbErrorClass,
+    /// The SQLSTATE code such as `23505`
+    pub sqlstate: Option<String>,
+    /// The name of the violated constraint
+    pub constraint: Option<String>,
+}
+
+impl DbErrorInfo {
+    fn new(class: DbErrorClass) -> Self {
+        DbErrorInfo { class, sqlstate: None, constraint: None }
+    }
+}
+
+/// Classify a SQLSTATE code
+///
+///     use errlog::db::{classify_sqlstate, DbErrorClass};
+///
+///     assert_eq!(classify_sqlstate("23505"), DbErrorClass::UniqueViolation);
+///     assert_eq!(classify_sqlstate("08006"), DbErrorClass::ConnectionLost);
+pub fn classify_sqlstate(sqlstate: &str) -> DbErrorClass {
+    match sqlstate {
+        "23505" => DbErrorClass::UniqueViolation,
+        "23503" => DbErrorClass::ForeignKeyViolation,
+        "23502" => DbErrorClass::NotNullViolation,
+        "23514" => DbErrorClass::CheckViolation,
+        "40001" | "40P01" => DbErrorClass::SerializationFailure,
+        "57P01" | "57P02" | "57P03" => DbErrorClass::ConnectionLost,
+        _ if sqlstate.starts_with("08") => DbErrorClass::ConnectionLost,
+        _ => DbErrorClass::Other,
+    }
+}
+
+fn extract_io(err: &(dyn std::error::Error + 'static)) -> Option<DbErrorInfo> {
+    if !err.is::<std::io::Error>() {
+        return None;
+    }
+    let class = match crate::class::infer(err) {
+        crate::Class::Transient => DbErrorClass::ConnectionLost,
+        _ => DbErrorClass::Other,
+    };
+    Some(DbErrorInfo::new(class))
+}
+
+#[cfg(feature = "sqlx")]
+fn extract_sqlx(err: &(dyn std::error::Error + 'static)) -> Option<DbErrorInfo> {
+    use sqlx::error::ErrorKind;
+
+    let info = match err.downcast_ref::<sqlx::Error>()? {
+        sqlx::Error::Database(db) => {
+            let sqlstate = db.code().map(|code| code.into_owned());
+            let class = match sqlstate.as_deref().map(classify_sqlstate) {
+                Some(class) if class != DbErrorClass::Other => class,
+                _ => match db.kind() {
+                    ErrorKind::UniqueViolation => DbErrorClass::UniqueViolation,
+                    ErrorKind::ForeignKeyViolation => DbErrorClass::ForeignKeyViolation,
+                    ErrorKind::NotNullViolation => DbErrorClass::NotNullViolation,
+                    ErrorKind::CheckViolation => DbErrorClass::CheckViolation,
+                    _ => DbErrorClass::Other,
+                },
+            };
+            let constraint = db.constraint().map(|name| name.to_string());
+            DbErrorInfo { class, sqlstate, constraint }
+        }
+        sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed => {
+            DbErrorInfo::new(DbErrorClass::ConnectionLost)
+        }
+        sqlx::Error::RowNotFound => DbErrorInfo::new(DbErrorClass::NotFound),
+        _ => DbErrorInfo::new(DbErrorClass::Other),
+    };
+    Some(info)
+}
+
+#[cfg(feature = "diesel")]
+fn extract_diesel(err: &(dyn std::error::Error + 'static)) -> Option<DbErrorInfo> {
+    use diesel::result::{DatabaseErrorKind, Error as DieselError};
+
+    let info = match err.downcast_ref::<DieselError>()? {
+        DieselError::DatabaseError(kind, info) => {
+            let class = match kind {
+                DatabaseErrorKind::UniqueViolation => DbErrorClass::UniqueViolation,
+                DatabaseErrorKind::ForeignKeyViolation => DbErrorClass::ForeignKeyViolation,
+                DatabaseErrorKind::NotNullViolation => DbErrorClass::NotNullViolation,
+                DatabaseErrorKind::CheckViolation => DbErrorClass::CheckViolation,
+                DatabaseErrorKind::SerializationFailure => DbErrorClass::SerializationFailure,
+                DatabaseErrorKind::ClosedConnection => DbErrorClass::ConnectionLost,
+                _ => DbErrorClass::Other,
+            };
+            let constraint = info.constraint_name().map(|name| name.to_string());
+            DbErrorInfo { class, sqlstate: None, constraint }
+        }
+        DieselError::NotFound => DbErrorInfo::new(DbErrorClass::NotFound),
+        _ => DbErrorInfo::new(DbErrorClass::Other),
+    };
+    Some(info)
+}
+
+/// Extract the database failure information from the first recognized error in the chain
+pub fn extract(err: &Error) -> DbErrorInfo {
+    let extractors: &[fn(&(dyn std::error::Error + 'static)) -> Option<DbErrorInfo>] = &[
+        #[cfg(feature = "sqlx")]
+        extract_sqlx,
+        #[cfg(feature = "diesel")]
+        extract_diesel,
+        extract_io,
+    ];
+    crate::chain(err)
+        .find_map(|cause| extractors.iter().find_map(|extract| extract(cause)))
+        .unwrap_or(DbErrorInfo::new(DbErrorClass::Other))
+}
+
+/// The context added by [wrap_query_err]
+#[derive(Debug, Clone)]
+pub struct QueryError {
+    pub sql: String,
+    pub params: String,
+    pub info: DbErrorInfo,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "query failed ({}", self.info.class)?;
+        if let Some(sqlstate) = &self.info.sqlstate {
+            write!(f, ", sqlstate {}", sqlstate)?;
+        }
+        if let Some(constraint) = &self.info.constraint {
+            write!(f, ", constraint {}", constraint)?;
+        }
+        write!(f, "): {}", self.sql)?;
+        if !self.params.is_empty() {
+            write!(f, " with {}", self.params)?;
+        }
+        Ok(())
+    }
+}
+
+/// Wrap a failed query with its statement and a summary of its parameters
+///
+/// The failure is classified (see [extract]) and logged with the location of the call, at `WARN`
+/// when it is retryable and `ERROR` otherwise
+///
+///     use errlog::db::{query_error, wrap_query_err, DbErrorClass};
+///
+///     let io = std::io::Error::from(std::io::ErrorKind::ConnectionReset);
+///     let err = wrap_query_err(io, "SELECT * FROM users WHERE id = $1", "id=42");
+///     assert_eq!(
+///         err.to_string(),
+///         "query failed (connection lost): SELECT * FROM users WHERE id = $1 with id=42",
+///     );
+///     let info = &query_error(&err).unwrap().info;
+///     assert_eq!(info.class, DbErrorClass::ConnectionLost);
+///     assert!(info.class.is_retryable());
+///
+/// Never put secrets in the parameters summary, it ends up in logs.
+#[track_caller]
+pub fn wrap_query_err<E: Into<Error>>(err: E, sql: &str, params_summary: &str) -> Error {
+    let location = Location::caller();
+    let err = err.into();
+    let context = QueryError {
+        sql: sql.to_string(),
+        params: params_summary.to_string(),
+        info: extract(&err),
+    };
+    if !crate::__private::SILENT {
+        let chain = crate::__private::chain_string(&err);
+        if context.info.class.is_retryable() {
+            tracing::warn!("{}:{} => {}: {}", location.file(), location.line(), context, chain);
+        } else {
+            tracing::error!("{}:{} => {}: {}", location.file(), location.line(), context, chain);
+        }
+    }
+    crate::__private::context(err, context)
+}
+
+/// Get the context added by [wrap_query_err]
+pub fn query_error(err: &Error) -> Option<&QueryError> {
+    err.downcast_ref::<QueryError>()
+}
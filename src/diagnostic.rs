@@ -0,0 +1,150 @@
+//! miette interop, in both directions
+//!
+//! - [from_report] turns a miette `Report` into an errlog error, keeping its help and labels
+//! - [into_report] and [render] show an errlog error through miette's graphical reporter
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use ::miette::{Diagnostic, GraphicalReportHandler, Report};
+
+use crate::Error;
+
+/// Convert a miette report into an errlog error
+///
+/// The chain of the result is the report message, its help text, its labels and then the sources
+/// of the report
+///
+///     let report = miette::miette!(help = "check the syntax", "invalid config");
+///     let err = errlog::diagnostic::from_report(report);
+///     let chain: Vec<String> = err.chain().map(|cause| cause.to_string()).collect();
+///     assert_eq!(chain, ["invalid config", "help: check the syntax"]);
+pub fn from_report(report: Report) -> Error {
+    let mut entries = vec![report.to_string()];
+    if let Some(help) = report.help() {
+        entries.push(format!("help: {}", help));
+    }
+    if let Some(labels) = report.labels() {
+        for label in labels {
+            match label.label() {
+                Some(text) => entries.push(format!("label: {} (at offset {})", text, label.offset())),
+                None => entries.push(format!("label at offset {}", label.offset())),
+            }
+        }
+    }
+    entries.extend(report.chain().skip(1).map(|cause| cause.to_string()));
+
+    let mut entries = entries.into_iter().rev();
+    let root = entries.next().unwrap_or_default();
+    entries.fold(Error::msg(root), crate::__private::context)
+}
+
+/// One message of the chain
+#[derive(Debug)]
+struct Cause {
+    message: String,
+    source: Option<Box<Cause>>,
+}
+
+impl Cause {
+    fn from_messages(messages: &[String]) -> Option<Box<Cause>> {
+        let (message, rest) = messages.split_first()?;
+        Some(Box::new(Cause {
+            message: message.clone(),
+            source: Cause::from_messages(rest),
+        }))
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl StdError for Cause {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source.as_deref().map(|cause| cause as &(dyn StdError + 'static))
+    }
+}
+
+/// An errlog error viewed as a miette diagnostic
+///
+/// Empty messages left in the chain by [wraperr](crate::wraperr) are skipped, and the error code
+/// from [error_code](crate::error_code) becomes the diagnostic code, [help](crate::help) and
+/// [doc_url](crate::doc_url) become its help and url.
+#[derive(Debug)]
+pub struct ErrlogDiagnostic {
+    code: Option<&'static str>,
+    help: Option<String>,
+    url: Option<String>,
+    message: String,
+    source: Option<Box<Cause>>,
+}
+
+impl ErrlogDiagnostic {
+    pub fn new(err: &Error) -> Self {
+        let messages = crate::chain_messages(err);
+        let (message, causes) = match messages.split_first() {
+            Some((message, causes)) => (message.clone(), causes),
+            None => (String::new(), &[][..]),
+        };
+        ErrlogDiagnostic {
+            code: crate::error_code(err),
+            help: crate::help(err).map(String::from),
+            url: crate::doc_url(err).map(String::from),
+            message,
+            source: Cause::from_messages(causes),
+        }
+    }
+}
+
+impl fmt::Display for ErrlogDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl StdError for ErrlogDiagnostic {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source.as_deref().map(|cause| cause as &(dyn StdError + 'static))
+    }
+}
+
+impl Diagnostic for ErrlogDiagnostic {
+    fn code<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.code.map(|code| Box::new(code) as Box<dyn fmt::Display>)
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.help.as_ref().map(|help| Box::new(help) as Box<dyn fmt::Display>)
+    }
+
+    fn url<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        self.url.as_ref().map(|url| Box::new(url) as Box<dyn fmt::Display>)
+    }
+}
+
+/// Convert an errlog error into a miette report
+pub fn into_report(err: &Error) -> Report {
+    Report::new(ErrlogDiagnostic::new(err))
+}
+
+/// Render the error with miette's graphical reporter
+///
+///     use errlog::{wraperr, Result};
+///
+///     fn load() -> Result<String> {
+///         wraperr!(std::fs::read_to_string("/path/to/config"), "failed to load config")
+///     }
+///
+///     let rendered = errlog::diagnostic::render(&load().unwrap_err());
+///     assert!(rendered.contains("No such file"));
+pub fn render(err: &Error) -> String {
+    let diagnostic = ErrlogDiagnostic::new(err);
+    let mut out = String::new();
+    if GraphicalReportHandler::new().render_report(&mut out, &diagnostic).is_err() {
+        return crate::__private::chain_string(err);
+    }
+    out
+}
@@ -0,0 +1,72 @@
+//! Cleanup hooks run before the process dies
+//!
+//!     use std::sync::atomic::{AtomicBool, Ordering};
+//!
+//!     static SAVED: AtomicBool = AtomicBool::new(false);
+//!
+//!     errlog::on_fatal(|| SAVED.store(true, Ordering::SeqCst));
+//!     // on the way out of main, for example after the error returned by the application
+//!     errlog::run_fatal_hooks();
+//!     assert!(SAVED.load(Ordering::SeqCst));
+//!
+//! The hooks run once, in registration order, from [run_fatal_hooks] and from the panic hook
+//! installed by [install_pretty_reports](crate::install_pretty_reports) when the main thread
+//! panics. They run on a thread named `errlog-fatal`, for at most the
+//! [budget](set_fatal_budget), 5 seconds by default, after which the process is let go. Every
+//! hook is logged at `INFO`, its panic at `ERROR`.
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+type Hook = Box<dyn FnOnce() + Send>;
+
+static HOOKS: Mutex<Vec<Hook>> = Mutex::new(Vec::new());
+static BUDGET_MS: AtomicU64 = AtomicU64::new(5000);
+
+/// Register a hook to run before an abnormal exit, see the [module](self) documentation
+pub fn on_fatal<F: FnOnce() + Send + 'static>(hook: F) {
+    HOOKS.lock().unwrap_or_else(|err| err.into_inner()).push(Box::new(hook));
+}
+
+/// How long [run_fatal_hooks] waits for the hooks in total
+pub fn set_fatal_budget(budget: Duration) {
+    BUDGET_MS.store(budget.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// Run the registered hooks, returning once they finished or the budget is spent
+pub fn run_fatal_hooks() {
+    let hooks = std::mem::take(&mut *HOOKS.lock().unwrap_or_else(|err| err.into_inner()));
+    if hooks.is_empty() {
+        return;
+    }
+    let budget = Duration::from_millis(BUDGET_MS.load(Ordering::Relaxed));
+    let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+    let (finished, done) = mpsc::channel::<()>();
+    let started = Instant::now();
+    let count = hooks.len();
+    let spawned = thread::Builder::new().name("errlog-fatal".to_string()).spawn(move || {
+        tracing::dispatcher::with_default(&dispatch, || {
+            for (index, hook) in hooks.into_iter().enumerate() {
+                let silent = crate::__private::SILENT;
+                if !silent {
+                    tracing::info!(fatal_hook = index + 1, "running fatal hook {}/{}", index + 1, count);
+                }
+                if let Err(panicked) = crate::unwind::catch(hook) {
+                    if !silent {
+                        tracing::error!(fatal_hook = index + 1, "fatal hook {}/{} {}", index + 1, count, panicked);
+                    }
+                }
+            }
+        });
+        let _ = finished.send(());
+    });
+    if spawned.is_err() {
+        return;
+    }
+    if done.recv_timeout(budget).is_err() && !crate::__private::SILENT {
+        tracing::warn!("fatal hooks still running after {:?}, giving up on them", started.elapsed());
+    }
+}
@@ -0,0 +1,110 @@
+//! C ABI access to errlog error chains
+//!
+//! Rust libraries exposing a C API store the error with [set_last_error] before returning an error
+//! status, then C/C++ callers read it back on the same thread
+//!
+//! ```c
+//! if (mylib_open(path) != 0) {
+//!     fprintf(stderr, "%s\n", errlog_last_error_message());
+//!     for (size_t i = 0; i < errlog_error_chain_len(); i++) {
+//!         ErrlogFrame frame;
+//!         errlog_error_frame(i, &frame);
+//!         fprintf(stderr, "  %zu: %s\n", i, frame.message);
+//!     }
+//! }
+//! ```
+//!
+//! The strings are owned by errlog and stay valid until the last error of the thread is replaced
+//! or cleared.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CString};
+use std::ptr;
+
+use crate::Error;
+
+struct LastError {
+    message: CString,
+    frames: Vec<CString>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<LastError>> = const { RefCell::new(None) };
+}
+
+fn to_cstring(text: String) -> CString {
+    CString::new(text.replace('\0', "\\0")).unwrap_or_default()
+}
+
+/// Store the error as the last error of the current thread
+///
+///     use errlog::ffi;
+///
+///     ffi::set_last_error(&errlog::Error::msg("disk full").context("failed to save"));
+///     assert_eq!(ffi::errlog_error_chain_len(), 2);
+pub fn set_last_error(err: &Error) {
+    let frames: Vec<String> = crate::chain_messages(err);
+    let last = LastError {
+        message: to_cstring(frames.join(": ")),
+        frames: frames.into_iter().map(to_cstring).collect(),
+    };
+    LAST_ERROR.with(|cell| *cell.borrow_mut() = Some(last));
+}
+
+/// Forget the last error of the current thread
+pub fn clear_last_error() {
+    LAST_ERROR.with(|cell| *cell.borrow_mut() = None);
+}
+
+/// One frame of the error chain
+#[repr(C)]
+pub struct ErrlogFrame {
+    pub message: *const c_char,
+}
+
+/// The whole chain of the last error on one line, or NULL if there is no error
+#[no_mangle]
+pub extern "C" fn errlog_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|cell| match &*cell.borrow() {
+        Some(last) => last.message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// The number of frames in the chain of the last error, 0 if there is no error
+#[no_mangle]
+pub extern "C" fn errlog_error_chain_len() -> usize {
+    LAST_ERROR.with(|cell| cell.borrow().as_ref().map(|last| last.frames.len()).unwrap_or(0))
+}
+
+/// Fill `out` with the frame at `index`, the outermost frame is 0
+///
+/// Returns 0 on success, -1 if there is no such frame or `out` is NULL.
+///
+/// # Safety
+///
+/// `out` must be NULL or point to writable memory for an `ErrlogFrame`.
+#[no_mangle]
+pub unsafe extern "C" fn errlog_error_frame(index: usize, out: *mut ErrlogFrame) -> c_int {
+    if out.is_null() {
+        return -1;
+    }
+    LAST_ERROR.with(|cell| {
+        let last = cell.borrow();
+        let frame = last.as_ref().and_then(|last| last.frames.get(index));
+        match frame.map(|frame| frame.as_ptr()) {
+            Some(message) => {
+                // SAFETY: out is not NULL and the caller guarantees it is writable
+                unsafe { out.write(ErrlogFrame { message }) };
+                0
+            }
+            None => -1,
+        }
+    })
+}
+
+/// Forget the last error of the current thread
+#[no_mangle]
+pub extern "C" fn errlog_clear_last_error() {
+    clear_last_error();
+}
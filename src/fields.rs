@@ -0,0 +1,46 @@
+//! Collect the message and the fields of tracing events
+
+use std::fmt;
+
+use tracing::field::{Field, Visit};
+
+/// Visitor splitting the `message` field from the other fields
+#[derive(Default)]
+pub(crate) struct FieldVisitor {
+    pub message: String,
+    pub fields: Vec<(String, String)>,
+    /// The non-empty messages of the chain of every error field, outermost first
+    pub errors: Vec<(String, Vec<String>)>,
+    /// The locations where the messages of `errors` were wrapped, if known
+    pub error_locations: Vec<Vec<Option<String>>>,
+}
+
+impl FieldVisitor {
+    fn push(&mut self, field: &Field, value: String) {
+        if field.name() == "message" {
+            self.message = value;
+        } else {
+            self.fields.push((field.name().to_string(), value));
+        }
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field, format!("{:?}", value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, value.to_string());
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        let causes = || std::iter::successors(Some(value), |err| err.source());
+        let located = crate::located_messages(causes());
+        let (chain, locations): (Vec<String>, Vec<Option<String>>) = located.into_iter().unzip();
+        self.push(field, chain.join(": "));
+        self.errors.push((field.name().to_string(), chain));
+        self.error_locations.push(locations);
+        self.fields.extend(crate::meta::from_chain(causes()));
+    }
+}
@@ -0,0 +1,68 @@
+//! async-graphql integration, turn errlog errors into structured GraphQL errors
+
+use async_graphql::indexmap::IndexMap;
+use async_graphql::{ErrorExtensions, Name, Value};
+
+use crate::trace::correlation_id;
+use crate::Error;
+
+/// Convert the error into a GraphQL error
+///
+/// The full chain is logged at `ERROR` together with a [correlation id](crate::correlation_id),
+/// while the client only gets the [public message](crate::public_message) or the
+/// [ErrorCode](crate::ErrorCode) message (or a generic message) and the extensions `code`,
+/// `tags` and `correlationId`, which support teams can use to find the server side log. The tags
+/// are the [context](crate::context) entries and the [metadata](crate::meta) of the error
+///
+///     use errlog::ErrorCode;
+///
+///     #[derive(Debug, ErrorCode)]
+///     enum UserError {
+///         #[code("E1001", msg = "user not found")]
+///         NotFound,
+///     }
+///
+///     let err = errlog::graphql::to_graphql_error(&UserError::NotFound.into());
+///     assert_eq!(err.message, "user not found");
+///     let extensions = err.extensions.unwrap();
+///     assert_eq!(extensions.get("code").unwrap(), &async_graphql::Value::from("E1001"));
+///     assert!(extensions.get("correlationId").is_some());
+///
+///     # use errlog::MetaExt;
+///     # let _tenant = errlog::context::push("tenant", "acme");
+///     # let failed: errlog::Result<()> = Err(errlog::Error::msg("timeout"));
+///     # let err = errlog::wraperr!(failed.with_meta("table", "orders")).unwrap_err();
+///     # let extensions = errlog::graphql::to_graphql_error(&err).extensions.unwrap();
+///     # assert_eq!(extensions.get("tags").unwrap().to_string(), r#"{tenant: "acme", table: "orders"}"#);
+pub fn to_graphql_error(err: &Error) -> async_graphql::Error {
+    let code = crate::error_code(err);
+    let correlation_id = correlation_id();
+    if !crate::__private::SILENT {
+        tracing::error!(
+            code,
+            correlation_id = correlation_id.as_str(),
+            "{}",
+            crate::__private::chain_string(err),
+        );
+    }
+    let message = match (crate::public_message(err), crate::find_error_code(err)) {
+        (Some(message), _) => message,
+        (None, Some(code)) => code.message(),
+        (None, None) => "internal server error",
+    };
+    let tags: IndexMap<Name, Value> = crate::context::fields(err)
+        .unwrap_or_default()
+        .iter()
+        .chain(crate::meta(err))
+        .map(|(name, value)| (Name::new(name), Value::from(value.as_str())))
+        .collect();
+    async_graphql::Error::new(message).extend_with(|_, extensions| {
+        if let Some(code) = code {
+            extensions.set("code", code);
+        }
+        if !tags.is_empty() {
+            extensions.set("tags", Value::Object(tags));
+        }
+        extensions.set("correlationId", correlation_id.as_str());
+    })
+}
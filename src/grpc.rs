@@ -0,0 +1,94 @@
+//! Tonic integration, turn errlog errors into gRPC statuses
+
+use std::io;
+
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Status};
+
+use crate::{Class, Error};
+
+/// The metadata key carrying the stable error code
+pub const ERROR_CODE_KEY: &str = "x-error-code";
+
+/// Classify the error into a gRPC code
+///
+/// The [HTTP status](crate::http_status) of the error is used first, then the kind of any
+/// `io::Error` in the chain, then its [class](crate::classify): transient failures map to
+/// `Unavailable`, permanent ones to `FailedPrecondition`, everything else is `Internal`.
+///
+///     use errlog::{Class, ClassExt};
+///
+///     let err = Err::<(), _>(errlog::Error::msg("queue full")).with_class(Class::Transient).unwrap_err();
+///     assert_eq!(errlog::grpc::grpc_code(&err), tonic::Code::Unavailable);
+pub fn grpc_code(err: &Error) -> Code {
+    if let Some(http) = crate::http_status(err) {
+        return match http {
+            400 | 422 => Code::InvalidArgument,
+            401 => Code::Unauthenticated,
+            403 => Code::PermissionDenied,
+            404 => Code::NotFound,
+            409 => Code::AlreadyExists,
+            412 => Code::FailedPrecondition,
+            429 => Code::ResourceExhausted,
+            501 => Code::Unimplemented,
+            503 => Code::Unavailable,
+            504 => Code::DeadlineExceeded,
+            _ => Code::Internal,
+        };
+    }
+    let kind = crate::chain(err)
+        .find_map(|cause| cause.downcast_ref::<io::Error>())
+        .map(|err| err.kind());
+    match kind {
+        Some(io::ErrorKind::NotFound) => Code::NotFound,
+        Some(io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData) => Code::InvalidArgument,
+        Some(io::ErrorKind::PermissionDenied) => Code::PermissionDenied,
+        Some(io::ErrorKind::AlreadyExists) => Code::AlreadyExists,
+        Some(io::ErrorKind::TimedOut) => Code::DeadlineExceeded,
+        _ => match crate::classify(err) {
+            Class::Transient => Code::Unavailable,
+            Class::Permanent => Code::FailedPrecondition,
+            Class::Unknown => Code::Internal,
+        },
+    }
+}
+
+/// Convert the error into a gRPC status
+///
+/// The full chain is logged at `ERROR` on the server side, while the client only gets the
+/// [ErrorCode](crate::ErrorCode) message (or the description of the gRPC code) and the stable
+/// error code in the `x-error-code` metadata
+///
+///     use errlog::ErrorCode;
+///
+///     #[derive(Debug, ErrorCode)]
+///     enum UserError {
+///         #[code("E1001", http = 404, msg = "user not found")]
+///         NotFound,
+///     }
+///
+///     let status = errlog::grpc::to_status(&UserError::NotFound.into());
+///     assert_eq!(status.code(), tonic::Code::NotFound);
+///     assert_eq!(status.message(), "user not found");
+///     assert_eq!(status.metadata().get("x-error-code").unwrap(), "E1001");
+pub fn to_status(err: &Error) -> Status {
+    let code = grpc_code(err);
+    let error_code = crate::error_code(err);
+    if !crate::__private::SILENT {
+        tracing::error!(
+            code = error_code,
+            grpc_code = ?code,
+            "{}",
+            crate::__private::chain_string(err),
+        );
+    }
+    let message = match crate::find_error_code(err) {
+        Some(code) => code.message(),
+        None => code.description(),
+    };
+    let mut status = Status::new(code, message);
+    if let Some(value) = error_code.and_then(|code| MetadataValue::try_from(code).ok()) {
+        status.metadata_mut().insert(ERROR_CODE_KEY, value);
+    }
+    status
+}
@@ -0,0 +1,63 @@
+//! Periodic liveness lines, to tell a hung process from an idle one in the logs
+//!
+//!     use std::time::Duration;
+//!
+//!     use errlog::logger::{self, MemoryWriter};
+//!
+//!     let output = MemoryWriter::new();
+//!     let _guard = logger::builder().writer(output.clone()).set_default();
+//!     let queue = vec![1, 2, 3];
+//!     let heartbeat = errlog::heartbeat(Duration::from_millis(10), move || format!("alive; queue={}", queue.len()));
+//!     std::thread::sleep(Duration::from_millis(100));
+//!     drop(heartbeat);
+//!
+//!     # #[cfg(not(feature = "silent"))]
+//!     assert!(output.contents().lines().count() >= 2);
+//!     # #[cfg(not(feature = "silent"))]
+//!     assert!(output.contents().contains("alive; queue=3"));
+
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Stops the heartbeat when dropped, see [heartbeat]
+#[must_use = "the heartbeat stops when the guard is dropped"]
+pub struct Heartbeat {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Log the line returned by `line` at `INFO` every `interval`, from a thread named
+/// `errlog-heartbeat`, until the guard is dropped
+///
+/// The lines go to the subscriber which is the default of the calling thread, and carry the
+/// `heartbeat` field counting them from 1.
+pub fn heartbeat<F>(interval: Duration, line: F) -> Heartbeat
+where
+    F: Fn() -> String + Send + 'static,
+{
+    let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+    let (stop, stopped) = mpsc::channel::<()>();
+    let thread = thread::Builder::new()
+        .name("errlog-heartbeat".to_string())
+        .spawn(move || {
+            tracing::dispatcher::with_default(&dispatch, || {
+                let mut count: u64 = 0;
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    count += 1;
+                    crate::logmsg!(INFO, heartbeat = count, "{}", line());
+                }
+            })
+        })
+        .expect("failed to spawn the errlog heartbeat thread");
+    Heartbeat { stop: Some(stop), thread: Some(thread) }
+}
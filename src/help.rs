@@ -0,0 +1,100 @@
+//! Guidance for end users attached to errors
+
+use std::fmt;
+
+use crate::{Error, Result};
+
+/// A hint telling the user how to fix the error, see [HelpExt]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Help(pub String);
+
+/// A link to the documentation of the error, see [HelpExt]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocUrl(pub String);
+
+/// The message shown to users instead of the internal details, see [HelpExt]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicMessage(pub String);
+
+impl fmt::Display for Help {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+impl fmt::Display for DocUrl {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+impl fmt::Display for PublicMessage {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Attach [Help] and [DocUrl] to the error of a result
+///
+///     use errlog::{HelpExt, Result};
+///
+///     fn migrate() -> Result<()> {
+///         Err(errlog::Error::msg("schema version 3 is outdated"))
+///             .help("run `myapp migrate` first")
+///             .doc_url("https://example.com/errors#E1042")
+///     }
+///
+///     let err = migrate().unwrap_err();
+///     assert_eq!(errlog::help(&err), Some("run `myapp migrate` first"));
+///     assert_eq!(errlog::doc_url(&err), Some("https://example.com/errors#E1042"));
+///
+/// Both render as empty strings in the chain, the pretty reports show them in a `Help` section
+/// and [ErrorReport](crate::ErrorReport) carries them.
+///
+/// A public message replaces the internal details in what users see, the responses of the web
+/// integrations and the headline of the [pretty](crate::report::pretty) reports, while the logs
+/// keep the whole chain
+///
+///     use errlog::{Context, HelpExt, Result};
+///
+///     fn save() -> Result<()> {
+///         Err(errlog::Error::msg("deadlock detected on table drafts"))
+///             .context("failed to commit the transaction")
+///             .public_msg("Could not save your changes")
+///     }
+///
+///     assert_eq!(errlog::public_message(&save().unwrap_err()), Some("Could not save your changes"));
+pub trait HelpExt<T> {
+    fn help(self, text: impl Into<String>) -> Result<T>;
+    fn doc_url(self, url: impl Into<String>) -> Result<T>;
+    fn public_msg(self, msg: impl Into<String>) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> HelpExt<T> for std::result::Result<T, E> {
+    fn help(self, text: impl Into<String>) -> Result<T> {
+        self.map_err(|err| crate::__private::context(err.into(), Help(text.into())))
+    }
+
+    fn doc_url(self, url: impl Into<String>) -> Result<T> {
+        self.map_err(|err| crate::__private::context(err.into(), DocUrl(url.into())))
+    }
+
+    fn public_msg(self, msg: impl Into<String>) -> Result<T> {
+        self.map_err(|err| crate::__private::context(err.into(), PublicMessage(msg.into())))
+    }
+}
+
+/// The outermost help text attached to the error
+pub fn help(err: &Error) -> Option<&str> {
+    err.downcast_ref::<Help>().map(|help| help.0.as_str())
+}
+
+/// The outermost documentation link attached to the error
+pub fn doc_url(err: &Error) -> Option<&str> {
+    err.downcast_ref::<DocUrl>().map(|url| url.0.as_str())
+}
+
+/// The outermost public message attached to the error
+pub fn public_message(err: &Error) -> Option<&str> {
+    err.downcast_ref::<PublicMessage>().map(|msg| msg.0.as_str())
+}
@@ -0,0 +1,149 @@
+//! reqwest integration, make failed upstream calls diagnosable from logs alone
+
+use std::fmt;
+use std::future::Future;
+use std::panic::Location;
+
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Method, RequestBuilder, Response, StatusCode, Url};
+
+use crate::{Error, Result};
+
+/// The context added to failed HTTP calls
+#[derive(Debug, Clone)]
+pub struct HttpError {
+    /// The method, unknown when only the result of `send` is available
+    pub method: Option<Method>,
+    /// The URL without query and fragment, which often carry secrets
+    pub url: Option<String>,
+    pub status: Option<StatusCode>,
+    /// The raw `Retry-After` header
+    pub retry_after: Option<String>,
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(method) = &self.method {
+            write!(f, "{} ", method)?;
+        }
+        write!(f, "{} failed", self.url.as_deref().unwrap_or("<unknown url>"))?;
+        if let Some(status) = self.status {
+            write!(f, " with status {}", status.as_u16())?;
+        }
+        if let Some(retry_after) = &self.retry_after {
+            write!(f, " (retry after {})", retry_after)?;
+        }
+        Ok(())
+    }
+}
+
+fn strip_query(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_query(None);
+    url.set_fragment(None);
+    url.to_string()
+}
+
+fn wrap(
+    result: std::result::Result<Response, reqwest::Error>,
+    method: Option<Method>,
+    location: &'static Location<'static>,
+) -> Result<Response> {
+    let (context, err) = match result {
+        Ok(response) if !response.status().is_client_error() && !response.status().is_server_error() => {
+            return Ok(response);
+        }
+        Ok(response) => {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string());
+            let context = HttpError {
+                method,
+                url: Some(strip_query(response.url())),
+                status: Some(response.status()),
+                retry_after,
+            };
+            let err = match response.error_for_status() {
+                Err(err) => Error::new(err.without_url()),
+                Ok(_) => Error::msg("unexpected status"),
+            };
+            (context, err)
+        }
+        Err(err) => {
+            let context = HttpError {
+                method,
+                url: err.url().map(strip_query),
+                status: err.status(),
+                retry_after: None,
+            };
+            (context, Error::new(err.without_url()))
+        }
+    };
+    if !crate::__private::SILENT {
+        tracing::error!(
+            method = context.method.as_ref().map(|method| method.as_str()),
+            url = context.url.as_deref(),
+            status = context.status.map(|status| status.as_u16()),
+            retry_after = context.retry_after.as_deref(),
+            "{}:{} => {}: {}",
+            location.file(),
+            location.line(),
+            context,
+            crate::__private::chain_string(&err),
+        );
+    }
+    Err(crate::__private::context(err, context))
+}
+
+/// Wrap the result of `RequestBuilder::send`
+///
+/// Transport errors and responses with a 4xx/5xx status become errors carrying an [HttpError]
+/// context, which is also logged at `ERROR` with the location of the call
+///
+///     use errlog::http::HttpResultExt;
+///
+///     async fn fetch(client: &reqwest::Client) -> errlog::Result<String> {
+///         let request = client.get("http://example.com/items?token=secret");
+///         let response = request.send().await.wrap_http_err()?;
+///         Ok(response.text().await?)
+///     }
+pub trait HttpResultExt {
+    fn wrap_http_err(self) -> Result<Response>;
+}
+
+impl HttpResultExt for std::result::Result<Response, reqwest::Error> {
+    #[track_caller]
+    fn wrap_http_err(self) -> Result<Response> {
+        wrap(self, None, Location::caller())
+    }
+}
+
+/// Send the request and wrap the result like [HttpResultExt::wrap_http_err], recording the
+/// method as well
+///
+///     use errlog::http::RequestBuilderExt;
+///
+///     async fn delete(client: &reqwest::Client) -> errlog::Result<()> {
+///         client.delete("http://example.com/items/42").send_wrapped().await?;
+///         Ok(())
+///     }
+pub trait RequestBuilderExt {
+    fn send_wrapped(self) -> impl Future<Output = Result<Response>> + Send;
+}
+
+impl RequestBuilderExt for RequestBuilder {
+    #[track_caller]
+    fn send_wrapped(self) -> impl Future<Output = Result<Response>> + Send {
+        let location = Location::caller();
+        let method = self
+            .try_clone()
+            .and_then(|builder| builder.build().ok())
+            .map(|request| request.method().clone());
+        async move {
+            let result = self.send().await;
+            wrap(result, method, location)
+        }
+    }
+}
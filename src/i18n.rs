@@ -0,0 +1,142 @@
+//! Localized, user-facing messages of coded errors
+//!
+//! A [Catalog] maps error codes to templates per locale, logs keep the canonical English message
+//! while [localized_message] renders the text shown to the user
+//!
+//!     use errlog::i18n::{self, Catalog};
+//!     use errlog::{Context, ErrorCode, Result};
+//!
+//!     #[derive(Debug, ErrorCode)]
+//!     enum UserError {
+//!         #[code("E1001", msg = "user not found")]
+//!         NotFound,
+//!     }
+//!
+//!     let catalog = Catalog::new()
+//!         .add("fr", "E1001", "utilisateur {id} introuvable")
+//!         .parse("de", "# German\nE1001 = Benutzer {id} nicht gefunden");
+//!     i18n::set_catalog(catalog);
+//!
+//!     fn find_user(id: u32) -> Result<()> {
+//!         Err(errlog::Error::new(UserError::NotFound)).context(i18n::args([("id", id.to_string())]))
+//!     }
+//!
+//!     let err = find_user(42).unwrap_err();
+//!     assert_eq!(errlog::localized_message(&err, "fr-CA"), "utilisateur 42 introuvable");
+//!     assert_eq!(errlog::localized_message(&err, "de"), "Benutzer 42 nicht gefunden");
+//!     assert_eq!(errlog::localized_message(&err, "ja"), "user not found");
+//!
+//! Templates refer to [args] as `{name}`, unknown names are left as they are.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use crate::Error;
+
+/// The arguments of the templates, attached as a context and rendered as an empty string
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageArgs(pub Vec<(String, String)>);
+
+impl fmt::Display for MessageArgs {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Build [MessageArgs] from name and value pairs
+pub fn args<K, V>(args: impl IntoIterator<Item = (K, V)>) -> MessageArgs
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    MessageArgs(args.into_iter().map(|(key, value)| (key.into(), value.into())).collect())
+}
+
+/// Templates keyed by locale then by error code
+#[derive(Debug, Clone, Default)]
+pub struct Catalog {
+    templates: HashMap<String, HashMap<String, String>>,
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the template of `code` in `locale`
+    pub fn add(mut self, locale: &str, code: &str, template: &str) -> Self {
+        self.templates
+            .entry(locale.to_string())
+            .or_default()
+            .insert(code.to_string(), template.to_string());
+        self
+    }
+
+    /// Add the templates of `locale` from lines of `CODE = template`, ignoring blank lines and
+    /// lines starting with `#`
+    pub fn parse(mut self, locale: &str, text: &str) -> Self {
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((code, template)) = line.split_once('=') {
+                self = self.add(locale, code.trim(), template.trim());
+            }
+        }
+        self
+    }
+
+    /// The template of `code` for `locale`, falling back from `fr-CA` to `fr`
+    pub fn template(&self, code: &str, locale: &str) -> Option<&str> {
+        let language = locale.split(['-', '_']).next().unwrap_or(locale);
+        [locale, language]
+            .into_iter()
+            .find_map(|locale| self.templates.get(locale)?.get(code))
+            .map(String::as_str)
+    }
+
+    /// The localized message of the error, see [localized_message]
+    pub fn localize(&self, err: &Error, locale: &str) -> String {
+        let template = crate::error_code(err).and_then(|code| self.template(code, locale));
+        match template {
+            Some(template) => render(template, err.downcast_ref::<MessageArgs>()),
+            None => canonical_message(err),
+        }
+    }
+}
+
+/// The message of the code, or the outermost message of the chain
+fn canonical_message(err: &Error) -> String {
+    match crate::find_error_code(err) {
+        Some(coded) => coded.message().to_string(),
+        None => crate::chain_messages(err).into_iter().next().unwrap_or_default(),
+    }
+}
+
+fn render(template: &str, args: Option<&MessageArgs>) -> String {
+    let mut text = template.to_string();
+    for (name, value) in args.map(|args| args.0.as_slice()).unwrap_or_default() {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+static CATALOG: RwLock<Option<Arc<Catalog>>> = RwLock::new(None);
+
+/// Install the catalog used by [localized_message]
+pub fn set_catalog(catalog: Catalog) {
+    *CATALOG.write().unwrap_or_else(|err| err.into_inner()) = Some(Arc::new(catalog));
+}
+
+/// The user-facing message of the error in `locale`
+///
+/// The template of the error code in the installed catalog is used when there is one, otherwise
+/// the canonical message of the code, otherwise the outermost message of the chain.
+pub fn localized_message(err: &Error, locale: &str) -> String {
+    let catalog = CATALOG.read().unwrap_or_else(|err| err.into_inner()).clone();
+    match catalog {
+        Some(catalog) => catalog.localize(err, locale),
+        None => canonical_message(err),
+    }
+}
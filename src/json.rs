@@ -0,0 +1,72 @@
+//! JSON helpers with readable errors
+
+use std::panic::Location;
+
+use serde::de::DeserializeOwned;
+
+use crate::Result;
+
+/// How many characters to show on each side of the error column
+const SNIPPET_RADIUS: usize = 40;
+
+/// Render the offending line with a caret under the column, which serde_json counts in bytes
+fn snippet(input: &str, line: usize, column: usize) -> String {
+    let text = input.lines().nth(line.saturating_sub(1)).unwrap_or_default();
+    let chars: Vec<char> = text.chars().collect();
+    let mut byte = column.saturating_sub(1).min(text.len());
+    while !text.is_char_boundary(byte) {
+        byte -= 1;
+    }
+    let column = text[..byte].chars().count();
+    let start = column.saturating_sub(SNIPPET_RADIUS);
+    let end = (column + SNIPPET_RADIUS).min(chars.len());
+    let prefix = if start > 0 { "..." } else { "" };
+    let suffix = if end < chars.len() { "..." } else { "" };
+    let excerpt: String = chars[start..end].iter().collect();
+    let gutter = format!("{} | ", line);
+    let caret = " ".repeat(gutter.len() + prefix.len() + column - start);
+    format!("{}{}{}{}\n{}^", gutter, prefix, excerpt, suffix, caret)
+}
+
+/// Deserialize JSON, on failure the error tells the file name, line, column and shows the
+/// offending input, and is logged at `ERROR` with the location of the call
+///
+///     use std::collections::HashMap;
+///
+///     let input = "{\n  \"port\": ,\n}";
+///     let err = errlog::json::from_str_logged::<HashMap<String, u16>>(input, "config.json").unwrap_err();
+///     let msg = err.to_string();
+///     let lines: Vec<&str> = msg.lines().collect();
+///     assert_eq!(lines[0], "failed to parse config.json:2:11: expected value");
+///     assert_eq!(lines[1], "2 |   \"port\": ,");
+///     assert_eq!(lines[2].find('^'), lines[1].find(','));
+///     # let err = errlog::json::from_str_logged::<HashMap<String, u16>>("{\"größe\": ,}", "config.json").unwrap_err();
+///     # let msg = err.to_string();
+///     # let lines: Vec<&str> = msg.lines().collect();
+///     # assert_eq!(lines[2].chars().position(|c| c == '^'), lines[1].chars().position(|c| c == ','));
+///     assert!(err.root_cause().is::<serde_json::Error>());
+///
+/// Long lines such as minified JSON are cut around the column.
+#[track_caller]
+pub fn from_str_logged<T: DeserializeOwned>(input: &str, name: &str) -> Result<T> {
+    let location = Location::caller();
+    serde_json::from_str(input).map_err(|err| {
+        let (line, column) = (err.line(), err.column());
+        let reason = match err.to_string().rsplit_once(" at line ") {
+            Some((reason, _)) => reason.to_string(),
+            None => err.to_string(),
+        };
+        let msg = format!(
+            "failed to parse {}:{}:{}: {}\n{}",
+            name,
+            line,
+            column,
+            reason,
+            snippet(input, line, column),
+        );
+        if !crate::__private::SILENT {
+            tracing::error!("{}:{} => {}", location.file(), location.line(), msg);
+        }
+        crate::__private::context(err.into(), msg)
+    })
+}
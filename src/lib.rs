@@ -1,7 +1,223 @@
 //! A thin wrapper around anyhow for easy use
+//!
+//! The error types come from anyhow in default, projects standardized on eyre can disable the
+//! default features and enable the `eyre` feature instead, then `Result`, `Error` and `Context`
+//! are re-exported from eyre (`Error` is `eyre::Report` and `Context` is `eyre::WrapErr`) and all
+//! the macros expand against them unchanged.
+//!
+//! With the `silent` feature, the macros still attach their contexts but never emit tracing
+//! events, leaving the logging policy to whoever builds the final binary. Since cargo unifies
+//! features, it silences every crate of the build, so enable it from the binary rather than from
+//! a library.
+//!
+//! With the `err-id` feature, the events of the macros carry an `err_id` field, 6 hex digits
+//! derived from the crate, the file and the line of the callsite, which stays the same across
+//! versions as long as the callsite does not move, even when the message wording changes.
 
+#[cfg(all(feature = "anyhow", feature = "eyre"))]
+compile_error!("features `anyhow` and `eyre` are mutually exclusive, disable default features to use eyre");
+#[cfg(not(any(feature = "anyhow", feature = "eyre")))]
+compile_error!("one of the features `anyhow` or `eyre` must be enabled");
+
+#[cfg(feature = "anyhow")]
 #[doc(no_inline)]
 pub use anyhow::{Context, Result, Error};
+#[cfg(feature = "eyre")]
+#[doc(no_inline)]
+pub use eyre::{Report as Error, Result, WrapErr as Context};
+
+mod attachment;
+pub mod budget;
+mod class;
+pub mod clock;
+mod code;
+mod complete;
+pub mod context;
+mod fatal;
+mod fields;
+pub mod heartbeat;
+mod help;
+pub mod i18n;
+mod location;
+mod logged;
+pub mod logger;
+mod meta;
+pub mod prelude;
+pub mod redact;
+pub mod report;
+pub mod retry;
+mod severity;
+mod snippet;
+mod span;
+mod status;
+pub mod thread;
+pub mod trace;
+mod unwind;
+pub mod watchdog;
+#[cfg(feature = "bundle")]
+pub mod bundle;
+#[cfg(feature = "tonic")]
+pub mod grpc;
+#[cfg(feature = "miette")]
+pub mod diagnostic;
+#[cfg(feature = "db")]
+pub mod db;
+#[cfg(feature = "async-graphql")]
+pub mod graphql;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "reqwest")]
+pub mod http;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "mem")]
+pub mod mem;
+#[cfg(feature = "pyo3")]
+pub mod python;
+#[cfg(feature = "scrub")]
+pub mod scrub;
+#[cfg(feature = "tokio")]
+pub mod task;
+#[cfg(feature = "test-util")]
+pub mod test;
+#[cfg(any(feature = "axum", feature = "actix"))]
+pub mod web;
+
+pub use attachment::{Attach, get_attachment};
+pub use budget::budget;
+#[cfg(feature = "bundle")]
+pub use bundle::support_bundle;
+pub use class::{Class, ClassExt, classify};
+pub use code::{Code, CodeEntry, ErrorCode, code_catalog, error_code, find_error_code};
+pub use complete::{MustComplete, must_complete};
+pub use errlog_macros::ErrorCode;
+pub use fatal::{on_fatal, run_fatal_hooks, set_fatal_budget};
+pub use heartbeat::heartbeat;
+pub use help::{DocUrl, Help, HelpExt, PublicMessage, doc_url, help, public_message};
+pub use i18n::localized_message;
+/// The tracing version the macros expand against, so that errlog is the only dependency needed
+pub use tracing;
+/// The tracing-subscriber version used by the [logger]
+pub use tracing_subscriber as subscriber;
+pub use location::{Location, location};
+pub use logged::LoggedResult;
+pub use meta::{Meta, MetaExt, meta};
+pub use report::{ErrorReport, install_pretty_reports};
+pub use retry::retry_with_log;
+#[cfg(feature = "tokio")]
+pub use retry::retry_with_log_async;
+pub use severity::{Severity, severity};
+pub use snippet::{Snippet, snippet};
+pub use span::{ErrorSpan, error_span};
+pub use status::{HttpStatus, StatusExt, http_status};
+pub use trace::correlation_id;
+#[cfg(feature = "tokio")]
+pub use task::{spawn_logged, with_timeout_log};
+pub use unwind::{Panicked, catch_unwind_log};
+pub use watchdog::watchdog;
+
+/// Run the function inside a span and record the error on the span when it fails
+///
+/// The span is named after the function and carries the fields `error`, `error.code` and
+/// `error.location`, which are filled in when the function returns `Err`, then one `ERROR` event
+/// is emitted inside the span. The location is the one of the outermost
+/// [wraperr](crate::wraperr), or the attribute's when the error was never wrapped. The function
+/// must return anyhow's `Result`, for example
+///
+///     use errlog::{instrument_err, wraperr, Result};
+///
+///     #[instrument_err]
+///     fn load(filepath: &str) -> Result<String> {
+///         wraperr!(std::fs::read_to_string(filepath), "failed to load {}", filepath)
+///     }
+///
+///     #[instrument_err]
+///     async fn fetch(url: &str) -> Result<String> {
+///         Ok(url.to_string())
+///     }
+///
+/// Since the error lives on the span, exporters such as OpenTelemetry will show the failure in the
+/// trace without extra plumbing.
+pub use errlog_macros::instrument_err;
+
+/// Log the call when the function takes longer than the threshold
+///
+/// Works for both sync and async functions, the threshold accepts units of `ns`, `us`, `ms`, `s`,
+/// `m` and `h` (default `100ms`), and the level defaults to `WARN`
+///
+///     use errlog::log_slow;
+///
+///     #[log_slow(threshold = "250ms", level = WARN)]
+///     fn handle(user: &str, id: u64) -> String {
+///         format!("{user}:{id}")
+///     }
+///
+///     #[log_slow(threshold = "1s")]
+///     async fn fetch(url: &str) -> usize {
+///         url.len()
+///     }
+///
+///     handle("alice", 42);
+///
+/// The event carries the function name, the arguments summary (`Debug` output of every argument
+/// which implements it, truncated) and the elapsed time. Arguments are only summarized when the
+/// level is enabled, but since they may be moved into the body, this happens before the call.
+pub use errlog_macros::log_slow;
+
+/// Retry the function when it fails, logging every failed attempt
+///
+/// The function must return anyhow's `Result`, the options are
+///
+/// - `times`: how many attempts in total, default 3
+/// - `backoff`: `"exp(100ms)"` doubles the delay after each attempt, `"fixed(100ms)"` or
+///   `"100ms"` waits the same delay, default no delay
+/// - `retry_if`: path to a `fn(&errlog::Error) -> bool` telling which errors are retryable,
+///   default every error not [classified](classify) as [Permanent](Class::Permanent)
+///
+/// For example
+///
+///     use errlog::{retry, Result};
+///
+///     fn is_transient(err: &errlog::Error) -> bool {
+///         err.to_string().contains("busy")
+///     }
+///
+///     #[retry(times = 3, backoff = "exp(1ms)", retry_if = is_transient)]
+///     fn connect(addr: &str) -> Result<()> {
+///         Err(errlog::Error::msg(format!("{addr} is busy")))
+///     }
+///
+///     let err = connect("db:5432").unwrap_err();
+///     assert_eq!(err.to_string(), "failed after 3 attempt(s)");
+///     assert_eq!(errlog::retry::attempts(&err).unwrap().errors.len(), 2);
+///
+/// The function runs through [retry_with_log] with the [Policy](retry::Policy) of the options, so
+/// every failed attempt but the last is logged at `WARN` with the location of the attribute, the
+/// attempt number and the delay, and the final failure is wrapped with
+/// [Attempts](retry::Attempts). Since the body runs several times, the arguments it consumes must
+/// be borrowed or cloned. Async functions sleep through tokio, so they require the `tokio`
+/// feature.
+pub use errlog_macros::retry;
+
+/// Run the test with a [capturing subscriber](test::capture), printing the captured events when
+/// it fails
+///
+///     #[errlog::test]
+///     fn parses_config() -> errlog::Result<()> {
+///         errlog::logmsg!(INFO, "parsing config");
+///         Ok(())
+///     }
+///
+///     #[errlog::test]
+///     async fn fetches_user() {
+///         errlog::logmsg!(INFO, "fetching user");
+///     }
+///
+/// A test fails when it panics or returns `Err`, its log history is then written to stderr, which
+/// the test harness shows next to the failure. Async tests run on a current-thread tokio runtime,
+/// so they require the `tokio` feature.
+#[cfg(feature = "test-util")]
+pub use errlog_macros::test;
 
 /// Logging message based on [tracing](https://github.com/tokio-rs/tracing) library
 ///
@@ -10,52 +226,54 @@ pub use anyhow::{Context, Result, Error};
 ///
 ///     tracing_subscriber::fmt::init();
 ///
-/// see [tracing-subscriber](https://docs.rs/tracing-subscriber/latest/tracing_subscriber/fmt/index.html) for detail.
+/// see [tracing-subscriber](https://docs.rs/tracing-subscriber/latest/tracing_subscriber/fmt/index.html) for detail,
+/// or use the [logger] of errlog. The macros expand against the [tracing] re-exported by errlog,
+/// so depending on tracing directly is not required.
 ///
 /// To log in specific trace level, do something such as
 ///
+///     # use errlog::logmsg;
+///     # let msg = "msg";
 ///     logmsg!(TRACE, "some msg");
 ///     logmsg!(TRACE, "some {}", msg);
 ///
 /// Other alternative log types are `DEBUG`, `INFO`, `WARN`, `ERROR`.
 ///
+/// The arguments are handed to tracing as they are, so fields are supported and the message is
+/// only formatted when the level is enabled
+///
+///     # use errlog::logmsg;
+///     # let user = "alice";
+///     logmsg!(INFO, user, attempt = 3, "login failed for {user}");
+///
 /// As a side note, you can override above log level through `RUST_LOG` env var.
 ///
 #[macro_export]
 macro_rules! logmsg {
-    (TRACE, $msg:literal) => {
-        tracing::trace!("{}", $msg);
-    };
-    (TRACE, $fmt:expr, $($arg:tt)*) => {
-        tracing::trace!("{}", format!($fmt, $($arg)*));
-    };
-
-    (DEBUG, $msg:literal) => {
-        tracing::debug!("{}", $msg);
-    };
-    (DEBUG, $fmt:expr, $($arg:tt)*) => {
-        tracing::debug!("{}", format!($fmt, $($arg)*));
-    };
-
-    (INFO, $msg:literal) => {
-        tracing::info!("{}", $msg);
-    };
-    (INFO, $fmt:expr, $($arg:tt)*) => {
-        tracing::info!("{}", format!($fmt, $($arg)*));
+    (TRACE, $($arg:tt)+) => {
+        if !$crate::__private::SILENT {
+            $crate::tracing::trace!($($arg)+);
+        }
     };
-
-    (WARN, $msg:literal) => {
-        tracing::warn!("{}", $msg);
+    (DEBUG, $($arg:tt)+) => {
+        if !$crate::__private::SILENT {
+            $crate::tracing::debug!($($arg)+);
+        }
     };
-    (WARN, $fmt:expr, $($arg:tt)*) => {
-        tracing::warn!("{}", format!($fmt, $($arg)*));
+    (INFO, $($arg:tt)+) => {
+        if !$crate::__private::SILENT {
+            $crate::tracing::info!($($arg)+);
+        }
     };
-
-    (ERROR, $msg:literal) => {
-        tracing::error!("{}", $msg);
+    (WARN, $($arg:tt)+) => {
+        if !$crate::__private::SILENT {
+            $crate::tracing::warn!($($arg)+);
+        }
     };
-    (ERROR, $fmt:expr, $($arg:tt)*) => {
-        tracing::error!("{}", format!($fmt, $($arg)*));
+    (ERROR, $($arg:tt)+) => {
+        if !$crate::__private::SILENT {
+            $crate::tracing::error!($($arg)+);
+        }
     };
 }
 
@@ -78,139 +296,1259 @@ macro_rules! logmsg {
 /// In default, wraperr log error in `ERROR` level, you can specify the level explicitly, for
 /// example
 ///
+///     # use errlog::{wraperr, Result};
+///     # use std::io::Read;
+///     # fn test(mut f: std::fs::File, filepath: &str) -> Result<()> {
+///     # let mut content = String::new();
 ///     wraperr!(TRACE, f.read_to_string(&mut content), "failed to read content from {}", filepath)?;
 ///     wraperr!(DEBUG, f.read_to_string(&mut content), "failed to read content from {}", filepath)?;
+///     # Ok(())
+///     # }
+///
+/// The level can also be picked from the error, by a closure receiving the error of the
+/// expression, for example to log transient failures at `WARN` only
+///
+///     use std::io::{self, ErrorKind};
+///
+///     use errlog::tracing::Level;
+///     use errlog::{wraperr, Result};
+///
+///     fn classify(err: &io::Error) -> Level {
+///         match err.kind() {
+///             ErrorKind::TimedOut | ErrorKind::ConnectionReset => Level::WARN,
+///             _ => Level::ERROR,
+///         }
+///     }
+///
+///     fn fetch(path: &str) -> Result<String> {
+///         wraperr!(level_fn = |e| classify(e), std::fs::read_to_string(path), "failed to fetch {}", path)
+///     }
+///     # assert!(fetch("/path/to/file").is_err());
 ///
 /// If you want to get backtrace error from anyhow, you can do as followings
 ///
+///     # use errlog::logmsg;
+///     # fn main() {
+///     # fn main() -> errlog::Result<()> { Ok(()) }
 ///     for err in errlog::backtrace_anyhow(main()) {
 ///         logmsg!(ERROR, "{err}");
 ///     }
+///     # }
 ///
 #[macro_export]
 macro_rules! wraperr {
+    (level_fn = $level_fn:expr, $expr:expr) => {
+        $crate::wraperr!(__level_fn $level_fn, $expr, |callsite| format!("{:?}", callsite))
+    };
+    (level_fn = $level_fn:expr, $expr:expr, $msg:literal $(,)?) => {
+        $crate::wraperr!(__level_fn $level_fn, $expr, |callsite| format!("{:?} => {}", callsite, $msg))
+    };
+    (level_fn = $level_fn:expr, $expr:expr, $fmt:expr, $($arg:tt)*) => {
+        $crate::wraperr!(
+            __level_fn $level_fn,
+            $expr,
+            |callsite| format!("{:?} => {}", callsite, format_args!($fmt, $($arg)*))
+        )
+    };
+
     (TRACE, $expr:expr) => {
-        wraperr!(__anyhow "TRACE", $expr)
+        $crate::wraperr!(__anyhow "TRACE", $expr)
     };
     (TRACE, $expr:expr, $msg:literal $(,)?) => {
-        wraperr!(__anyhow "TRACE", $expr, $msg)
+        $crate::wraperr!(__anyhow "TRACE", $expr, $msg)
     };
     (TRACE, $expr:expr, $fmt:expr, $($arg:tt)*) => {
-        wraperr!(__anyhow "TRACE", $expr, $fmt, $($arg)*)
+        $crate::wraperr!(__anyhow "TRACE", $expr, $fmt, $($arg)*)
     };
 
     (DEBUG, $expr:expr) => {
-        wraperr!(__anyhow "DEBUG", $expr)
+        $crate::wraperr!(__anyhow "DEBUG", $expr)
     };
     (DEBUG, $expr:expr, $msg:literal $(,)?) => {
-        wraperr!(__anyhow "DEBUG", $expr, $msg)
+        $crate::wraperr!(__anyhow "DEBUG", $expr, $msg)
     };
     (DEBUG, $expr:expr, $fmt:expr, $($arg:tt)*) => {
-        wraperr!(__anyhow "DEBUG", $expr, $fmt, $($arg)*)
+        $crate::wraperr!(__anyhow "DEBUG", $expr, $fmt, $($arg)*)
     };
 
     (INFO, $expr:expr) => {
-        wraperr!(__anyhow "INFO", $expr)
+        $crate::wraperr!(__anyhow "INFO", $expr)
     };
     (INFO, $expr:expr, $msg:literal $(,)?) => {
-        wraperr!(__anyhow "INFO", $expr, $msg)
+        $crate::wraperr!(__anyhow "INFO", $expr, $msg)
     };
     (INFO, $expr:expr, $fmt:expr, $($arg:tt)*) => {
-        wraperr!(__anyhow "INFO", $expr, $fmt, $($arg)*)
+        $crate::wraperr!(__anyhow "INFO", $expr, $fmt, $($arg)*)
     };
 
     (WARN, $expr:expr) => {
-        wraperr!(__anyhow "WARN", $expr)
+        $crate::wraperr!(__anyhow "WARN", $expr)
     };
     (WARN, $expr:expr, $msg:literal $(,)?) => {
-        wraperr!(__anyhow "WARN", $expr, $msg)
+        $crate::wraperr!(__anyhow "WARN", $expr, $msg)
     };
     (WARN, $expr:expr, $fmt:expr, $($arg:tt)*) => {
-        wraperr!(__anyhow "WARN", $expr, $fmt, $($arg)*)
+        $crate::wraperr!(__anyhow "WARN", $expr, $fmt, $($arg)*)
     };
 
     (ERROR, $expr:expr) => {
-        wraperr!(__anyhow "ERROR", $expr)
+        $crate::wraperr!(__anyhow "ERROR", $expr)
     };
     (ERROR, $expr:expr, $msg:literal $(,)?) => {
-        wraperr!(__anyhow "ERROR", $expr, $msg)
+        $crate::wraperr!(__anyhow "ERROR", $expr, $msg)
     };
     (ERROR, $expr:expr, $fmt:expr, $($arg:tt)*) => {
-        wraperr!(__anyhow "ERROR", $expr, $fmt, $($arg)*)
+        $crate::wraperr!(__anyhow "ERROR", $expr, $fmt, $($arg)*)
     };
     ($expr:expr) => {
-        wraperr!(__anyhow "ERROR", $expr)
+        $crate::wraperr!(__anyhow "ERROR", $expr)
     };
     ($expr:expr, $msg:literal $(,)?) => {
-        wraperr!(__anyhow "ERROR", $expr, $msg)
+        $crate::wraperr!(__anyhow "ERROR", $expr, $msg)
     };
     ($expr:expr, $fmt:expr, $($arg:tt)*) => {
-        wraperr!(__anyhow "ERROR", $expr, $fmt, $($arg)*)
+        $crate::wraperr!(__anyhow "ERROR", $expr, $fmt, $($arg)*)
     };
 
     (__anyhowmsg $typ:literal, $msg:expr) => {
         match $typ {
-            "TRACE" => {
-                tracing::trace!("{}", $msg);
-            }
-            "DEBUG" => {
-                tracing::debug!("{}", $msg);
-            }
-            "INFO" => {
-                tracing::info!("{}", $msg);
-            }
-            "WARN" => {
-                tracing::warn!("{}", $msg);
-            }
-            "ERROR" => {
-                tracing::error!("{}", $msg);
-            }
+            "TRACE" => $crate::__event!(TRACE, $msg),
+            "DEBUG" => $crate::__event!(DEBUG, $msg),
+            "INFO" => $crate::__event!(INFO, $msg),
+            "WARN" => $crate::__event!(WARN, $msg),
+            "ERROR" => $crate::__event!(ERROR, $msg),
             _ => {}
         }
     };
 
+    (__wrapmsg $typ:literal, $msg:expr) => {
+        match $typ {
+            "TRACE" => $crate::__event!(TRACE, span, $msg),
+            "DEBUG" => $crate::__event!(DEBUG, span, $msg),
+            "INFO" => $crate::__event!(INFO, span, $msg),
+            "WARN" => $crate::__event!(WARN, span, $msg),
+            "ERROR" => $crate::__event!(ERROR, span, $msg),
+            _ => {}
+        }
+    };
+
+    (__level_fn $level_fn:expr, $expr:expr, $msg:expr) => {
+        match $expr {
+            ::core::result::Result::Ok(value) => ::core::result::Result::Ok(value),
+            ::core::result::Result::Err(err) => {
+                let level = $crate::__private::pick_level(&err, $level_fn);
+                let callsite: &'static $crate::Location = $crate::__callsite!();
+                let msg: String = ($msg)(callsite);
+                match level {
+                    $crate::tracing::Level::TRACE => $crate::__event!(TRACE, span, msg),
+                    $crate::tracing::Level::DEBUG => $crate::__event!(DEBUG, span, msg),
+                    $crate::tracing::Level::INFO => $crate::__event!(INFO, span, msg),
+                    $crate::tracing::Level::WARN => $crate::__event!(WARN, span, msg),
+                    _ => $crate::__event!(ERROR, span, msg),
+                }
+                let err: $crate::Error = ::core::convert::Into::into(err);
+                ::core::result::Result::Err($crate::__private::wrap_context($crate::__private::context(err, *callsite)))
+            }
+        }
+    };
+
     (__anyhow $typ:literal, $expr:expr) => {
         {
             use $crate::Context;
             $expr.with_context(|| {
-                let msg = format!("{}:{}", file!(), line!());
-                wraperr!(__anyhowmsg $typ, msg);
-                "".to_string()
+                let callsite: &'static $crate::Location = $crate::__callsite!();
+                $crate::wraperr!(__wrapmsg $typ, format_args!("{:?}", callsite));
+                *callsite
             })
+            .map_err($crate::__private::wrap_context)
         }
     };
     (__anyhow $typ:literal, $expr:expr, $msg:literal $(,)?) => {
         {
             use $crate::Context;
             $expr.with_context(|| {
-                let msg = format!("{}:{} => {}", file!(), line!(), $msg);
-                wraperr!(__anyhowmsg $typ, msg);
-                "".to_string()
+                let callsite: &'static $crate::Location = $crate::__callsite!();
+                $crate::wraperr!(__wrapmsg $typ, format_args!("{:?} => {}", callsite, $msg));
+                *callsite
             })
+            .map_err($crate::__private::wrap_context)
         }
     };
     (__anyhow $typ:literal, $expr:expr, $fmt:expr, $($arg:tt)*) => {
         {
             use $crate::Context;
             $expr.with_context(|| {
-                let msg = format!("{}:{} => {}", file!(), line!(), format!($fmt, $($arg)*));
-                wraperr!(__anyhowmsg $typ, msg);
-                "".to_string()
+                let callsite: &'static $crate::Location = $crate::__callsite!();
+                $crate::wraperr!(__wrapmsg $typ, format_args!("{:?} => {}", callsite, format_args!($fmt, $($arg)*)));
+                *callsite
             })
+            .map_err($crate::__private::wrap_context)
         }
     };
 }
 
+/// Log a typed error the moment it is created, then evaluate to the error itself
+///
+/// This bridges error enums such as the ones defined with thiserror into errlog's logging
+/// conventions, the event is `file:line => message` where the message is the `Display` output of
+/// the error, for example
+///
+///     use errlog::logvariant;
+///     use std::fmt;
+///
+///     #[derive(Debug)]
+///     enum MyError {
+///         ConnectionLost { addr: String },
+///     }
+///
+///     impl fmt::Display for MyError {
+///         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///             match self {
+///                 MyError::ConnectionLost { addr } => write!(f, "connection to {} lost", addr),
+///             }
+///         }
+///     }
+///
+///     fn send(addr: String) -> Result<(), MyError> {
+///         Err(logvariant!(MyError::ConnectionLost { addr }, WARN))
+///     }
+///
+/// In default, the error is logged in `ERROR` level.
+#[macro_export]
+macro_rules! logvariant {
+    (__log $typ:literal, $err:expr) => {
+        {
+            let err = $err;
+            let callsite: &'static $crate::Location = $crate::__callsite!();
+            $crate::wraperr!(__anyhowmsg $typ, format_args!("{:?} => {}", callsite, err));
+            err
+        }
+    };
+
+    ($err:expr, TRACE) => {
+        $crate::logvariant!(__log "TRACE", $err)
+    };
+    ($err:expr, DEBUG) => {
+        $crate::logvariant!(__log "DEBUG", $err)
+    };
+    ($err:expr, INFO) => {
+        $crate::logvariant!(__log "INFO", $err)
+    };
+    ($err:expr, WARN) => {
+        $crate::logvariant!(__log "WARN", $err)
+    };
+    ($err:expr, ERROR) => {
+        $crate::logvariant!(__log "ERROR", $err)
+    };
+    ($err:expr) => {
+        $crate::logvariant!(__log "ERROR", $err)
+    };
+}
+
+/// Log the message at the given level, then return it as an error tagged with that [Severity]
+///
+/// Meant for expected, non-fatal failures, where the top-level handler should know the error is
+/// benign, for example
+///
+///     use errlog::{assert_err_chain, bail_with_level, warnbail, Result};
+///     use errlog::tracing::Level;
+///
+///     fn parse_entry(id: u32, raw: &str) -> Result<u32> {
+///         if raw.is_empty() {
+///             warnbail!("skipping corrupt entry {}", id);
+///         }
+///         if raw == "-" {
+///             bail_with_level!(INFO, "entry {} is a placeholder", id);
+///         }
+///         Ok(raw.len() as u32)
+///     }
+///
+///     assert_err_chain!(parse_entry(7, ""), ["skipping corrupt entry 7"]);
+///     assert_eq!(errlog::severity(&parse_entry(7, "").unwrap_err()), Some(Level::WARN));
+///     assert_eq!(errlog::severity(&parse_entry(7, "-").unwrap_err()), Some(Level::INFO));
+///
+/// The levels are `TRACE`, `DEBUG`, `INFO`, `WARN` and `ERROR`, and the [Location] of the bail is
+/// attached like [wraperr] does.
+#[macro_export]
+macro_rules! bail_with_level {
+    ($level:ident, $($arg:tt)+) => {{
+        let callsite: &'static $crate::Location = $crate::__callsite!();
+        let msg = format!($($arg)+);
+        $crate::__event!($level, format_args!("{:?} => {}", callsite, msg));
+        return ::core::result::Result::Err($crate::__private::bail(
+            $crate::Severity($crate::tracing::Level::$level),
+            *callsite,
+            msg,
+        ));
+    }};
+}
+
+/// [bail_with_level] at `WARN`
+#[macro_export]
+macro_rules! warnbail {
+    ($($arg:tt)+) => {
+        $crate::bail_with_level!(WARN, $($arg)+)
+    };
+}
+
+/// Evaluate to the value of a result, or log its error and evaluate to `Default::default()`
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::{or_log_default, Result};
+///
+///     #[derive(Default)]
+///     struct Config {
+///         retries: u32,
+///     }
+///
+///     fn load_config(path: &str) -> Result<Config> {
+///         Err(errlog::Error::msg(format!("{} not found", path)))
+///     }
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///     let config = or_log_default!(load_config("app.toml"), WARN);
+///     assert_eq!(config.retries, 0);
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("app.toml not found, using the default"));
+///
+/// The error is logged with its chain and the location of the macro, in `WARN` level by default.
+#[macro_export]
+macro_rules! or_log_default {
+    ($expr:expr $(,)?) => {
+        $crate::or_log_default!($expr, WARN)
+    };
+    ($expr:expr, $level:ident $(,)?) => {
+        match $expr {
+            ::core::result::Result::Ok(value) => value,
+            ::core::result::Result::Err(err) => {
+                let err: $crate::Error = ::core::convert::Into::into(err);
+                let callsite: &'static $crate::Location = $crate::__callsite!();
+                $crate::__event!(
+                    $level,
+                    format_args!("{:?} => {}, using the default", callsite, $crate::__private::chain_string(&err))
+                );
+                ::core::default::Default::default()
+            }
+        }
+    };
+}
+
+/// Like `dbg!`, but the value goes to tracing at `DEBUG` instead of stderr, then is returned
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::logdbg;
+///     use errlog::tracing::Level;
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().level(Level::DEBUG).writer(output.clone()).set_default();
+///     let total = logdbg!(2 + 3) * 2;
+///     assert_eq!(total, 10);
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("=> 2 + 3 = 5"));
+///
+/// The event is `file:line => expr = value` with the pretty `Debug` output of the value, it is
+/// only formatted when `DEBUG` is enabled. Several expressions return a tuple, and no expression
+/// logs the location only.
+#[macro_export]
+macro_rules! logdbg {
+    () => {
+        $crate::__event!(DEBUG, format_args!("{:?}", $crate::__callsite!()))
+    };
+    ($expr:expr $(,)?) => {
+        match $expr {
+            value => {
+                $crate::__event!(
+                    DEBUG,
+                    format_args!("{:?} => {} = {:#?}", $crate::__callsite!(), stringify!($expr), &value)
+                );
+                value
+            }
+        }
+    };
+    ($($expr:expr),+ $(,)?) => {
+        ($($crate::logdbg!($expr)),+,)
+    };
+}
+
+/// Log a value with a label, then return it unchanged, to look inside iterator and builder chains
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::loginspect;
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///     let even: Vec<u32> = (1..=4)
+///         .filter(|x| x % 2 == 0)
+///         .map(|x| loginspect!(INFO, "item", x))
+///         .collect();
+///     assert_eq!(even, [2, 4]);
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("=> item: 4"));
+///
+/// The event is `file:line => label: value` with the `Debug` output of the value, only formatted
+/// when the level is enabled. Without a label, the expression is used instead.
+#[macro_export]
+macro_rules! loginspect {
+    ($level:ident, $label:literal, $value:expr $(,)?) => {
+        $crate::loginspect!(__log $level, $label, $value)
+    };
+    ($level:ident, $value:expr $(,)?) => {
+        $crate::loginspect!(__log $level, stringify!($value), $value)
+    };
+
+    (__log $level:ident, $label:expr, $value:expr) => {
+        match $value {
+            value => {
+                $crate::__event!($level, format_args!("{:?} => {}: {:?}", $crate::__callsite!(), $label, &value));
+                value
+            }
+        }
+    };
+}
+
+/// Log every Nth time the macro is reached, with the rate since the previous event
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::logprogress;
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///     let total = 25_000;
+///     for i in 1..=total {
+///         logprogress!(INFO, every = 10_000, "processed {i}/{total} rows");
+///     }
+///     # #[cfg(not(feature = "silent"))]
+///     assert_eq!(output.contents().lines().count(), 2);
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("processed 20000/25000 rows"));
+///
+/// Each call site counts its own hits, across threads. The event carries the `rate` field, the
+/// hits per second since the previous event of the call site, or since its first hit.
+#[macro_export]
+macro_rules! logprogress {
+    ($level:ident, every = $every:expr, $($arg:tt)+) => {{
+        static PROGRESS: $crate::__private::Progress = $crate::__private::Progress::new();
+        if let Some(rate) = PROGRESS.hit($every) {
+            $crate::logmsg!($level, rate, $($arg)+);
+        }
+    }};
+}
+
+/// Evaluate to `Some` value of an option or a result, or log a message and evaluate to `None`
+///
+///     use std::collections::HashMap;
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::ensure_or_log;
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///     let limits = HashMap::from([("cpu", 4)]);
+///     let k = "memory";
+///     let limit: Option<&i32> = ensure_or_log!(limits.get(k), WARN, "missing key {k}");
+///     assert_eq!(limit, None);
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("missing key memory"));
+///
+///     let port: Option<u16> = ensure_or_log!("80a".parse::<u16>(), WARN, "bad port");
+///     assert_eq!(port, None);
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("bad port: invalid digit found in string"));
+///
+/// The event is `file:line => message`, followed by the chain of the error for a result. For
+/// checks which must stop the caller, use [wraperr] with `?` instead.
+#[macro_export]
+macro_rules! ensure_or_log {
+    ($expr:expr, $level:ident, $($arg:tt)+) => {
+        match $crate::__private::Checked::checked($expr) {
+            ::core::result::Result::Ok(value) => ::core::option::Option::Some(value),
+            ::core::result::Result::Err(err) => {
+                let callsite: &'static $crate::Location = $crate::__callsite!();
+                match err {
+                    ::core::option::Option::Some(err) => $crate::__event!(
+                        $level,
+                        format_args!("{:?} => {}: {}", callsite, format_args!($($arg)+), $crate::__private::chain_string(&err))
+                    ),
+                    ::core::option::Option::None => {
+                        $crate::__event!($level, format_args!("{:?} => {}", callsite, format_args!($($arg)+)))
+                    }
+                }
+                ::core::option::Option::None
+            }
+        }
+    };
+}
+
+/// Log a failed assertion at `ERROR` instead of panicking
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::{soft_assert, soft_assert_eq};
+///
+///     # if cfg!(feature = "soft-assert-panic") { return; }
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///     let (reserved, stock) = (5, 3);
+///     soft_assert!(reserved <= stock, "reserved {} of {} items", reserved, stock);
+///     soft_assert_eq!(stock % 2, 0);
+///
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("reserved 5 of 3 items"));
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("assertion `left == right` failed, left: 1, right: 0"));
+///     assert_eq!(errlog::soft_assert_failures(), 2);
+///
+/// The event is `file:line => message` with the `soft_assert` field set to true, and
+/// [soft_assert_failures] counts the failures for metrics. With the `soft-assert-panic` feature,
+/// debug builds and tests panic like `assert!`.
+#[macro_export]
+macro_rules! soft_assert {
+    ($cond:expr $(,)?) => {
+        $crate::soft_assert!($cond, "assertion failed: {}", stringify!($cond))
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            let msg = format!($($arg)+);
+            $crate::logmsg!(ERROR, soft_assert = true, "{:?} => {}", $crate::__callsite!(), msg);
+            $crate::__private::soft_assert_failed(msg, cfg!(debug_assertions));
+        }
+    };
+}
+
+/// [soft_assert] that two expressions are equal, logging both values when they differ
+#[macro_export]
+macro_rules! soft_assert_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::soft_assert_eq!(__check $left, $right, "assertion `left == right` failed")
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        $crate::soft_assert_eq!(__check $left, $right, "assertion `left == right` failed: {}", format_args!($($arg)+))
+    };
+
+    (__check $left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if !(*left == *right) {
+                    $crate::soft_assert!(false, "{}, left: {:?}, right: {:?}", format_args!($($arg)+), left, right);
+                }
+            }
+        }
+    };
+}
+
+/// How many [soft_assert] and [soft_assert_eq] failed in this process
+pub fn soft_assert_failures() -> u64 {
+    __private::SOFT_ASSERT_FAILURES.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Emit the event of [wraperr], [logvariant], [bail_with_level], [or_log_default], [logdbg],
+/// [loginspect] and [ensure_or_log], carrying the `err_id` field with the `err-id` feature
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __event {
+    ($level:ident, span, $msg:expr) => {
+        if !$crate::__private::SILENT {
+            let span = $crate::__private::span_field();
+            if $crate::__private::ERR_ID {
+                const ERR_ID: [u8; 6] = $crate::__private::err_id(module_path!(), file!(), line!());
+                $crate::tracing::event!(
+                    $crate::tracing::Level::$level,
+                    err_id = $crate::__private::err_id_str(&ERR_ID),
+                    span = span.as_deref(),
+                    "{}",
+                    $msg
+                );
+            } else {
+                $crate::tracing::event!($crate::tracing::Level::$level, span = span.as_deref(), "{}", $msg);
+            }
+        }
+    };
+    ($level:ident, $msg:expr) => {
+        if !$crate::__private::SILENT {
+            if $crate::__private::ERR_ID {
+                const ERR_ID: [u8; 6] = $crate::__private::err_id(module_path!(), file!(), line!());
+                $crate::tracing::event!(
+                    $crate::tracing::Level::$level,
+                    err_id = $crate::__private::err_id_str(&ERR_ID),
+                    "{}",
+                    $msg
+                );
+            } else {
+                $crate::tracing::event!($crate::tracing::Level::$level, "{}", $msg);
+            }
+        }
+    };
+}
+
+/// The static [Location] of the invocation, see the `no-location` and `hash-location` features
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __callsite {
+    () => {{
+        const HASHED: [u8; 9] = $crate::__private::hash_file(file!());
+        static CALLSITE: $crate::Location =
+            $crate::Location::new($crate::__private::location_file(file!(), &HASHED), line!());
+        &CALLSITE
+    }};
+}
+
 /// Convert anyhow::Result into a list of string if the result is Error
+///
+/// Like every chain errlog renders, it stops at the [chain limit](set_chain_limit).
 pub fn backtrace_anyhow<T>(err: Result<T>) -> Vec<String> {
-    let mut errmsg = vec![];
-    if let Err(err) = err {
-        err.chain().skip(1).for_each(|cause| {
-            let cause = cause.to_string();
-            if cause.len() > 0 {
-                errmsg.push(cause);
+    match err {
+        Ok(_) => vec![],
+        Err(err) => limited_messages(err.chain().skip(1).map(|cause| cause as &(dyn std::error::Error + 'static))),
+    }
+}
+
+static CHAIN_LIMIT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(64);
+
+/// The last message of the chains cut by the [chain limit](set_chain_limit) or by a cycle
+pub const CHAIN_TRUNCATED: &str = "... (chain truncated)";
+
+/// Render at most `depth` errors of every chain, 64 by default
+///
+///     use std::fmt;
+///
+///     #[derive(Debug)]
+///     struct Looping;
+///
+///     impl fmt::Display for Looping {
+///         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///             f.write_str("looping")
+///         }
+///     }
+///
+///     impl std::error::Error for Looping {
+///         fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+///             Some(self)
+///         }
+///     }
+///
+///     errlog::set_chain_limit(16);
+///     let report = errlog::ErrorReport::new(&errlog::Error::new(Looping));
+///     assert_eq!(report.message, "looping");
+///     assert_eq!(report.causes, [errlog::CHAIN_TRUNCATED]);
+///
+/// The chains rendered by the [logger], the [reports](report::pretty), [ErrorReport] and the
+/// macros end with [CHAIN_TRUNCATED] when longer, or when a source is repeated, so a buggy
+/// `source` implementation cannot hang them.
+pub fn set_chain_limit(depth: usize) {
+    CHAIN_LIMIT.store(depth.max(1), std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Iterator stopping at the [chain limit](set_chain_limit) or at a repeated error
+pub(crate) struct Limited<I> {
+    chain: I,
+    seen: Vec<*const ()>,
+    limit: usize,
+    truncated: bool,
+}
+
+impl<'a, I: Iterator<Item = &'a (dyn std::error::Error + 'static)>> Iterator for Limited<I> {
+    type Item = &'a (dyn std::error::Error + 'static);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.truncated {
+            return None;
+        }
+        let cause = self.chain.next()?;
+        let address = cause as *const dyn std::error::Error as *const ();
+        if self.seen.len() >= self.limit || self.seen.contains(&address) {
+            self.truncated = true;
+            return None;
+        }
+        self.seen.push(address);
+        Some(cause)
+    }
+}
+
+pub(crate) fn limited<'a, I>(chain: I) -> Limited<I>
+where
+    I: Iterator<Item = &'a (dyn std::error::Error + 'static)>,
+{
+    let limit = CHAIN_LIMIT.load(std::sync::atomic::Ordering::Relaxed);
+    Limited { chain, seen: vec![], limit, truncated: false }
+}
+
+/// The chain of the error, outermost first, protected like [limited]
+pub(crate) fn chain(err: &Error) -> impl Iterator<Item = &(dyn std::error::Error + 'static)> {
+    limited(err.chain().map(|cause| cause as &(dyn std::error::Error + 'static)))
+}
+
+/// The non-empty messages of the chain, ending with [CHAIN_TRUNCATED] when cut
+pub(crate) fn limited_messages<'a>(chain: impl Iterator<Item = &'a (dyn std::error::Error + 'static)>) -> Vec<String> {
+    let mut chain = limited(chain);
+    let mut messages: Vec<String> =
+        chain.by_ref().map(|cause| scrub(cause.to_string())).filter(|msg| !msg.is_empty()).collect();
+    if chain.truncated {
+        messages.push(CHAIN_TRUNCATED.to_string());
+    }
+    messages
+}
+
+/// The non-empty messages of the chain like [limited_messages], with the location where each
+/// error was wrapped by [wraperr], if any
+pub(crate) fn located_messages<'a>(
+    chain: impl Iterator<Item = &'a (dyn std::error::Error + 'static)>,
+) -> Vec<(String, Option<String>)> {
+    let mut chain = limited(chain);
+    let mut messages = vec![];
+    let mut wrapped = None;
+    location::shown(|| {
+        for cause in chain.by_ref() {
+            let message = cause.to_string();
+            // a location applies to the error it wraps, which comes next
+            match message.strip_prefix(location::MARKER) {
+                Some(location) => wrapped = Some(location.to_string()),
+                None if message.is_empty() || message.starts_with(meta::MARKER) => {}
+                None => messages.push((scrub(message), wrapped.take())),
+            }
+        }
+    });
+    if chain.truncated {
+        messages.push((CHAIN_TRUNCATED.to_string(), None));
+    }
+    messages
+}
+
+/// The error as a `dyn Error`, so that it can be recorded as a tracing field
+///
+/// Subscribers then see the whole source chain, which the [logger] renders on separate lines
+/// with [multiline_errors](logger::Builder::multiline_errors), and the logger adds the [meta] of
+/// the error as fields
+pub fn as_dyn_error(err: &Error) -> &(dyn std::error::Error + 'static) {
+    err.as_ref()
+}
+
+/// Get the kind of the first `io::Error` in the chain
+///
+/// [wraperr] only adds contexts on top of the original error, so the source chain is never lost
+/// and the kind is still reachable however many times the error is wrapped
+///
+///     use errlog::{wraperr, Result};
+///     use std::io::ErrorKind;
+///
+///     fn load() -> Result<String> {
+///         let content = wraperr!(std::fs::read_to_string("/path/to/config"), "failed to read")?;
+///         Ok(content)
+///     }
+///
+///     fn setup() -> Result<String> {
+///         wraperr!(WARN, load(), "failed to load config")
+///     }
+///
+///     assert_eq!(errlog::io_kind(&setup().unwrap_err()), Some(ErrorKind::NotFound));
+pub fn io_kind(err: &Error) -> Option<std::io::ErrorKind> {
+    chain(err)
+        .find_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .map(|err| err.kind())
+}
+
+/// Branch on the [io_kind] of an error, the `_` arm is required and must come last
+///
+///     use errlog::match_io_kind;
+///
+///     # let err = errlog::Error::new(std::io::Error::from(std::io::ErrorKind::NotFound));
+///     let action = match_io_kind!(err, {
+///         NotFound => "create it",
+///         PermissionDenied | ReadOnlyFilesystem => "ask for permission",
+///         _ => "give up",
+///     });
+///     assert_eq!(action, "create it");
+#[macro_export]
+macro_rules! match_io_kind {
+    ($err:expr, { $($($kind:ident)|+ => $arm:expr,)* _ => $default:expr $(,)? }) => {
+        match $crate::io_kind(&$err) {
+            $(Some($(::std::io::ErrorKind::$kind)|+) => $arm,)*
+            _ => $default,
+        }
+    };
+}
+
+/// Log an error at a level picked by the type of one of its causes, then wrap it like [wraperr]
+///
+///     use std::io::{self, ErrorKind};
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::{matcherr, Result};
+///
+///     fn load(path: &str) -> Result<String> {
+///         matcherr!(std::fs::read_to_string(path), {
+///             io::Error where |err| err.kind() == ErrorKind::NotFound => INFO("no config at {}", path),
+///             io::Error where |err| err.kind() == ErrorKind::PermissionDenied => WARN,
+///             _ => ERROR,
+///         }, "failed to load {}", path)
+///     }
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///     assert!(errlog::location(&load("/path/to/config").unwrap_err()).is_some());
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("INFO"));
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("no config at /path/to/config: No such file"));
+///
+/// The first arm whose type is found in the chain and whose optional `where` closure returns
+/// true picks the level and the message of the event, the `_` arm is required and must come
+/// last. Without a message in the arm, the message after the arms is used, the event ends with
+/// the chain of the error.
+#[macro_export]
+macro_rules! matcherr {
+    ($expr:expr, { $($arms:tt)+ } $(,)?) => {
+        $crate::matcherr!(__match $expr, { $($arms)+ }, None)
+    };
+    ($expr:expr, { $($arms:tt)+ }, $fmt:expr $(, $arg:expr)* $(,)?) => {
+        $crate::matcherr!(__match $expr, { $($arms)+ }, Some(format!($fmt $(, $arg)*)))
+    };
+
+    (__match $expr:expr, { $($arms:tt)+ }, $msg:expr) => {
+        match $expr {
+            ::core::result::Result::Ok(value) => ::core::result::Result::Ok(value),
+            ::core::result::Result::Err(err) => {
+                let err: $crate::Error = ::core::convert::Into::into(err);
+                let msg: Option<String> = $msg;
+                let callsite: &'static $crate::Location = $crate::__callsite!();
+                $crate::matcherr!(__arm err, callsite, msg; $($arms)+);
+                ::core::result::Result::Err($crate::__private::wrap_context($crate::__private::context(err, *callsite)))
+            }
+        }
+    };
+
+    (__arm $err:ident, $callsite:ident, $msg:ident; _ => $level:ident $(($($arm_msg:tt)+))? $(,)?) => {{
+        let msg = $msg;
+        $( let msg = Some(format!($($arm_msg)+)); )?
+        $crate::__event!($level, span, $crate::__private::matched_message($callsite, msg, &$err));
+    }};
+    (__arm $err:ident, $callsite:ident, $msg:ident;
+        $ty:ty $(where $guard:expr)? => $level:ident $(($($arm_msg:tt)+))?, $($rest:tt)+
+    ) => {
+        let matched = $crate::__private::find_cause::<$ty>(&$err)
+            $( .filter(|cause| $crate::__private::matches_guard(*cause, $guard)) )?
+            .is_some();
+        if matched {
+            let msg = $msg.clone();
+            $( let msg = Some(format!($($arm_msg)+)); )?
+            $crate::__event!($level, span, $crate::__private::matched_message($callsite, msg, &$err));
+        } else {
+            $crate::matcherr!(__arm $err, $callsite, $msg; $($rest)+);
+        }
+    };
+}
+
+/// Assert the result is an error whose chain contains each expected substring in order
+///
+/// Every substring must be found in a message of the chain at or after the message matching the
+/// previous substring, empty messages left by [wraperr] are ignored
+///
+///     use errlog::{assert_err_chain, wraperr, Context, Result};
+///
+///     fn load() -> Result<String> {
+///         wraperr!(std::fs::read_to_string("/path/to/config"), "failed to read")
+///             .context("failed to load config")
+///     }
+///
+///     assert_err_chain!(load(), ["failed to load config", "No such file"]);
+///
+/// On mismatch it panics with a diff-style message showing which substrings were found where,
+/// and the whole actual chain.
+#[macro_export]
+macro_rules! assert_err_chain {
+    ($result:expr, [$($expected:expr),* $(,)?] $(,)?) => {
+        match &$result {
+            Ok(_) => panic!("assertion failed: expected an error, got Ok"),
+            Err(err) => {
+                if let Err(msg) = $crate::__private::check_err_chain(err, &[$($expected),*]) {
+                    panic!("{}", msg);
+                }
+            }
+        }
+    };
+}
+
+/// Collect all non-empty messages in the error chain, outermost first
+fn chain_messages(err: &Error) -> Vec<String> {
+    limited_messages(err.chain().map(|cause| cause as &(dyn std::error::Error + 'static)))
+}
+
+/// The message scrubbed by the installed [Scrubber](crate::scrub::Scrubber), if any
+fn scrub(msg: String) -> String {
+    #[cfg(feature = "scrub")]
+    return crate::scrub::apply(msg);
+    #[cfg(not(feature = "scrub"))]
+    msg
+}
+
+/// The message of a panic payload
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+
+    pub use inventory;
+    pub use crate::code::{CodeLookup, lookup_code};
+
+    #[cfg(feature = "tokio")]
+    pub async fn sleep(delay: std::time::Duration) {
+        tokio::time::sleep(delay).await
+    }
+
+    /// Wrapper used to summarize function arguments through autoref specialization
+    pub struct ArgWrap<'a, T: ?Sized>(pub &'a T);
+
+    pub trait DebugArg {
+        fn summary(&self) -> String;
+    }
+
+    impl<T: std::fmt::Debug + ?Sized> DebugArg for ArgWrap<'_, T> {
+        fn summary(&self) -> String {
+            const MAX_LEN: usize = 64;
+            let mut summary = format!("{:?}", self.0);
+            if summary.len() > MAX_LEN {
+                let mut end = MAX_LEN;
+                while !summary.is_char_boundary(end) {
+                    end -= 1;
+                }
+                summary.truncate(end);
+                summary.push_str("...");
+            }
+            summary
+        }
+    }
+
+    pub trait OpaqueArg {
+        fn summary(&self) -> String;
+    }
+
+    impl<T: ?Sized> OpaqueArg for &ArgWrap<'_, T> {
+        fn summary(&self) -> String {
+            "_".to_string()
+        }
+    }
+
+    /// Whether the macros are compiled without their events, see the `silent` feature
+    pub const SILENT: bool = cfg!(feature = "silent");
+
+    /// Whether the macros add the `err_id` field to their events, see the `err-id` feature
+    pub const ERR_ID: bool = cfg!(feature = "err-id");
+
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    /// 32-bit FNV-1a of `bytes[..len]`, continuing from `hash`
+    const fn fnv1a(mut hash: u32, bytes: &[u8], len: usize) -> u32 {
+        let mut i = 0;
+        while i < len {
+            hash ^= bytes[i] as u32;
+            hash = hash.wrapping_mul(0x01000193);
+            i += 1;
+        }
+        hash
+    }
+
+    /// Hash the file path into `#` followed by 8 hex digits (FNV-1a)
+    pub const fn hash_file(file: &str) -> [u8; 9] {
+        let hash = fnv1a(0x811c9dc5, file.as_bytes(), file.len());
+        let mut out = [b'#'; 9];
+        let mut i = 0;
+        while i < 8 {
+            out[8 - i] = HEX[((hash >> (i * 4)) & 0xf) as usize];
+            i += 1;
+        }
+        out
+    }
+
+    /// Derive the `err_id` of a callsite from the crate name, the file and the line, as 6 hex
+    /// digits
+    pub const fn err_id(module_path: &str, file: &str, line: u32) -> [u8; 6] {
+        let module = module_path.as_bytes();
+        let mut crate_len = 0;
+        while crate_len < module.len() && module[crate_len] != b':' {
+            crate_len += 1;
+        }
+        let hash = fnv1a(0x811c9dc5, module, crate_len);
+        let hash = fnv1a(hash, file.as_bytes(), file.len());
+        let hash = fnv1a(hash, &line.to_le_bytes(), 4);
+        let hash = (hash >> 24) ^ (hash & 0xffffff);
+        let mut out = [0; 6];
+        let mut i = 0;
+        while i < 6 {
+            out[5 - i] = HEX[((hash >> (i * 4)) & 0xf) as usize];
+            i += 1;
+        }
+        out
+    }
+
+    pub const fn err_id_str(id: &'static [u8; 6]) -> &'static str {
+        match std::str::from_utf8(id) {
+            Ok(id) => id,
+            Err(_) => "",
+        }
+    }
+
+    /// The file recorded in locations, evaluated at compile time so that the path of a stripped
+    /// location never reaches the binary
+    pub const fn location_file(file: &'static str, hashed: &'static [u8; 9]) -> &'static str {
+        if cfg!(feature = "no-location") {
+            ""
+        } else if cfg!(feature = "hash-location") {
+            match std::str::from_utf8(hashed) {
+                Ok(hashed) => hashed,
+                Err(_) => "",
+            }
+        } else {
+            file
+        }
+    }
+
+    /// Attach the diagnostic context and the current span of this thread, see
+    /// [context](crate::context) and [ErrorSpan]
+    pub fn wrap_context(err: Error) -> Error {
+        let err = crate::context::wrap(err);
+        match ErrorSpan::current() {
+            Some(span) if error_span(&err) != Some(span) => context(err, span),
+            _ => err,
+        }
+    }
+
+    /// The outermost cause of type `T`, attached or in the chain
+    pub fn find_cause<T>(err: &Error) -> Option<&T>
+    where
+        T: std::error::Error + Send + Sync + 'static,
+    {
+        err.downcast_ref::<T>().or_else(|| crate::chain(err).find_map(|cause| cause.downcast_ref::<T>()))
+    }
+
+    /// The values [ensure_or_log](crate::ensure_or_log) checks, an option or a result
+    pub trait Checked<T> {
+        fn checked(self) -> std::result::Result<T, Option<Error>>;
+    }
+
+    impl<T> Checked<T> for Option<T> {
+        fn checked(self) -> std::result::Result<T, Option<Error>> {
+            self.ok_or(None)
+        }
+    }
+
+    impl<T, E: Into<Error>> Checked<T> for std::result::Result<T, E> {
+        fn checked(self) -> std::result::Result<T, Option<Error>> {
+            self.map_err(|err| Some(err.into()))
+        }
+    }
+
+    pub static SOFT_ASSERT_FAILURES: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+    /// Count a failed [soft_assert](crate::soft_assert), panicking in debug builds with the
+    /// `soft-assert-panic` feature
+    #[track_caller]
+    pub fn soft_assert_failed(msg: String, debug: bool) {
+        SOFT_ASSERT_FAILURES.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        if cfg!(feature = "soft-assert-panic") && debug {
+            panic!("{}", msg);
+        }
+    }
+
+    /// The hit counter of a [logprogress](crate::logprogress) call site
+    pub struct Progress {
+        hits: std::sync::atomic::AtomicU64,
+        last: std::sync::Mutex<Option<std::time::Instant>>,
+    }
+
+    impl Progress {
+        pub const fn new() -> Self {
+            Progress { hits: std::sync::atomic::AtomicU64::new(0), last: std::sync::Mutex::new(None) }
+        }
+
+        /// Count a hit, the rate in hits per second when it is the Nth
+        ///
+        /// Only the first hit and every Nth one read the clock and lock, to start the windows.
+        pub fn hit(&self, every: u64) -> Option<f64> {
+            let every = every.max(1);
+            let hits = self.hits.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+            if hits != 1 && !hits.is_multiple_of(every) {
+                return None;
+            }
+            let now = std::time::Instant::now();
+            let mut last = self.last.lock().unwrap_or_else(|err| err.into_inner());
+            let since = *last.get_or_insert(now);
+            if !hits.is_multiple_of(every) {
+                return None;
+            }
+            *last = Some(now);
+            let elapsed = now.duration_since(since).as_secs_f64();
+            let rate = match elapsed > 0.0 {
+                true => every as f64 / elapsed,
+                false => 0.0,
+            };
+            Some((rate * 10.0).round() / 10.0)
+        }
+    }
+
+    impl Default for Progress {
+        fn default() -> Self {
+            Progress::new()
+        }
+    }
+
+    /// Call the `level_fn` closure of [wraperr](crate::wraperr)
+    pub fn pick_level<E>(err: &E, level_fn: impl FnOnce(&E) -> tracing::Level) -> tracing::Level {
+        level_fn(err)
+    }
+
+    /// Call the `where` closure of a [matcherr](crate::matcherr) arm
+    pub fn matches_guard<T: ?Sized>(cause: &T, guard: impl FnOnce(&T) -> bool) -> bool {
+        guard(cause)
+    }
+
+    /// The event message of [matcherr](crate::matcherr)
+    pub fn matched_message(callsite: &Location, msg: Option<String>, err: &Error) -> String {
+        match msg {
+            Some(msg) => format!("{:?} => {}: {}", callsite, msg, chain_string(err)),
+            None => format!("{:?} => {}", callsite, chain_string(err)),
+        }
+    }
+
+    /// The `span` field of the events of [wraperr](crate::wraperr)
+    pub fn span_field() -> Option<String> {
+        ErrorSpan::current().map(|span| span.field())
+    }
+
+    /// Wrap the error with a context message, for both anyhow and eyre
+    pub fn context<C>(err: Error, context: C) -> Error
+    where
+        C: std::fmt::Display + Send + Sync + 'static,
+    {
+        #[cfg(feature = "anyhow")]
+        return err.context(context);
+        #[cfg(feature = "eyre")]
+        return err.wrap_err(context);
+    }
+
+    /// The error returned by `bail_with_level!`
+    pub fn bail(severity: Severity, location: Location, msg: String) -> Error {
+        context(context(Error::msg(msg), severity), location)
+    }
+
+    /// Render all non-empty messages in the chain on one line
+    pub fn chain_string(err: &Error) -> String {
+        chain_messages(err).join(": ")
+    }
+
+    /// Check the chain for `assert_err_chain!`, returning the failure message on mismatch
+    pub fn check_err_chain(err: &Error, expected: &[&str]) -> std::result::Result<(), String> {
+        use std::fmt::Write;
+
+        let chain = chain_messages(err);
+        let mut start = 0;
+        let mut found = vec![];
+        for pattern in expected {
+            match chain.iter().skip(start).position(|msg| msg.contains(pattern)) {
+                Some(index) => {
+                    start += index;
+                    found.push(Some(start));
+                }
+                None => found.push(None),
             }
-        });
+        }
+        if found.iter().all(|index| index.is_some()) {
+            return Ok(());
+        }
+
+        let mut msg = String::from("assertion failed: error chain does not match\n  expected, in order:\n");
+        for (pattern, index) in expected.iter().zip(&found) {
+            let _ = match index {
+                Some(index) => writeln!(msg, "  + {:?} (found in #{})", pattern, index),
+                None => writeln!(msg, "  - {:?} (missing)", pattern),
+            };
+        }
+        msg.push_str("  actual chain:\n");
+        for (index, cause) in chain.iter().enumerate() {
+            let _ = writeln!(msg, "    #{} {}", index, cause);
+        }
+        Err(msg)
+    }
+
+    /// Block on the future with a current-thread runtime, for async `#[errlog::test]`s
+    #[cfg(all(feature = "test-util", feature = "tokio"))]
+    pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to build the tokio runtime")
+            .block_on(future)
+    }
+
+    /// Whether the value returned by a test is a failure
+    pub trait TestOutcome {
+        fn is_failure(&self) -> bool;
+    }
+
+    impl TestOutcome for () {
+        fn is_failure(&self) -> bool {
+            false
+        }
+    }
+
+    impl<T, E> TestOutcome for std::result::Result<T, E> {
+        fn is_failure(&self) -> bool {
+            self.is_err()
+        }
+    }
+
+    /// Print the captured events of a `#[errlog::test]` when it panics or returns an error
+    #[cfg(feature = "test-util")]
+    pub struct TestGuard(crate::test::Capture);
+
+    #[cfg(feature = "test-util")]
+    impl TestGuard {
+        pub fn new(capture: crate::test::Capture) -> Self {
+            TestGuard(capture)
+        }
+
+        pub fn finish<T: TestOutcome>(self, outcome: T) -> T {
+            if outcome.is_failure() {
+                self.dump();
+            }
+            outcome
+        }
+
+        fn dump(&self) {
+            let events = self.0.events();
+            eprintln!("---- captured log ({} event(s)) ----", events.len());
+            for event in events.iter() {
+                eprintln!("{}", event);
+            }
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    impl Drop for TestGuard {
+        fn drop(&mut self) {
+            if std::thread::panicking() {
+                self.dump();
+            }
+        }
+    }
+
+    /// Record the error fields on the span and return the rendered chain, the location is the one
+    /// of the outermost wrap or else `callsite`
+    pub fn record_err(span: &tracing::Span, err: &Error, callsite: &Location) -> String {
+        let chain = chain_string(err);
+        span.record("error", chain.as_str());
+        if let Some(code) = error_code(err) {
+            span.record("error.code", code);
+        }
+        let location = crate::location(err).unwrap_or(*callsite);
+        span.record("error.location", tracing::field::debug(location));
+        chain
     }
-    return errmsg;
 }
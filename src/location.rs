@@ -0,0 +1,86 @@
+//! Source locations attached by [wraperr](crate::wraperr)
+
+use std::cell::Cell;
+use std::fmt;
+
+use crate::Error;
+
+/// The source location where an error was wrapped
+///
+/// [wraperr](crate::wraperr) attaches it as the context of every wrap, the data lives in a static
+/// of the call site, so attaching it allocates nothing. Read the outermost one back with
+/// [location]
+///
+///     use errlog::{wraperr, Result};
+///
+///     fn load() -> Result<String> {
+///         wraperr!(std::fs::read_to_string("/path/to/config"))
+///     }
+///
+///     let location = errlog::location(&load().unwrap_err()).unwrap();
+///     # #[cfg(not(any(feature = "no-location", feature = "hash-location")))]
+///     assert_eq!(location.file, file!());
+///
+/// Like [Code](crate::Code), the location renders as an empty string, so it never shows up in
+/// the message chain.
+///
+/// To keep source paths out of release binaries, the `no-location` feature leaves `file` empty
+/// and the `hash-location` feature replaces it by `#` and a 32-bit FNV-1a hash of the path (map
+/// it back by hashing the files of the source tree), both keeping the line. The paths embedded
+/// by tracing's own event metadata and by `#[track_caller]` are not affected, rustc's
+/// `--remap-path-prefix` covers those.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub file: &'static str,
+    pub line: u32,
+}
+
+impl Location {
+    pub const fn new(file: &'static str, line: u32) -> Self {
+        Location { file, line }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match is_shown() {
+            true => write!(f, "{}{:?}", MARKER, self),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Starts the rendering of locations inside [shown]
+pub(crate) const MARKER: char = '\u{1}';
+
+thread_local! {
+    static SHOWN: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the thread runs [shown], so that markers are rendered instead of nothing
+pub(crate) fn is_shown() -> bool {
+    SHOWN.with(Cell::get)
+}
+
+/// Run `f` with locations rendering as [MARKER] followed by `file:line`, to find them in chains
+pub(crate) fn shown<R>(f: impl FnOnce() -> R) -> R {
+    let previous = SHOWN.with(|shown| shown.replace(true));
+    let result = f();
+    SHOWN.with(|shown| shown.set(previous));
+    result
+}
+
+impl fmt::Debug for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.file.is_empty() {
+            write!(f, "line {}", self.line)
+        } else {
+            write!(f, "{}:{}", self.file, self.line)
+        }
+    }
+}
+
+/// The location of the outermost [wraperr](crate::wraperr) of the error
+pub fn location(err: &Error) -> Option<Location> {
+    err.downcast_ref::<Location>().copied()
+}
@@ -0,0 +1,101 @@
+//! Results which complain when their error is dropped unseen
+
+use std::panic::Location;
+
+use crate::{Error, Result};
+
+/// A result whose error logs a `WARN` if dropped without being handled
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::LoggedResult;
+///
+///     fn sync(peer: &str) -> LoggedResult<()> {
+///         LoggedResult::new(Err(errlog::Error::msg(format!("{} unreachable", peer))))
+///     }
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///
+///     assert!(sync("replica-1").into_result().is_err());
+///     sync("replica-2").ignore_err();
+///     assert!(output.contents().is_empty());
+///
+///     let _ = sync("replica-3");
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("error dropped without being handled: replica-3 unreachable"));
+///
+/// The error is handled by [into_result](LoggedResult::into_result) to propagate it,
+/// [log_err](LoggedResult::log_err) or [ignore_err](LoggedResult::ignore_err). The warning
+/// carries the location where the result was created.
+#[must_use = "the error is logged as dropped unless handled"]
+#[derive(Debug)]
+pub struct LoggedResult<T> {
+    inner: Option<Result<T>>,
+    location: &'static Location<'static>,
+}
+
+impl<T> LoggedResult<T> {
+    #[track_caller]
+    pub fn new(result: Result<T>) -> Self {
+        LoggedResult { inner: Some(result), location: Location::caller() }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        matches!(self.inner, Some(Ok(_)))
+    }
+
+    pub fn is_err(&self) -> bool {
+        matches!(self.inner, Some(Err(_)))
+    }
+
+    /// The result, to propagate the error with `?`
+    pub fn into_result(mut self) -> Result<T> {
+        self.take()
+    }
+
+    /// The value, or `None` after logging the error at `ERROR`
+    pub fn log_err(mut self) -> Option<T> {
+        let location = self.location;
+        match self.take() {
+            Ok(value) => Some(value),
+            Err(err) => {
+                if !crate::__private::SILENT {
+                    tracing::error!("{}:{} => {}", location.file(), location.line(), crate::__private::chain_string(&err));
+                }
+                None
+            }
+        }
+    }
+
+    /// The value, or `None` when the error is deliberately discarded
+    pub fn ignore_err(mut self) -> Option<T> {
+        self.take().ok()
+    }
+
+    fn take(&mut self) -> Result<T> {
+        self.inner.take().expect("the result is only taken once")
+    }
+}
+
+impl<T, E: Into<Error>> From<std::result::Result<T, E>> for LoggedResult<T> {
+    #[track_caller]
+    fn from(result: std::result::Result<T, E>) -> Self {
+        LoggedResult::new(result.map_err(Into::into))
+    }
+}
+
+impl<T> Drop for LoggedResult<T> {
+    fn drop(&mut self) {
+        let Some(Err(err)) = &self.inner else {
+            return;
+        };
+        if !crate::__private::SILENT {
+            tracing::warn!(
+                "{}:{} => error dropped without being handled: {}",
+                self.location.file(),
+                self.location.line(),
+                crate::__private::chain_string(err),
+            );
+        }
+    }
+}
@@ -0,0 +1,1762 @@
+//! A ready-made subscriber formatting events to a writer
+//!
+//!     use errlog::logger::{self, Deterministic, Format, MemoryWriter};
+//!     use errlog::wraperr;
+//!
+//!     let output = MemoryWriter::new();
+//!     let _guard = logger::builder()
+//!         .format(Format::Full)
+//!         .writer(output.clone())
+//!         .deterministic(Deterministic::MaskLines)
+//!         .set_default();
+//!
+//!     let _ = wraperr!(std::fs::read_to_string("/path/to/config"), "failed to load config");
+//!     # #[cfg(not(feature = "silent"))]
+//!     assert!(output.contents().starts_with("1970-01-01T00:00:00.000000Z ERROR "));
+//!     # #[cfg(not(any(feature = "no-location", feature = "hash-location", feature = "silent")))]
+//!     assert!(output.contents().contains("src/logger.rs:[line] => failed to load config"));
+//!
+//! `init` installs the subscriber globally, while `set_default` installs it for the current
+//! thread only, which suits tests.
+
+use std::io::{self, IsTerminal, Write};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tracing::level_filters::LevelFilter;
+use tracing::subscriber::DefaultGuard;
+use tracing::{Dispatch, Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context as LayerContext, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::clock::{Clock, SystemClock};
+use crate::fields::FieldVisitor;
+use crate::Result;
+
+mod batch;
+#[cfg(feature = "chrome")]
+mod chrome;
+mod console;
+#[cfg(feature = "email")]
+mod email;
+mod enrich;
+#[cfg(feature = "flame")]
+mod flame;
+#[cfg(feature = "gelf")]
+mod gelf;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "loki")]
+mod loki;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod net;
+mod ring;
+mod rules;
+mod store;
+mod timings;
+#[cfg(feature = "webhook")]
+mod webhook;
+
+pub use batch::BatchWriter;
+#[cfg(feature = "email")]
+pub use email::Email;
+pub use enrich::{Enricher, FieldMap};
+#[cfg(feature = "gelf")]
+pub use gelf::GelfWriter;
+#[cfg(feature = "kafka")]
+pub use kafka::{Kafka, KafkaProducer, KafkaRecord, RdKafkaProducer};
+#[cfg(feature = "loki")]
+pub use loki::Loki;
+#[cfg(feature = "mqtt")]
+pub use mqtt::{Mqtt, MqttQos};
+pub use net::NetWriter;
+pub use ring::RingWriter;
+pub use store::{ErrorStore, MemoryStore, StoredError, SQLITE_SCHEMA};
+#[cfg(feature = "sqlite")]
+pub use store::SqliteStore;
+#[cfg(feature = "webhook")]
+pub use webhook::Webhook;
+
+use rules::{Dedup, Escalation};
+
+/// The layout of a formatted event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// `2024-01-01T12:00:00.000000Z  INFO span: target: message key=value`
+    #[default]
+    Full,
+    /// One JSON object per line with `timestamp`, `level`, `target`, `spans`, `message` and the
+    /// event fields
+    Json,
+    /// `ts=2024-01-01T12:00:00.000000Z level=info target=app msg="message" file=src/main.rs line=7 key=value`
+    Logfmt,
+    /// Elastic Common Schema JSON with `@timestamp`, `log.level`, `message`, `error.message`,
+    /// `error.stack_trace`, `error.code` and `service.name`, other fields go to `labels`
+    ///
+    /// The service name is `ERRLOG_SERVICE_NAME` or else the name of the executable.
+    Ecs,
+    /// GELF 1.1 JSON for Graylog, with the error chains as `full_message`, see [GelfWriter]
+    #[cfg(feature = "gelf")]
+    Gelf,
+    /// Bunyan JSON with `name`, `hostname`, `pid`, the numeric `level`, `msg`, `time` and `v`,
+    /// which the `bunyan` CLI pretty prints
+    ///
+    ///     use errlog::logger::{self, Deterministic, Format, MemoryWriter};
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder()
+    ///         .format(Format::Bunyan)
+    ///         .writer(output.clone())
+    ///         .deterministic(Deterministic::On)
+    ///         .set_default();
+    ///     errlog::logmsg!(WARN, attempt = 3, "cache miss");
+    ///
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     # {
+    ///     let contents = output.contents();
+    ///     assert!(contents.starts_with(r#"{"name":""#));
+    ///     assert!(contents.contains(&format!(r#","pid":{},"level":40,"msg":""#, std::process::id())));
+    ///     assert!(contents.contains(r#","time":"1970-01-01T00:00:00.000Z","v":0,"target":"#));
+    ///     assert!(contents.ends_with(",\"attempt\":\"3\"}\n"));
+    ///     # }
+    ///
+    /// The name is the one of [Format::Ecs]. The chain of the first error field goes to `err` with
+    /// its `message` and a `stack` of `caused by:` lines, and the location to `src` with
+    /// [locations](Builder::locations).
+    Bunyan,
+    /// `12:00:00.000 ERROR app              message key=value` for terminals, with colored levels,
+    /// dimmed times, targets and locations, long lines wrapped under the message column and the
+    /// chains of error fields on `caused by:` lines
+    ///
+    ///     use errlog::logger::{self, Format, MemoryWriter};
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder().format(Format::Pretty).color(true).writer(output.clone()).set_default();
+    ///     errlog::logmsg!(WARN, attempt = 3, "the connection to the primary database was reset while the nightly report was being generated");
+    ///
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     # {
+    ///     let contents = output.contents();
+    ///     let lines: Vec<&str> = contents.lines().collect();
+    ///     assert!(lines[0].contains("\x1b[1;33m WARN\x1b[0m"));
+    ///     assert!(lines[1].starts_with(&" ".repeat(36)));
+    ///     assert!(lines[1].ends_with("attempt=3"));
+    ///     # }
+    ///
+    /// Colors are only used when the writer is a terminal and `NO_COLOR` is not set, unless
+    /// [Builder::color] says otherwise.
+    Pretty,
+    /// `15:04:05 E db src/db.rs:88 insert failed: connection reset key=value` with one-letter
+    /// levels, for dense terminals and CI logs
+    ///
+    ///     use errlog::logger::{self, Deterministic, Format, MemoryWriter};
+    ///     use errlog::wraperr;
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder()
+    ///         .format(Format::Compact)
+    ///         .targets(false)
+    ///         .writer(output.clone())
+    ///         .deterministic(Deterministic::MaskLines)
+    ///         .set_default();
+    ///     let _ = wraperr!(std::fs::read_to_string("/path/to/config"), "failed to load config");
+    ///
+    ///     # #[cfg(not(any(feature = "no-location", feature = "hash-location", feature = "silent", feature = "err-id")))]
+    ///     assert_eq!(output.contents(), "00:00:00 E src/logger.rs:[line] failed to load config\n");
+    ///
+    /// The location is the one of errlog's message prefix when there is one, else the one of the
+    /// event, and the target is left out with [targets](Builder::targets).
+    Compact,
+}
+
+/// Rewrites making formatted output stable across runs and refactors, for snapshot tests
+///
+/// When enabled, timestamps are fixed to the Unix epoch, the current directory, the manifest
+/// directory and the home directory are stripped from paths, and with `MaskLines` the line
+/// numbers following `.rs:` are replaced by `[line]`. Pretty reports omit the environment and
+/// backtrace sections as well.
+///
+/// The mode is also read from `ERRLOG_DETERMINISTIC`: `1` enables it and `lines` masks lines too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Deterministic {
+    #[default]
+    Off,
+    On,
+    MaskLines,
+}
+
+/// The mode set by the last installed logger
+static INSTALLED: AtomicU8 = AtomicU8::new(0);
+
+impl Deterministic {
+    /// The mode requested by `ERRLOG_DETERMINISTIC`
+    pub fn from_env() -> Self {
+        match std::env::var("ERRLOG_DETERMINISTIC").as_deref() {
+            Ok("1") | Ok("true") | Ok("on") => Deterministic::On,
+            Ok("lines") => Deterministic::MaskLines,
+            _ => Deterministic::Off,
+        }
+    }
+
+    /// The strictest of the environment and the installed logger modes
+    pub fn current() -> Self {
+        let installed = match INSTALLED.load(Ordering::Relaxed) {
+            1 => Deterministic::On,
+            2 => Deterministic::MaskLines,
+            _ => Deterministic::Off,
+        };
+        installed.max(Self::from_env())
+    }
+
+    pub fn is_on(self) -> bool {
+        self != Deterministic::Off
+    }
+
+    /// Apply the path and line rewrites to `text`
+    ///
+    ///     use errlog::logger::Deterministic;
+    ///
+    ///     let cwd = std::env::current_dir().unwrap();
+    ///     let text = format!("{}/src/main.rs:42 => failed", cwd.display());
+    ///     assert_eq!(Deterministic::MaskLines.normalize(&text), "src/main.rs:[line] => failed");
+    ///     assert_eq!(Deterministic::Off.normalize(&text), text);
+    pub fn normalize(self, text: &str) -> String {
+        if !self.is_on() {
+            return text.to_string();
+        }
+        let mut text = text.to_string();
+        let mut prefixes = vec![];
+        for dir in [std::env::current_dir().ok(), std::env::var_os("CARGO_MANIFEST_DIR").map(Into::into)]
+            .into_iter()
+            .flatten()
+        {
+            prefixes.push((format!("{}/", dir.display()), String::new()));
+        }
+        if let Some(home) = std::env::var_os("HOME").filter(|home| !home.is_empty()) {
+            prefixes.push((format!("{}/", home.to_string_lossy()), "~/".to_string()));
+        }
+        prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        for (prefix, replacement) in prefixes {
+            if prefix.len() > 1 {
+                text = text.replace(&prefix, &replacement);
+            }
+        }
+        if self == Deterministic::MaskLines {
+            text = mask_lines(&text);
+        }
+        text
+    }
+}
+
+/// Replace the digits following `.rs:` by `[line]`
+fn mask_lines(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(".rs:") {
+        let (head, tail) = rest.split_at(index + 4);
+        out.push_str(head);
+        let digits = tail.find(|c: char| !c.is_ascii_digit()).unwrap_or(tail.len());
+        if digits > 0 {
+            out.push_str("[line]");
+        }
+        rest = &tail[digits..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The limit of loggers without their own, 0 for none
+static MAX_MESSAGE_LEN: AtomicUsize = AtomicUsize::new(0);
+
+/// The field exempting an event from truncation, as in `logmsg!(INFO, untruncated = true, ...)`
+pub const UNTRUNCATED_FIELD: &str = "untruncated";
+
+/// Limit the length of messages and field values for every logger without its own
+/// [max_message_len](Builder::max_message_len), `None` removes the limit
+pub fn set_max_message_len(max: Option<usize>) {
+    MAX_MESSAGE_LEN.store(max.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// Cut `text` to at most `max` bytes on a char boundary, then tell how much was cut
+///
+///     use errlog::logger::truncate;
+///
+///     assert_eq!(truncate("short", 10), "short");
+///     assert_eq!(truncate("héllo world", 3), "hé... (truncated 9 bytes)");
+pub fn truncate(text: &str, max: usize) -> Cow<'_, str> {
+    if text.len() <= max {
+        return Cow::Borrowed(text);
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    Cow::Owned(format!("{}... (truncated {} bytes)", &text[..end], text.len() - end))
+}
+
+/// Format a timestamp as RFC 3339 in UTC with microseconds
+pub(crate) fn rfc3339(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs();
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60,
+        elapsed.subsec_micros()
+    )
+}
+
+/// Append `text` as a JSON string
+fn json_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Append a logfmt value, quoted when it is empty or holds spaces, quotes, `=` or control
+/// characters
+fn logfmt_value(out: &mut String, text: &str) {
+    if text.is_empty() || text.chars().any(|c| c == ' ' || c == '"' || c == '=' || c.is_control()) {
+        json_string(out, text);
+    } else {
+        out.push_str(text);
+    }
+}
+
+/// Append the line of [Format::Full] up to the fields, leaving out the error fields unless
+/// `errors`, which the lines below the record show otherwise
+fn full_line(out: &mut String, record: &Record, timestamp: &str, errors: bool) {
+    out.push_str(&format!("{} {:>5} ", timestamp, record.level));
+    for span in &record.spans {
+        out.push_str(span);
+        out.push(':');
+    }
+    if !record.spans.is_empty() {
+        out.push(' ');
+    }
+    out.push_str(&format!("{}: {}", record.target, record.message));
+    for (name, value) in &record.fields {
+        if errors || !record.errors.iter().any(|(error, _)| error == name) {
+            out.push_str(&format!(" {}={}", name, value));
+        }
+    }
+}
+
+/// The name of this host, or `localhost`
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+/// The number of the current thread, `7` for `ThreadId(7)`
+fn thread_id() -> String {
+    let id = format!("{:?}", std::thread::current().id());
+    id.trim_start_matches("ThreadId(").trim_end_matches(')').to_string()
+}
+
+/// The `host` field and the `k8s.*` fields from the downward API variables which are set
+fn host_fields(hostname: &str) -> Vec<(String, String)> {
+    let mut fields = vec![("host".to_string(), hostname.to_string())];
+    for (field, var) in [
+        ("k8s.namespace", "POD_NAMESPACE"),
+        ("k8s.pod", "POD_NAME"),
+        ("k8s.container", "CONTAINER_NAME"),
+        ("k8s.node", "NODE_NAME"),
+    ] {
+        if let Some(value) = std::env::var(var).ok().filter(|value| !value.is_empty()) {
+            fields.push((field.to_string(), value));
+        }
+    }
+    fields
+}
+
+/// The `service.name` of [Format::Ecs]
+fn service_name() -> String {
+    std::env::var("ERRLOG_SERVICE_NAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+        .or_else(|| {
+            let exe = std::env::current_exe().ok()?;
+            Some(exe.file_stem()?.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default()
+}
+
+/// The names the layouts carry, resolved once per logger
+#[derive(Debug, Clone)]
+struct Names {
+    service: String,
+    hostname: String,
+}
+
+impl Names {
+    fn resolve() -> Self {
+        Names { service: service_name(), hostname: hostname() }
+    }
+}
+
+/// An event ready to be formatted
+#[derive(Clone)]
+struct Record {
+    timestamp: SystemTime,
+    level: Level,
+    target: String,
+    spans: Vec<String>,
+    message: String,
+    fields: Vec<(String, String)>,
+    errors: Vec<(String, Vec<String>)>,
+    /// The locations where the messages of `errors` were wrapped, if known
+    error_locations: Vec<Vec<Option<String>>>,
+    location: Option<String>,
+}
+
+impl Record {
+    /// The record with its message prefixed by `location =>` like the ones of errlog's macros,
+    /// unless it already is
+    fn located(&self, location: &str) -> Record {
+        let message = match self.message.starts_with(&format!("{} =>", location)) {
+            true => self.message.clone(),
+            false => format!("{} => {}", location, self.message),
+        };
+        Record { message, ..self.clone() }
+    }
+}
+
+/// The options of the layouts
+#[derive(Debug, Clone)]
+struct Layout {
+    multiline_errors: bool,
+    error_blocks: bool,
+    targets: bool,
+    locations: bool,
+    /// Paint the `Pretty` format with ANSI escapes
+    color: bool,
+    /// Escape the line breaks inside records, for writers framing records by lines
+    single_line: bool,
+    /// Resolved on every render when missing
+    names: Option<Arc<Names>>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout { multiline_errors: false, error_blocks: false, targets: true, locations: false, color: false, single_line: false, names: None }
+    }
+}
+
+impl Layout {
+    fn names(&self) -> Cow<'_, Names> {
+        match &self.names {
+            Some(names) => Cow::Borrowed(names),
+            None => Cow::Owned(Names::resolve()),
+        }
+    }
+}
+
+impl Format {
+    fn render(self, record: &Record, layout: &Layout) -> String {
+        let located;
+        let record = match &record.location {
+            Some(location) if layout.locations && matches!(self, Format::Full | Format::Pretty) => {
+                located = record.located(location);
+                &located
+            }
+            _ => record,
+        };
+        let mut out = String::new();
+        let timestamp = rfc3339(record.timestamp);
+        match self {
+            Format::Full if layout.error_blocks && !record.errors.is_empty() => {
+                full_line(&mut out, record, &timestamp, false);
+                console::error_block(&mut out, record, 4, false);
+                if let Some(location) = &record.location {
+                    out.push_str(&format!("\n    at {}", location));
+                }
+            }
+            Format::Full if layout.multiline_errors && !record.errors.is_empty() => {
+                full_line(&mut out, record, &timestamp, false);
+                for (_, chain) in &record.errors {
+                    for cause in chain {
+                        out.push_str(&format!("\n    caused by: {}", cause));
+                    }
+                }
+                if let Some(location) = &record.location {
+                    out.push_str(&format!("\n    at {}", location));
+                }
+            }
+            Format::Full => full_line(&mut out, record, &timestamp, true),
+            Format::Json => {
+                out.push_str("{\"timestamp\":");
+                json_string(&mut out, &timestamp);
+                out.push_str(",\"level\":");
+                json_string(&mut out, record.level.as_str());
+                out.push_str(",\"target\":");
+                json_string(&mut out, &record.target);
+                out.push_str(",\"spans\":[");
+                for (index, span) in record.spans.iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+                    json_string(&mut out, span);
+                }
+                out.push_str("],\"message\":");
+                json_string(&mut out, &record.message);
+                if let Some((file, line)) = record.location.as_ref().filter(|_| layout.locations).and_then(|location| location.rsplit_once(':')) {
+                    out.push_str(",\"file\":");
+                    json_string(&mut out, file);
+                    match line.parse::<u32>() {
+                        Ok(line) => out.push_str(&format!(",\"line\":{}", line)),
+                        Err(_) => {
+                            out.push_str(",\"line\":");
+                            json_string(&mut out, line);
+                        }
+                    }
+                }
+                for (name, value) in &record.fields {
+                    out.push(',');
+                    json_string(&mut out, name);
+                    out.push(':');
+                    json_string(&mut out, value);
+                }
+                out.push('}');
+            }
+            Format::Logfmt => {
+                out.push_str("ts=");
+                logfmt_value(&mut out, &timestamp);
+                out.push_str(" level=");
+                out.push_str(&record.level.as_str().to_lowercase());
+                out.push_str(" target=");
+                logfmt_value(&mut out, &record.target);
+                out.push_str(" msg=");
+                logfmt_value(&mut out, &record.message);
+                if let Some((file, line)) = record.location.as_ref().and_then(|location| location.rsplit_once(':')) {
+                    out.push_str(" file=");
+                    logfmt_value(&mut out, file);
+                    out.push_str(" line=");
+                    logfmt_value(&mut out, line);
+                }
+                for (name, value) in &record.fields {
+                    out.push(' ');
+                    out.push_str(name);
+                    out.push('=');
+                    logfmt_value(&mut out, value);
+                }
+            }
+            Format::Ecs => {
+                out.push_str("{\"@timestamp\":");
+                json_string(&mut out, &timestamp);
+                out.push_str(",\"log.level\":");
+                json_string(&mut out, &record.level.as_str().to_lowercase());
+                out.push_str(",\"log.logger\":");
+                json_string(&mut out, &record.target);
+                if let Some((file, line)) = record.location.as_ref().and_then(|location| location.rsplit_once(':')) {
+                    out.push_str(",\"log.origin.file.name\":");
+                    json_string(&mut out, file);
+                    if line.parse::<u32>().is_ok() {
+                        out.push_str(&format!(",\"log.origin.file.line\":{}", line));
+                    }
+                }
+                out.push_str(",\"message\":");
+                json_string(&mut out, &record.message);
+                if let Some((_, chain)) = record.errors.first() {
+                    out.push_str(",\"error.message\":");
+                    json_string(&mut out, chain.first().map_or("", String::as_str));
+                    out.push_str(",\"error.stack_trace\":");
+                    json_string(&mut out, &chain.join("\ncaused by: "));
+                }
+                if let Some((_, code)) = record.fields.iter().find(|(name, _)| name == "code") {
+                    out.push_str(",\"error.code\":");
+                    json_string(&mut out, code);
+                }
+                if !record.spans.is_empty() {
+                    out.push_str(",\"span.name\":");
+                    json_string(&mut out, &record.spans.join(":"));
+                }
+                out.push_str(",\"service.name\":");
+                json_string(&mut out, &layout.names().service);
+                out.push_str(",\"ecs.version\":\"8.11.0\"");
+                let labels: Vec<&(String, String)> = record
+                    .fields
+                    .iter()
+                    .filter(|(name, _)| name != "code" && !record.errors.iter().any(|(error, _)| error == name))
+                    .collect();
+                if !labels.is_empty() {
+                    out.push_str(",\"labels\":{");
+                    for (index, (name, value)) in labels.into_iter().enumerate() {
+                        if index > 0 {
+                            out.push(',');
+                        }
+                        json_string(&mut out, name);
+                        out.push(':');
+                        json_string(&mut out, value);
+                    }
+                    out.push('}');
+                }
+                out.push('}');
+            }
+            #[cfg(feature = "gelf")]
+            Format::Gelf => gelf::render(&mut out, record, &layout.names().hostname),
+            Format::Bunyan => {
+                let names = layout.names();
+                out.push_str("{\"name\":");
+                json_string(&mut out, &names.service);
+                out.push_str(",\"hostname\":");
+                json_string(&mut out, &names.hostname);
+                let level = match record.level {
+                    Level::ERROR => 50,
+                    Level::WARN => 40,
+                    Level::INFO => 30,
+                    Level::DEBUG => 20,
+                    _ => 10,
+                };
+                out.push_str(&format!(",\"pid\":{},\"level\":{},\"msg\":", std::process::id(), level));
+                json_string(&mut out, &record.message);
+                // milliseconds, which JavaScript dates keep
+                out.push_str(",\"time\":");
+                json_string(&mut out, &format!("{}Z", &timestamp[..23]));
+                out.push_str(",\"v\":0,\"target\":");
+                json_string(&mut out, &record.target);
+                if !record.spans.is_empty() {
+                    out.push_str(",\"spans\":");
+                    json_string(&mut out, &record.spans.join(":"));
+                }
+                if let Some((file, line)) = record.location.as_ref().filter(|_| layout.locations).and_then(|location| location.rsplit_once(':')) {
+                    out.push_str(",\"src\":{\"file\":");
+                    json_string(&mut out, file);
+                    match line.parse::<u32>() {
+                        Ok(line) => out.push_str(&format!(",\"line\":{}}}", line)),
+                        Err(_) => out.push('}'),
+                    }
+                }
+                if let Some((_, chain)) = record.errors.first() {
+                    out.push_str(",\"err\":{\"message\":");
+                    json_string(&mut out, chain.first().map_or("", String::as_str));
+                    out.push_str(",\"stack\":");
+                    json_string(&mut out, &chain.join("\ncaused by: "));
+                    out.push('}');
+                }
+                for (name, value) in &record.fields {
+                    if record.errors.first().is_some_and(|(error, _)| error == name) {
+                        continue;
+                    }
+                    out.push(',');
+                    json_string(&mut out, name);
+                    out.push(':');
+                    json_string(&mut out, value);
+                }
+                out.push('}');
+            }
+            Format::Pretty => console::render_pretty(&mut out, record, layout.error_blocks, layout.color),
+            Format::Compact => console::render_compact(&mut out, record, layout.targets),
+        }
+        if layout.single_line && out.contains('\n') {
+            out = out.replace('\n', "\\n");
+        }
+        out.push('\n');
+        out
+    }
+}
+
+/// Where formatted records go
+#[derive(Clone)]
+enum Sink {
+    Locked(Arc<Mutex<Box<dyn Write + Send>>>),
+    Batched(BatchWriter),
+}
+
+impl Sink {
+    fn write(&self, record: &[u8]) {
+        match self {
+            Sink::Locked(writer) => {
+                let _ = writer.lock().unwrap_or_else(|err| err.into_inner()).write_all(record);
+            }
+            Sink::Batched(writer) => writer.send(record),
+        }
+    }
+
+    fn flush(&self) {
+        match self {
+            Sink::Locked(writer) => {
+                let _ = writer.lock().unwrap_or_else(|err| err.into_inner()).flush();
+            }
+            Sink::Batched(writer) => writer.sync(),
+        }
+    }
+}
+
+/// A writer keeping everything in memory, clones share the same buffer
+#[derive(Debug, Clone, Default)]
+pub struct MemoryWriter(Arc<Mutex<Vec<u8>>>);
+
+impl MemoryWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything written so far
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap_or_else(|err| err.into_inner())).into_owned()
+    }
+}
+
+impl Write for MemoryWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the summaries of events left out by [Builder::dedup]
+#[derive(Clone)]
+struct Repeats {
+    dedup: Arc<Dedup>,
+    format: Format,
+    layout: Layout,
+    clock: Arc<dyn Clock>,
+    deterministic: Deterministic,
+    sink: Sink,
+}
+
+impl Repeats {
+    /// Write the summaries of the windows ended at `now`, or of all of them
+    fn write(&self, now: Option<SystemTime>) {
+        for repeated in self.dedup.take_repeated(now) {
+            let timestamp = if self.deterministic.is_on() { UNIX_EPOCH } else { now.unwrap_or_else(|| self.clock.now()) };
+            let record = Record {
+                timestamp,
+                level: repeated.level,
+                target: repeated.target,
+                spans: vec![],
+                message: format!("previous message repeated {} times", repeated.count),
+                fields: vec![],
+                errors: vec![],
+                error_locations: vec![],
+                location: repeated.location,
+            };
+            self.sink.write(self.format.render(&record, &Layout { multiline_errors: false, ..self.layout.clone() }).as_bytes());
+        }
+    }
+}
+
+struct FormatLayer {
+    format: Format,
+    layout: Layout,
+    clock: Arc<dyn Clock>,
+    deterministic: Deterministic,
+    max_message_len: Option<usize>,
+    pid: bool,
+    thread_ids: bool,
+    thread_names: bool,
+    host: Vec<(String, String)>,
+    enrichers: Vec<Box<dyn Enricher>>,
+    escalation: Option<Escalation>,
+    repeats: Option<Repeats>,
+    sink: Sink,
+    #[cfg(feature = "loki")]
+    loki: Option<loki::LokiClient>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<kafka::KafkaClient>,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<mqtt::MqttClient>,
+    store: Option<store::Storing>,
+    #[cfg(feature = "webhook")]
+    webhook: Option<Arc<webhook::WebhookClient>>,
+    #[cfg(feature = "email")]
+    email: Option<email::EmailClient>,
+}
+
+impl<S> Layer<S> for FormatLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: LayerContext<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let untruncated = visitor.fields.iter().any(|(name, value)| name == UNTRUNCATED_FIELD && value == "true");
+        visitor.fields.retain(|(name, _)| name != UNTRUNCATED_FIELD);
+        let max = self
+            .max_message_len
+            .or(Some(MAX_MESSAGE_LEN.load(Ordering::Relaxed)).filter(|max| *max > 0))
+            .filter(|_| !untruncated);
+        let limit = |text: String| match max {
+            Some(max) => truncate(&text, max).into_owned(),
+            None => text,
+        };
+        let deterministic = self.deterministic;
+        let now = self.clock.now();
+        let timestamp = if deterministic.is_on() { UNIX_EPOCH } else { now };
+        if let Some(repeats) = &self.repeats {
+            repeats.write(Some(now));
+        }
+        let location = match (event.metadata().file(), event.metadata().line()) {
+            (Some(file), Some(line)) => Some(deterministic.normalize(&format!("{}:{}", file, line))),
+            _ => None,
+        };
+        let keyed = self.store.is_some();
+        #[cfg(feature = "kafka")]
+        let keyed = keyed || self.kafka.is_some();
+        #[cfg(feature = "webhook")]
+        let keyed = keyed || self.webhook.is_some();
+        let fingerprint = match self.escalation.is_some() || self.repeats.is_some() || keyed {
+            true => rules::fingerprint(event, &visitor.message),
+            false => 0,
+        };
+        let mut level = *event.metadata().level();
+        let mut escalated = false;
+        if let Some(escalation) = &self.escalation {
+            if let Some(count) = escalation.check(level, fingerprint, now) {
+                level = Level::ERROR;
+                escalated = true;
+                visitor.fields.push(("repeat_count".to_string(), count.to_string()));
+            }
+        }
+        if let Some(repeats) = &self.repeats {
+            let target = event.metadata().target();
+            if !escalated && repeats.dedup.check(fingerprint, level, target, location.as_deref(), now) {
+                return;
+            }
+        }
+        for (name, value) in crate::context::entries() {
+            if !visitor.fields.iter().any(|(field, _)| *field == name) {
+                visitor.fields.push((name, value));
+            }
+        }
+        if self.pid {
+            visitor.fields.push(("pid".to_string(), std::process::id().to_string()));
+        }
+        if self.thread_ids || self.thread_names {
+            let thread = std::thread::current();
+            if self.thread_ids {
+                visitor.fields.push(("thread_id".to_string(), thread_id()));
+            }
+            if self.thread_names {
+                visitor.fields.push(("thread_name".to_string(), thread.name().unwrap_or("<unnamed>").to_string()));
+            }
+        }
+        visitor.fields.extend(self.host.iter().cloned());
+        if !self.enrichers.is_empty() {
+            let mut fields = FieldMap::from_vec(std::mem::take(&mut visitor.fields));
+            for enricher in &self.enrichers {
+                enricher.enrich(&mut fields);
+            }
+            visitor.fields = fields.into_vec();
+        }
+        let spans = ctx
+            .event_scope(event)
+            .map(|scope| scope.from_root().map(|span| span.name().to_string()).collect())
+            .unwrap_or_default();
+        let record = Record {
+            timestamp,
+            level,
+            target: event.metadata().target().to_string(),
+            spans,
+            message: limit(deterministic.normalize(&visitor.message)),
+            fields: visitor
+                .fields
+                .into_iter()
+                .map(|(name, value)| (name, limit(deterministic.normalize(&value))))
+                .collect(),
+            errors: visitor
+                .errors
+                .into_iter()
+                .map(|(name, chain)| {
+                    (name, chain.iter().map(|cause| limit(deterministic.normalize(cause))).collect())
+                })
+                .collect(),
+            error_locations: visitor
+                .error_locations
+                .into_iter()
+                .map(|locations| {
+                    locations.into_iter().map(|location| location.map(|location| deterministic.normalize(&location))).collect()
+                })
+                .collect(),
+            location,
+        };
+        let line = self.format.render(&record, &self.layout);
+        self.sink.write(line.as_bytes());
+        #[cfg(feature = "loki")]
+        if let Some(loki) = &self.loki {
+            loki.send(&record, &line);
+        }
+        #[cfg(feature = "kafka")]
+        if let Some(kafka) = &self.kafka {
+            match self.format {
+                Format::Json => kafka.send(fingerprint, &line),
+                _ => kafka.send(fingerprint, &Format::Json.render(&record, &Layout::default())),
+            }
+        }
+        if let Some(store) = self.store.as_ref().filter(|_| record.level == Level::ERROR) {
+            store.insert(&record, fingerprint);
+        }
+        #[cfg(feature = "webhook")]
+        if let Some(webhook) = &self.webhook {
+            webhook.send(&record, fingerprint, &line);
+        }
+        #[cfg(feature = "email")]
+        if let Some(email) = &self.email {
+            email.record(&record, &line);
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(mqtt) = self.mqtt.as_ref().filter(|_| record.level <= Level::WARN) {
+            match self.format {
+                Format::Json => mqtt.send(&line),
+                _ => mqtt.send(&Format::Json.render(&record, &Layout::default())),
+            }
+        }
+    }
+}
+
+/// Configure the subscriber, see [builder]
+pub struct Builder {
+    level: LevelFilter,
+    format: Format,
+    targets: bool,
+    clock: Arc<dyn Clock>,
+    deterministic: Deterministic,
+    max_message_len: Option<usize>,
+    multiline_errors: bool,
+    error_blocks: bool,
+    locations: bool,
+    pid: bool,
+    thread_ids: bool,
+    thread_names: bool,
+    host: bool,
+    enrichers: Vec<Box<dyn Enricher>>,
+    escalation: Option<(usize, Duration)>,
+    dedup: Option<Duration>,
+    dedup_targets: Vec<(String, Duration)>,
+    span_summary: bool,
+    #[cfg(feature = "flame")]
+    flame: Option<std::path::PathBuf>,
+    #[cfg(feature = "chrome")]
+    chrome_trace: Option<std::path::PathBuf>,
+    writer: Box<dyn Write + Send>,
+    /// The drops of a [NetWriter] writer
+    net_dropped: Option<Arc<AtomicU64>>,
+    /// Whether the writer is a terminal
+    terminal: bool,
+    color: Option<bool>,
+    batch: Option<usize>,
+    #[cfg(feature = "loki")]
+    loki: Option<Loki>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<Kafka>,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<Mqtt>,
+    store: Option<(Box<dyn ErrorStore>, Duration)>,
+    version: String,
+    #[cfg(feature = "webhook")]
+    webhook: Option<Webhook>,
+    #[cfg(feature = "email")]
+    email: Option<Email>,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder {
+            level: LevelFilter::INFO,
+            format: Format::default(),
+            targets: true,
+            clock: Arc::new(SystemClock),
+            deterministic: Deterministic::Off,
+            max_message_len: None,
+            multiline_errors: false,
+            error_blocks: false,
+            locations: false,
+            pid: false,
+            thread_ids: false,
+            thread_names: false,
+            host: false,
+            enrichers: vec![],
+            escalation: None,
+            dedup: None,
+            dedup_targets: vec![],
+            span_summary: false,
+            #[cfg(feature = "flame")]
+            flame: None,
+            #[cfg(feature = "chrome")]
+            chrome_trace: None,
+            writer: Box::new(io::stderr()),
+            net_dropped: None,
+            terminal: io::stderr().is_terminal(),
+            color: None,
+            batch: None,
+            #[cfg(feature = "loki")]
+            loki: None,
+            #[cfg(feature = "kafka")]
+            kafka: None,
+            #[cfg(feature = "mqtt")]
+            mqtt: None,
+            store: None,
+            version: String::new(),
+            #[cfg(feature = "webhook")]
+            webhook: None,
+            #[cfg(feature = "email")]
+            email: None,
+        }
+    }
+}
+
+/// Start configuring a subscriber writing `INFO` and above in the `Full` format to stderr
+pub fn builder() -> Builder {
+    Builder::default()
+}
+
+impl Builder {
+    /// The most verbose level written
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = LevelFilter::from_level(level);
+        self
+    }
+
+    /// The layout of each line
+    ///
+    ///     use errlog::logger::{self, Deterministic, Format, MemoryWriter};
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder()
+    ///         .format(Format::Logfmt)
+    ///         .writer(output.clone())
+    ///         .deterministic(Deterministic::MaskLines)
+    ///         .set_default();
+    ///     errlog::logmsg!(WARN, code = "E042", "disk almost full");
+    ///
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(output.contents().starts_with("ts=1970-01-01T00:00:00.000000Z level=warn target=rust_out msg=\"disk almost full\""));
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(output.contents().ends_with(" line=[line] code=E042\n"));
+    ///
+    /// With `Format::Ecs`, the same event lands in Elasticsearch without remapping:
+    ///
+    ///     # use errlog::logger::{self, Deterministic, Format, MemoryWriter};
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder().format(Format::Ecs).writer(output.clone()).set_default();
+    ///     errlog::logmsg!(WARN, code = "E042", "disk almost full");
+    ///
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(output.contents().contains(r#""message":"disk almost full","error.code":"E042""#));
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Color the `Pretty` format, by default when the writer is stderr or stdout attached to a
+    /// terminal and `NO_COLOR` is not set
+    pub fn color(mut self, enabled: bool) -> Self {
+        self.color = Some(enabled);
+        self
+    }
+
+    /// Write the target of events in the `Compact` format, true by default
+    pub fn targets(mut self, enabled: bool) -> Self {
+        self.targets = enabled;
+        self
+    }
+
+    /// The source of timestamps, the system clock by default, see [clock](crate::clock)
+    pub fn clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Where formatted events are written, stderr by default
+    ///
+    /// With a [NetWriter], line breaks inside records are escaped and its dropped records are
+    /// counted by the [guard](Guard::dropped).
+    pub fn writer(mut self, writer: impl Write + Send + 'static) -> Self {
+        let any = &writer as &dyn std::any::Any;
+        self.net_dropped = any.downcast_ref::<NetWriter>().map(NetWriter::counter);
+        self.terminal = any.downcast_ref::<io::Stderr>().is_some_and(IsTerminal::is_terminal)
+            || any.downcast_ref::<io::Stdout>().is_some_and(IsTerminal::is_terminal);
+        self.writer = Box::new(writer);
+        self
+    }
+
+    /// Hand records over to a [BatchWriter] queuing up to `capacity` of them, instead of writing
+    /// them on the logging thread under a lock
+    pub fn batched(mut self, capacity: usize) -> Self {
+        self.batch = Some(capacity);
+        self
+    }
+
+    /// See [Deterministic], `ERRLOG_DETERMINISTIC` takes effect even when this is not set, as read
+    /// when building
+    pub fn deterministic(mut self, deterministic: Deterministic) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Cut messages and field values longer than `max` bytes, see [truncate]
+    ///
+    /// Events carrying the field `untruncated = true` are written whole, and
+    /// [set_max_message_len] applies when this is not set
+    pub fn max_message_len(mut self, max: usize) -> Self {
+        self.max_message_len = Some(max);
+        self
+    }
+
+    /// Write the chain of error fields on indented `caused by:` lines followed by the location of
+    /// the event, instead of one `error=a: b: c` field, in the `Full` format
+    ///
+    ///     use errlog::logger::{self, Deterministic, MemoryWriter};
+    ///     use errlog::{logmsg, wraperr, Context};
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder()
+    ///         .writer(output.clone())
+    ///         .multiline_errors(true)
+    ///         .deterministic(Deterministic::MaskLines)
+    ///         .set_default();
+    ///
+    ///     let err = wraperr!(DEBUG, std::fs::read_to_string("/path/to/config"), "failed to read")
+    ///         .context("failed to load config")
+    ///         .unwrap_err();
+    ///     logmsg!(ERROR, error = errlog::as_dyn_error(&err), "startup failed");
+    ///
+    ///     let expected = "\
+    ///     : startup failed
+    ///         caused by: failed to load config
+    ///         caused by: No such file or directory (os error 2)
+    ///         at src/logger.rs:[line]
+    ///     ";
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(output.contents().ends_with(expected));
+    pub fn multiline_errors(mut self, enabled: bool) -> Self {
+        self.multiline_errors = enabled;
+        self
+    }
+
+    /// Write the chains of error fields as an indented block, with the location where each error
+    /// was wrapped by [wraperr](crate::wraperr) aligned on the right, in the `Full` and `Pretty`
+    /// formats
+    ///
+    ///     use errlog::logger::{self, Deterministic, MemoryWriter};
+    ///     use errlog::{logmsg, wraperr, Context};
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder()
+    ///         .writer(output.clone())
+    ///         .error_blocks(true)
+    ///         .deterministic(Deterministic::MaskLines)
+    ///         .set_default();
+    ///
+    ///     let err = wraperr!(DEBUG, std::fs::read_to_string("/path/to/config"), "failed to read")
+    ///         .context("failed to load config")
+    ///         .unwrap_err();
+    ///     logmsg!(ERROR, error = errlog::as_dyn_error(&err), "startup failed");
+    ///
+    ///     let contents = output.contents();
+    ///     let lines: Vec<&str> = contents.lines().collect();
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     # {
+    ///     assert_eq!(lines[1], "    error: failed to load config");
+    ///     assert!(lines[2].starts_with("      caused by: No such file or directory (os error 2)   "));
+    ///     # #[cfg(not(any(feature = "no-location", feature = "hash-location")))]
+    ///     assert!(lines[2].ends_with(" src/logger.rs:[line]"));
+    ///     assert_eq!(lines[2].len(), 100);
+    ///     # }
+    ///
+    /// The chains are those of the errors recorded with [as_dyn_error](crate::as_dyn_error), it
+    /// takes precedence over [multiline_errors](Builder::multiline_errors).
+    pub fn error_blocks(mut self, enabled: bool) -> Self {
+        self.error_blocks = enabled;
+        self
+    }
+
+    /// Add the `file:line` of the callsite to every record, as the `location =>` prefix of the
+    /// message in the `Full` and `Pretty` formats and as the `file` and `line` fields in the
+    /// `Json` format
+    ///
+    ///     use errlog::logger::{self, Deterministic, Format, MemoryWriter};
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder()
+    ///         .writer(output.clone())
+    ///         .locations(true)
+    ///         .deterministic(Deterministic::MaskLines)
+    ///         .set_default();
+    ///     errlog::logmsg!(INFO, "cache warmed");
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(output.contents().ends_with(": src/logger.rs:[line] => cache warmed\n"));
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder().format(Format::Json).writer(output.clone()).locations(true).set_default();
+    ///     errlog::logmsg!(INFO, "cache warmed");
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(output.contents().contains(r#""message":"cache warmed","file":"src/logger.rs","line":"#));
+    ///
+    /// The `Logfmt`, `Ecs` and `Compact` formats always carry the location. The messages of
+    /// errlog's macros which already start with their location are left alone.
+    pub fn locations(mut self, enabled: bool) -> Self {
+        self.locations = enabled;
+        self
+    }
+
+    /// Add the `pid` field with the process id to every record
+    ///
+    ///     use errlog::logger::{self, Format, MemoryWriter};
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder()
+    ///         .format(Format::Json)
+    ///         .writer(output.clone())
+    ///         .pid(true)
+    ///         .thread_ids(true)
+    ///         .thread_names(true)
+    ///         .set_default();
+    ///     errlog::logmsg!(WARN, "queue is full");
+    ///
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(output.contents().contains(&format!(r#""pid":"{}","thread_id":""#, std::process::id())));
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(output.contents().contains(r#""thread_name":"main""#));
+    ///
+    /// The threads of errlog itself are named `errlog-<purpose>`.
+    pub fn pid(mut self, enabled: bool) -> Self {
+        self.pid = enabled;
+        self
+    }
+
+    /// Add the `thread_id` field with the id of the current thread to every record
+    pub fn thread_ids(mut self, enabled: bool) -> Self {
+        self.thread_ids = enabled;
+        self
+    }
+
+    /// Add the `thread_name` field with the name of the current thread to every record, or
+    /// `<unnamed>`
+    pub fn thread_names(mut self, enabled: bool) -> Self {
+        self.thread_names = enabled;
+        self
+    }
+
+    /// Add the `host` field with the hostname to every record, and in Kubernetes the
+    /// `k8s.namespace`, `k8s.pod`, `k8s.container` and `k8s.node` fields
+    ///
+    /// The Kubernetes fields come from the `POD_NAMESPACE`, `POD_NAME`, `CONTAINER_NAME` and
+    /// `NODE_NAME` variables, which the pod spec sets with the downward API, for example
+    ///
+    /// ```yaml
+    /// env:
+    ///   - name: POD_NAME
+    ///     valueFrom:
+    ///       fieldRef:
+    ///         fieldPath: metadata.name
+    /// ```
+    ///
+    /// They are read once when building the subscriber.
+    pub fn host(mut self, enabled: bool) -> Self {
+        self.host = enabled;
+        self
+    }
+
+    /// Add or rewrite fields of every record, after the fields of the event and the built-in
+    /// ones, enrichers run in the order they were added
+    ///
+    ///     use errlog::logger::{self, FieldMap, MemoryWriter};
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder()
+    ///         .writer(output.clone())
+    ///         .enricher(|fields: &mut FieldMap| fields.insert("region", "eu-west-1"))
+    ///         .set_default();
+    ///     errlog::logmsg!(WARN, tenant = "acme", "quota almost reached");
+    ///
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(output.contents().ends_with("quota almost reached tenant=acme region=eu-west-1\n"));
+    pub fn enricher(mut self, enricher: impl Enricher) -> Self {
+        self.enrichers.push(Box::new(enricher));
+        self
+    }
+
+    /// Write a `WARN` as `ERROR` with a `repeat_count` field once the same event was logged more
+    /// than `times` within `window`, so that chronic failures eventually trip alerting
+    ///
+    ///     use std::time::Duration;
+    ///
+    ///     use errlog::logger::{self, MemoryWriter};
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let _guard = logger::builder()
+    ///         .writer(output.clone())
+    ///         .escalate_warnings(2, Duration::from_secs(60))
+    ///         .set_default();
+    ///     for _ in 0..3 {
+    ///         errlog::logmsg!(WARN, "cache miss");
+    ///     }
+    ///
+    ///     let lines: Vec<String> = output.contents().lines().map(String::from).collect();
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(lines[1].contains(" WARN ") && lines[2].contains("ERROR") && lines[2].ends_with("repeat_count=3"));
+    ///
+    /// Events are the same when they come from the same callsite with the same message, the
+    /// count starts over after each escalation, and time comes from the [clock](Builder::clock).
+    pub fn escalate_warnings(mut self, times: usize, window: Duration) -> Self {
+        self.escalation = Some((times, window));
+        self
+    }
+
+    /// Leave out repetitions of an event until it stopped repeating for `window`, then write
+    /// `previous message repeated N times`
+    ///
+    ///     use std::time::Duration;
+    ///
+    ///     use errlog::clock::MockClock;
+    ///     use errlog::logger::{self, MemoryWriter};
+    ///
+    ///     let clock = MockClock::new(std::time::UNIX_EPOCH);
+    ///     let output = MemoryWriter::new();
+    ///     let guard = logger::builder()
+    ///         .writer(output.clone())
+    ///         .clock(clock.clone())
+    ///         .dedup(Duration::from_secs(10))
+    ///         .set_default();
+    ///     for _ in 0..58 {
+    ///         errlog::logmsg!(WARN, "disk almost full");
+    ///         clock.advance(Duration::from_secs(5));
+    ///     }
+    ///     clock.advance(Duration::from_secs(5));
+    ///     errlog::logmsg!(INFO, "disk cleaned");
+    ///     drop(guard);
+    ///
+    ///     let lines: Vec<String> = output.contents().lines().map(String::from).collect();
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert_eq!(lines.len(), 3);
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(lines[1].ends_with("previous message repeated 57 times"));
+    ///
+    /// Events are the same when they come from the same callsite with the same message, and each
+    /// repetition extends the window, like the `last message repeated` lines of syslog. Pending
+    /// summaries are written by the next event after the window and when the guard is dropped.
+    pub fn dedup(mut self, window: Duration) -> Self {
+        self.dedup = Some(window);
+        self
+    }
+
+    /// Use another [dedup](Builder::dedup) window for a target and its children, a zero window
+    /// disables deduplication for them
+    pub fn dedup_target(mut self, target: &str, window: Duration) -> Self {
+        self.dedup_targets.push((target.to_string(), window));
+        self
+    }
+
+    /// Time every span and write a table of their count, total, mean and longest durations by
+    /// name when the guard is dropped
+    ///
+    ///     use errlog::logger::{self, MemoryWriter};
+    ///
+    ///     let output = MemoryWriter::new();
+    ///     let guard = logger::builder().writer(output.clone()).span_summary(true).set_default();
+    ///     for _ in 0..3 {
+    ///         let _span = tracing::info_span!("load_config").entered();
+    ///     }
+    ///     tracing::debug_span!("connect").in_scope(|| std::thread::sleep(std::time::Duration::from_millis(5)));
+    ///     drop(guard);
+    ///
+    ///     let lines: Vec<String> = output.contents().lines().map(String::from).collect();
+    ///     assert!(lines[0].ends_with("span timings"));
+    ///     assert!(lines[2].starts_with("connect "));
+    ///     assert!(lines[3].starts_with("load_config        3 "));
+    ///
+    /// Spans are timed from their creation to their close, whatever the level, and the rows are
+    /// sorted by decreasing total time. Nothing is written when no span closed.
+    pub fn span_summary(mut self, enabled: bool) -> Self {
+        self.span_summary = enabled;
+        self
+    }
+
+    /// Also write the time spent in every stack of spans to `path` in the folded format of
+    /// [inferno](https://github.com/jonhoo/inferno) when the guard is dropped
+    ///
+    ///     use errlog::logger::{self, MemoryWriter};
+    ///
+    ///     let path = std::env::temp_dir().join(format!("errlog-{}.folded", std::process::id()));
+    ///     let guard = logger::builder().writer(MemoryWriter::new()).flame(&path).set_default();
+    ///     tracing::info_span!("request").in_scope(|| {
+    ///         tracing::info_span!("query").in_scope(|| std::thread::sleep(std::time::Duration::from_millis(5)));
+    ///     });
+    ///     drop(guard);
+    ///
+    ///     let folded = std::fs::read_to_string(&path).unwrap();
+    ///     # std::fs::remove_file(&path).unwrap();
+    ///     assert!(folded.lines().any(|line| line.starts_with("request;query ")));
+    ///
+    /// Each line holds the microseconds spans were entered, minus the time of their children, so
+    /// `inferno-flamegraph out.folded > flame.svg` draws them. Spans are recorded whatever the
+    /// level, and the file is replaced.
+    #[cfg(feature = "flame")]
+    pub fn flame(mut self, path: impl AsRef<std::path::Path>) -> Self {
+        self.flame = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Also write the spans and events to `path` as Chrome trace events, which Perfetto and
+    /// `chrome://tracing` open
+    ///
+    ///     use errlog::logger::{self, MemoryWriter};
+    ///
+    ///     let path = std::env::temp_dir().join(format!("errlog-{}.trace.json", std::process::id()));
+    ///     let guard = logger::builder().writer(MemoryWriter::new()).chrome_trace(&path).set_default();
+    ///     tracing::info_span!("request", id = 7).in_scope(|| errlog::logmsg!(WARN, "slow query"));
+    ///     drop(guard);
+    ///
+    ///     let trace = std::fs::read_to_string(&path).unwrap();
+    ///     # std::fs::remove_file(&path).unwrap();
+    ///     assert!(trace.starts_with("[\n{\"name\":\"request\","));
+    ///     assert!(trace.contains(r#""ph":"B","ts":"#));
+    ///     assert!(trace.contains(r#""args":{"id":"7"}}"#));
+    ///     # #[cfg(not(feature = "silent"))]
+    ///     assert!(trace.contains(r#""ph":"i","#));
+    ///     assert!(trace.ends_with("\n]\n"));
+    ///
+    /// Spans become `B` and `E` events each time they are entered and exited, events become
+    /// instant `i` events named after their message with their level and fields as `args`. Only
+    /// the levels written to the writer are kept. The file is replaced when building and
+    /// buffered, and dropping the guard flushes it and closes the array.
+    #[cfg(feature = "chrome")]
+    pub fn chrome_trace(mut self, path: impl AsRef<std::path::Path>) -> Self {
+        self.chrome_trace = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Also push records to Grafana Loki from a background thread, in the configured format
+    ///
+    ///     use errlog::logger::{self, Format, Loki};
+    ///
+    ///     let _guard = logger::builder()
+    ///         .format(Format::Logfmt)
+    ///         .loki(Loki::new("http://localhost:3100").label("app", "billing").label_field("code"))
+    ///         .set_default();
+    ///
+    /// Records are dropped when the queue is full or the pushes keep failing, the
+    /// [guard](Guard::dropped) counts them, and dropping the guard waits for the pending pushes.
+    /// HTTPS endpoints are reached with rustls and the platform's certificates.
+    #[cfg(feature = "loki")]
+    pub fn loki(mut self, loki: Loki) -> Self {
+        self.loki = Some(loki);
+        self
+    }
+
+    /// Also publish records in the `Json` format to Kafka from a background thread, keyed by the
+    /// fingerprint of the event, see [Kafka]
+    ///
+    /// Records are dropped when the queue is full, the [guard](Guard::dropped) counts them, and
+    /// dropping the guard waits for the pending batch.
+    #[cfg(feature = "kafka")]
+    pub fn kafka(mut self, kafka: Kafka) -> Self {
+        self.kafka = Some(kafka);
+        self
+    }
+
+    /// Also publish `WARN` and `ERROR` records in the `Json` format to an MQTT broker from a
+    /// background thread, see [Mqtt]
+    ///
+    ///     use errlog::logger::{self, Mqtt, MqttQos};
+    ///
+    ///     let _guard = logger::builder()
+    ///         .mqtt(Mqtt::new("localhost:1883", "devices/42/errors").qos(MqttQos::AtLeastOnce))
+    ///         .set_default();
+    ///
+    /// The [guard](Guard::dropped) counts the records dropped from the full buffer. Dropping it
+    /// makes a last attempt to publish the buffered records, even during a backoff, and counts
+    /// the ones still failing as dropped.
+    #[cfg(feature = "mqtt")]
+    pub fn mqtt(mut self, mqtt: Mqtt) -> Self {
+        self.mqtt = Some(mqtt);
+        self
+    }
+
+    /// Also keep `ERROR` records in `store`, forgetting them after `retention`, see [MemoryStore]
+    /// and, with the `sqlite` feature, `SqliteStore`
+    pub fn store(mut self, store: impl ErrorStore, retention: Duration) -> Self {
+        self.store = Some((Box::new(store), retention));
+        self
+    }
+
+    /// The version of the application, recorded with the errors of the [store](Builder::store)
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = version.to_string();
+        self
+    }
+
+    /// Also post alerts for `ERROR` records to a chat webhook from a background thread, see
+    /// [Webhook]
+    ///
+    ///     use std::time::Duration;
+    ///
+    ///     use errlog::logger::{self, Webhook};
+    ///
+    ///     let _guard = logger::builder()
+    ///         .webhook(Webhook::slack("https://hooks.slack.com/services/T0/B0/X").code("E042").tag("alert", "oncall"))
+    ///         .set_default();
+    ///
+    /// Dropping the guard posts the collected records right away. HTTPS endpoints are reached with
+    /// rustls and the platform's certificates.
+    #[cfg(feature = "webhook")]
+    pub fn webhook(mut self, webhook: Webhook) -> Self {
+        self.webhook = Some(webhook);
+        self
+    }
+
+    /// Also email tagged `ERROR` records and panics with the records logged before, see [Email]
+    ///
+    ///     use errlog::logger::{self, Email};
+    ///
+    ///     let _guard = logger::builder()
+    ///         .email(Email::new("localhost:25", "jobs@example.com", "oncall@example.com").panics(false))
+    ///         .set_default();
+    ///     errlog::logmsg!(INFO, "nightly import started");
+    ///     // errlog::logmsg!(ERROR, notify = true, "nightly import failed");
+    ///
+    /// Panics are caught by a panic hook installed by the first logger emailing them, which calls
+    /// the previous one. Dropping the guard waits for the queued emails.
+    #[cfg(feature = "email")]
+    pub fn email(mut self, email: Email) -> Self {
+        self.email = Some(email);
+        self
+    }
+
+    /// Build the subscriber without installing it
+    pub fn build(self) -> (Dispatch, Guard) {
+        INSTALLED.store(self.deterministic as u8, Ordering::Relaxed);
+        let deterministic = self.deterministic.max(Deterministic::from_env());
+        let names = Arc::new(Names::resolve());
+        let layout = Layout {
+            multiline_errors: self.multiline_errors,
+            error_blocks: self.error_blocks,
+            targets: self.targets,
+            color: self.color.unwrap_or_else(|| self.terminal && std::env::var_os("NO_COLOR").is_none()),
+            locations: self.locations,
+            single_line: self.net_dropped.is_some(),
+            names: Some(names.clone()),
+        };
+        let sink = match self.batch {
+            Some(capacity) => Sink::Batched(BatchWriter::new(self.writer, capacity)),
+            None => Sink::Locked(Arc::new(Mutex::new(self.writer))),
+        };
+        let repeats = (self.dedup.is_some() || !self.dedup_targets.is_empty()).then(|| Repeats {
+            dedup: Arc::new(Dedup::new(self.dedup, self.dedup_targets)),
+            format: self.format,
+            layout: layout.clone(),
+            clock: self.clock.clone(),
+            deterministic,
+            sink: sink.clone(),
+        });
+        #[cfg(feature = "loki")]
+        let loki = self.loki.map(loki::LokiClient::new);
+        #[cfg(feature = "kafka")]
+        let kafka = self.kafka.map(kafka::KafkaClient::new);
+        #[cfg(feature = "mqtt")]
+        let mqtt = self.mqtt.map(mqtt::MqttClient::new);
+        #[cfg(feature = "webhook")]
+        let webhook = self.webhook.map(|webhook| Arc::new(webhook::WebhookClient::new(webhook)));
+        let timings = self.span_summary.then(timings::Timings::default);
+        let summary = timings.clone().map(|timings| timings::Summary {
+            timings,
+            format: self.format,
+            layout: layout.clone(),
+            clock: self.clock.clone(),
+            deterministic,
+            sink: sink.clone(),
+        });
+        #[cfg(feature = "flame")]
+        let flame = self.flame.map(flame::Flame::new);
+        #[cfg(feature = "chrome")]
+        let chrome = self.chrome_trace.as_deref().map(chrome::Chrome::new);
+        #[cfg(feature = "email")]
+        let email = self.email.map(email::EmailClient::new);
+        let guard = Guard {
+            sink: sink.clone(),
+            net_dropped: self.net_dropped,
+            repeats: repeats.clone(),
+            summary,
+            #[cfg(feature = "flame")]
+            flame: flame.clone(),
+            #[cfg(feature = "chrome")]
+            chrome: chrome.clone(),
+            #[cfg(feature = "loki")]
+            loki: loki.clone(),
+            #[cfg(feature = "kafka")]
+            kafka: kafka.clone(),
+            #[cfg(feature = "mqtt")]
+            mqtt: mqtt.clone(),
+            #[cfg(feature = "webhook")]
+            webhook: webhook.clone(),
+            #[cfg(feature = "email")]
+            email: email.clone(),
+            _default: None,
+        };
+        let budgets = crate::budget::layer().shared_clock(self.clock.clone());
+        let layer = FormatLayer {
+            format: self.format,
+            layout,
+            clock: self.clock,
+            deterministic,
+            max_message_len: self.max_message_len,
+            pid: self.pid,
+            thread_ids: self.thread_ids,
+            thread_names: self.thread_names,
+            host: if self.host { host_fields(&names.hostname) } else { vec![] },
+            enrichers: self.enrichers,
+            escalation: self.escalation.map(|(times, window)| Escalation::new(times, window)),
+            repeats,
+            sink,
+            #[cfg(feature = "loki")]
+            loki,
+            #[cfg(feature = "kafka")]
+            kafka,
+            #[cfg(feature = "mqtt")]
+            mqtt,
+            store: self.store.map(|(store, retention)| store::Storing::new(store, retention, self.version)),
+            #[cfg(feature = "webhook")]
+            webhook,
+            #[cfg(feature = "email")]
+            email,
+        };
+        let subscriber = tracing_subscriber::registry()
+            .with(layer.with_filter(self.level))
+            .with(budgets)
+            .with(timings);
+        #[cfg(feature = "flame")]
+        let subscriber = subscriber.with(flame);
+        #[cfg(feature = "chrome")]
+        let subscriber = subscriber.with(chrome.map(|chrome| chrome.with_filter(self.level)));
+        (Dispatch::new(subscriber), guard)
+    }
+
+    /// Install the subscriber as the global default, failing when one is already installed
+    pub fn init(self) -> Result<Guard> {
+        let (dispatch, guard) = self.build();
+        tracing::dispatcher::set_global_default(dispatch)
+            .map_err(|err| crate::Error::msg(format!("failed to install the logger: {}", err)))?;
+        Ok(guard)
+    }
+
+    /// Install the subscriber as the default of the current thread until the guard is dropped
+    pub fn set_default(self) -> Guard {
+        let (dispatch, mut guard) = self.build();
+        guard._default = Some(tracing::dispatcher::set_default(&dispatch));
+        guard
+    }
+}
+
+/// Flushes the writer when dropped, keep it alive as long as logging is needed
+pub struct Guard {
+    sink: Sink,
+    net_dropped: Option<Arc<AtomicU64>>,
+    repeats: Option<Repeats>,
+    summary: Option<timings::Summary>,
+    #[cfg(feature = "flame")]
+    flame: Option<flame::Flame>,
+    #[cfg(feature = "chrome")]
+    chrome: Option<chrome::Chrome>,
+    #[cfg(feature = "loki")]
+    loki: Option<loki::LokiClient>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<kafka::KafkaClient>,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<mqtt::MqttClient>,
+    #[cfg(feature = "webhook")]
+    webhook: Option<Arc<webhook::WebhookClient>>,
+    #[cfg(feature = "email")]
+    email: Option<email::EmailClient>,
+    _default: Option<DefaultGuard>,
+}
+
+impl Guard {
+    /// How many records the [batched](Builder::batched) writer, [Loki](Builder::loki),
+    /// [Kafka](Builder::kafka), [MQTT](Builder::mqtt), the [webhook](Builder::webhook) or a
+    /// [NetWriter] dropped so far
+    pub fn dropped(&self) -> u64 {
+        let dropped = match &self.sink {
+            Sink::Batched(writer) => writer.dropped(),
+            Sink::Locked(_) => 0,
+        };
+        let dropped = dropped + self.net_dropped.as_ref().map_or(0, |net| net.load(Ordering::Relaxed));
+        #[cfg(feature = "loki")]
+        let dropped = dropped + self.loki.as_ref().map_or(0, |loki| loki.dropped());
+        #[cfg(feature = "kafka")]
+        let dropped = dropped + self.kafka.as_ref().map_or(0, |kafka| kafka.dropped());
+        #[cfg(feature = "mqtt")]
+        let dropped = dropped + self.mqtt.as_ref().map_or(0, |mqtt| mqtt.dropped());
+        #[cfg(feature = "webhook")]
+        let dropped = dropped + self.webhook.as_ref().map_or(0, |webhook| webhook.dropped());
+        dropped
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if let Some(repeats) = &self.repeats {
+            repeats.write(None);
+        }
+        if let Some(summary) = &self.summary {
+            summary.write();
+        }
+        #[cfg(feature = "flame")]
+        if let Some(flame) = &self.flame {
+            flame.write();
+        }
+        #[cfg(feature = "chrome")]
+        if let Some(chrome) = &self.chrome {
+            chrome.finish();
+        }
+        self.sink.flush();
+        #[cfg(feature = "loki")]
+        if let Some(loki) = &self.loki {
+            loki.sync();
+        }
+        #[cfg(feature = "kafka")]
+        if let Some(kafka) = &self.kafka {
+            kafka.sync();
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(mqtt) = &self.mqtt {
+            mqtt.sync();
+        }
+        #[cfg(feature = "webhook")]
+        if let Some(webhook) = &self.webhook {
+            webhook.sync();
+        }
+        #[cfg(feature = "email")]
+        if let Some(email) = &self.email {
+            email.sync();
+        }
+    }
+}
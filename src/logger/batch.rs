@@ -0,0 +1,124 @@
+//! A writer handing records over to a background thread
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+
+/// The largest batch written in one `write` call
+const MAX_BATCH_BYTES: usize = 64 * 1024;
+
+enum Message {
+    Record(Vec<u8>),
+    Flush(SyncSender<()>),
+}
+
+/// A writer queuing records for a dedicated thread which writes them in batches
+///
+/// The queue is std's bounded channel, which is lock-free, so loggers on many threads never wait
+/// on each other nor on the underlying writer. The queue holds at most `capacity` records, when it
+/// is full records are dropped and counted rather than blocking the caller, and the thread writes
+/// a line reporting the drops once there is room again. Clones share the same queue.
+///
+///     use errlog::logger::{BatchWriter, MemoryWriter};
+///     use std::io::Write;
+///
+///     let output = MemoryWriter::new();
+///     let mut writer = BatchWriter::new(output.clone(), 1024);
+///     writer.write_all(b"first\n").unwrap();
+///     writer.write_all(b"second\n").unwrap();
+///     writer.flush().unwrap();
+///     assert_eq!(output.contents(), "first\nsecond\n");
+///     assert_eq!(writer.dropped(), 0);
+///
+/// With the [logger](crate::logger), use [Builder::batched](super::Builder::batched) instead.
+#[derive(Clone)]
+pub struct BatchWriter {
+    sender: SyncSender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl BatchWriter {
+    /// Spawn the writing thread, queuing at most `capacity` records
+    pub fn new(writer: impl Write + Send + 'static, capacity: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(capacity.max(1));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let counter = dropped.clone();
+        thread::Builder::new()
+            .name("errlog-writer".to_string())
+            .spawn(move || drain(receiver, writer, counter))
+            .expect("failed to spawn the errlog writer thread");
+        BatchWriter { sender, dropped }
+    }
+
+    /// Queue one record, dropping it when the queue is full
+    pub fn send(&self, record: &[u8]) {
+        match self.sender.try_send(Message::Record(record.to_vec())) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    /// How many records were dropped so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Wait until every queued record is written and the underlying writer is flushed
+    pub fn sync(&self) {
+        let (ack, done) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Flush(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+}
+
+impl Write for BatchWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.send(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.sync();
+        Ok(())
+    }
+}
+
+fn drain(receiver: Receiver<Message>, mut writer: impl Write, dropped: Arc<AtomicU64>) {
+    let mut reported = 0;
+    let mut batch = Vec::with_capacity(MAX_BATCH_BYTES);
+    let mut acks = vec![];
+    while let Ok(message) = receiver.recv() {
+        let mut next = Some(message);
+        while let Some(message) = next.take() {
+            match message {
+                Message::Record(record) => batch.extend_from_slice(&record),
+                Message::Flush(ack) => acks.push(ack),
+            }
+            if batch.len() < MAX_BATCH_BYTES {
+                next = receiver.try_recv().ok();
+            }
+        }
+        let total = dropped.load(Ordering::Relaxed);
+        if total > reported {
+            batch.extend_from_slice(
+                format!("errlog: {} record(s) dropped, the queue was full\n", total - reported).as_bytes(),
+            );
+            reported = total;
+        }
+        let _ = writer.write_all(&batch);
+        batch.clear();
+        if !acks.is_empty() {
+            let _ = writer.flush();
+            for ack in acks.drain(..) {
+                let _ = ack.send(());
+            }
+        }
+    }
+    let _ = writer.flush();
+}
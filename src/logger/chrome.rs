@@ -0,0 +1,118 @@
+//! Spans and events in the Chrome Trace Event format for [Builder::chrome_trace](super::Builder::chrome_trace)
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context as LayerContext, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::{json_string, thread_id};
+use crate::fields::FieldVisitor;
+
+struct Trace {
+    file: BufWriter<File>,
+    written: usize,
+    finished: bool,
+}
+
+/// Writes a JSON array of trace events to a file, clones share it
+#[derive(Clone)]
+pub(super) struct Chrome {
+    trace: Arc<Mutex<Option<Trace>>>,
+    started: Instant,
+}
+
+/// The `args` of the `B` event of a span
+struct Args(String);
+
+impl Chrome {
+    /// Nothing is written when the file cannot be created
+    pub(super) fn new(path: &Path) -> Self {
+        let trace = File::create(path).ok().map(|file| Trace { file: BufWriter::new(file), written: 0, finished: false });
+        Chrome { trace: Arc::new(Mutex::new(trace)), started: Instant::now() }
+    }
+
+    /// Append an event with the phase `ph` and the extra `members`, already JSON
+    fn write(&self, ph: &str, name: &str, category: &str, members: &str) {
+        let mut out = String::from("{\"name\":");
+        json_string(&mut out, name);
+        out.push_str(",\"cat\":");
+        json_string(&mut out, category);
+        out.push_str(&format!(
+            ",\"ph\":\"{}\",\"ts\":{},\"pid\":{},\"tid\":{}{}}}",
+            ph,
+            self.started.elapsed().as_micros(),
+            std::process::id(),
+            thread_id(),
+            members
+        ));
+        let mut trace = self.trace.lock().unwrap_or_else(|err| err.into_inner());
+        let Some(trace) = trace.as_mut().filter(|trace| !trace.finished) else { return };
+        let separator = if trace.written == 0 { "[\n" } else { ",\n" };
+        let _ = write!(trace.file, "{}{}", separator, out);
+        trace.written += 1;
+    }
+
+    /// Close the array and flush the file, later events are left out
+    pub(super) fn finish(&self) {
+        let mut trace = self.trace.lock().unwrap_or_else(|err| err.into_inner());
+        let Some(trace) = trace.as_mut().filter(|trace| !trace.finished) else { return };
+        let _ = trace.file.write_all(if trace.written == 0 { b"[]\n" } else { b"\n]\n" });
+        let _ = trace.file.flush();
+        trace.finished = true;
+    }
+}
+
+/// `,"args":{...}` with the fields, after `first`
+fn args(first: Option<(&str, &str)>, fields: &[(String, String)]) -> String {
+    let mut out = String::from(",\"args\":{");
+    for (index, (name, value)) in first.into_iter().chain(fields.iter().map(|(name, value)| (name.as_str(), value.as_str()))).enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        json_string(&mut out, name);
+        out.push(':');
+        json_string(&mut out, value);
+    }
+    out.push('}');
+    out
+}
+
+impl<S> Layer<S> for Chrome
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Args(args(None, &visitor.fields)));
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let extensions = span.extensions();
+        let args = extensions.get::<Args>().map_or("", |args| args.0.as_str());
+        self.write("B", span.name(), span.metadata().target(), args);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: LayerContext<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            self.write("E", span.name(), span.metadata().target(), "");
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, _: LayerContext<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let members = format!(",\"s\":\"t\"{}", args(Some(("level", metadata.level().as_str())), &visitor.fields));
+        self.write("i", &visitor.message, metadata.target(), &members);
+    }
+}
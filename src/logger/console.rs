@@ -0,0 +1,156 @@
+//! Layouts meant for people reading a terminal
+
+use tracing::Level;
+
+use super::{rfc3339, Record};
+
+/// The total width [Format::Pretty](super::Format::Pretty) wraps lines at
+const WIDTH: usize = 100;
+/// The width of the target column, longer targets push the message
+const TARGET_WIDTH: usize = 16;
+
+fn paint(text: &str, ansi: &str, color: bool) -> String {
+    match color {
+        true => format!("\x1b[{}m{}\x1b[0m", ansi, text),
+        false => text.to_string(),
+    }
+}
+
+fn level_color(level: Level) -> &'static str {
+    match level {
+        Level::ERROR => "1;31",
+        Level::WARN => "1;33",
+        Level::INFO => "1;32",
+        Level::DEBUG => "1;34",
+        _ => "1;35",
+    }
+}
+
+/// The words of `text` in lines of at most `width` chars, longer words on their own line
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut line));
+            } else if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Dim the `file:line => ` prefix of errlog's messages
+fn dim_location(line: &str, color: bool) -> String {
+    match line.split_once(" => ") {
+        Some((location, rest)) if !location.contains(' ') && location.contains(':') => {
+            format!("{} {}", paint(&format!("{} =>", location), "2", color), rest)
+        }
+        _ => line.to_string(),
+    }
+}
+
+/// Append `record` with aligned columns, see [Format::Pretty](super::Format::Pretty)
+pub(super) fn render_pretty(out: &mut String, record: &Record, error_blocks: bool, color: bool) {
+    let timestamp = rfc3339(record.timestamp);
+    // `HH:MM:SS.mmm` from `YYYY-MM-DDTHH:MM:SS.uuuuuuZ`
+    let time = &timestamp[11..23];
+    let target = format!("{:<width$}", record.target, width = TARGET_WIDTH);
+    out.push_str(&paint(time, "2", color));
+    out.push(' ');
+    out.push_str(&paint(&format!("{:>5}", record.level), level_color(record.level), color));
+    out.push(' ');
+    out.push_str(&paint(&target, "2", color));
+    out.push(' ');
+
+    let indent = time.len() + 1 + 5 + 1 + TARGET_WIDTH + 1;
+    let mut text = String::new();
+    for span in &record.spans {
+        text.push_str(span);
+        text.push(':');
+    }
+    if !record.spans.is_empty() {
+        text.push(' ');
+    }
+    text.push_str(&record.message);
+    for (name, value) in &record.fields {
+        if !record.errors.iter().any(|(error, _)| error == name) {
+            text.push_str(&format!(" {}={}", name, value));
+        }
+    }
+    for (index, line) in wrap(&text, WIDTH.saturating_sub(indent).max(20)).iter().enumerate() {
+        if index > 0 {
+            out.push('\n');
+            out.push_str(&" ".repeat(indent));
+        }
+        out.push_str(&match index {
+            0 => dim_location(line, color),
+            _ => line.clone(),
+        });
+    }
+    if error_blocks {
+        return error_block(out, record, indent, color);
+    }
+    for cause in record.errors.iter().flat_map(|(_, chain)| chain) {
+        out.push('\n');
+        out.push_str(&" ".repeat(indent));
+        out.push_str(&paint("caused by:", "2", color));
+        out.push(' ');
+        out.push_str(cause);
+    }
+}
+
+/// Append the chains of the error fields indented by `indent`, each error on its line with the
+/// location where it was wrapped on the right
+pub(super) fn error_block(out: &mut String, record: &Record, indent: usize, color: bool) {
+    let locations = record.error_locations.iter().map(Some).chain(std::iter::repeat(None));
+    for ((name, chain), locations) in record.errors.iter().zip(locations) {
+        for (index, cause) in chain.iter().enumerate() {
+            let label = match index {
+                0 => format!("{}{}:", " ".repeat(indent), name),
+                _ => format!("{}  caused by:", " ".repeat(indent)),
+            };
+            let line = format!("{} {}", label, cause);
+            out.push('\n');
+            out.push_str(&paint(&label, "2", color));
+            out.push(' ');
+            out.push_str(cause);
+            let location = locations.and_then(|locations| locations.get(index)).and_then(Option::as_deref);
+            if let Some(location) = location {
+                let used = line.chars().count() + 1 + location.chars().count();
+                out.push_str(&" ".repeat(WIDTH.saturating_sub(used) + 1));
+                out.push_str(&paint(location, "2", color));
+            }
+        }
+    }
+}
+
+/// Append `record` on one dense line, see [Format::Compact](super::Format::Compact)
+pub(super) fn render_compact(out: &mut String, record: &Record, targets: bool) {
+    let timestamp = rfc3339(record.timestamp);
+    // `HH:MM:SS` from `YYYY-MM-DDTHH:MM:SS.uuuuuuZ`
+    out.push_str(&timestamp[11..19]);
+    out.push(' ');
+    out.push_str(&record.level.as_str()[..1]);
+    if targets {
+        out.push(' ');
+        out.push_str(&record.target);
+    }
+    let (location, message) = match record.message.split_once(" => ") {
+        Some((location, message)) if !location.contains(' ') && location.contains(':') => (Some(location), message),
+        _ => (record.location.as_deref(), record.message.as_str()),
+    };
+    if let Some(location) = location {
+        out.push(' ');
+        out.push_str(location);
+    }
+    out.push(' ');
+    out.push_str(message);
+    for (name, value) in &record.fields {
+        out.push_str(&format!(" {}={}", name, value));
+    }
+}
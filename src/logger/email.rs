@@ -0,0 +1,405 @@
+//! Emailing fatal errors
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex, Once, Weak};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{rfc3339, Format, Layout, Record};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where and when to email alerts, see [Builder::email](super::Builder::email)
+///
+/// An email is sent for `ERROR` records with one of the [tags](Email::tag) and for panics. It
+/// holds the record with its error chain and the records logged just before. After an email,
+/// tagged records only count until the [throttle](Email::throttle) ends, and the next email tells
+/// how many were left out. Panics are always sent, before the panic unwinds.
+///
+/// Emails are sent one after the other from a background thread, and dropping the
+/// [guard](super::Guard) waits for the queued ones.
+///
+///     use errlog::logger::{self, Email};
+///     # use std::io::{BufRead, BufReader, Write};
+///     # let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+///     # let server = listener.local_addr().unwrap().to_string();
+///     # let relay = std::thread::spawn(move || {
+///     #     let (mut stream, _) = listener.accept().unwrap();
+///     #     let mut reader = BufReader::new(stream.try_clone().unwrap());
+///     #     let mut received = String::new();
+///     #     stream.write_all(b"220 relay\r\n").unwrap();
+///     #     let mut line = String::new();
+///     #     while reader.read_line(&mut line).unwrap() > 0 {
+///     #         let reply: &[u8] = match line.trim_end() {
+///     #             "DATA" => b"354 go on\r\n",
+///     #             "." => b"250 queued\r\n",
+///     #             "QUIT" => b"221 bye\r\n",
+///     #             text if text.starts_with("EHLO") || text.starts_with("MAIL") || text.starts_with("RCPT") => b"250 ok\r\n",
+///     #             _ => b"",
+///     #         };
+///     #         received.push_str(&line);
+///     #         stream.write_all(reply).unwrap();
+///     #         line.clear();
+///     #     }
+///     #     received
+///     # });
+///
+///     let guard = logger::builder().email(Email::new(&server, "jobs@example.com", "oncall@example.com")).set_default();
+///     errlog::logmsg!(ERROR, notify = true, "nightly import failed");
+///     drop(guard);
+///
+///     # #[cfg(not(feature = "silent"))]
+///     # {
+///     # let received = relay.join().unwrap();
+///     assert!(received.contains("Date: "));
+///     assert!(received.contains("Subject: nightly import failed"));
+///     assert!(received.contains("Message-ID: <"));
+///     # }
+///
+/// This is a small SMTP client over plain TCP, meant for a relay on the host or the local network.
+/// With [credentials](Email::credentials), the connection is upgraded with `STARTTLS` first and
+/// nothing is sent when the server does not offer it.
+#[derive(Debug, Clone)]
+pub struct Email {
+    server: String,
+    from: String,
+    to: Vec<String>,
+    credentials: Option<(String, String)>,
+    tags: Vec<(String, String)>,
+    context: usize,
+    throttle: Duration,
+    panics: bool,
+}
+
+impl Email {
+    /// Send from `from` to `to` through the SMTP server at `server`, like `localhost:25`
+    pub fn new(server: &str, from: &str, to: &str) -> Self {
+        Email {
+            server: server.to_string(),
+            from: from.to_string(),
+            to: vec![to.to_string()],
+            credentials: None,
+            tags: vec![("notify".to_string(), "true".to_string())],
+            context: 20,
+            throttle: Duration::from_secs(15 * 60),
+            panics: true,
+        }
+    }
+
+    /// Send to one more recipient
+    pub fn to(mut self, to: &str) -> Self {
+        self.to.push(to.to_string());
+        self
+    }
+
+    /// Log in with `AUTH PLAIN`, over TLS only
+    pub fn credentials(mut self, username: &str, password: &str) -> Self {
+        self.credentials = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    /// Email `ERROR` records with the field `name` set to `value`, in addition to `notify = true`
+    pub fn tag(mut self, name: &str, value: &str) -> Self {
+        self.tags.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// How many of the previous records are included, the default is 20
+    pub fn context(mut self, records: usize) -> Self {
+        self.context = records;
+        self
+    }
+
+    /// The shortest time between two emails, the default is 15 minutes
+    pub fn throttle(mut self, throttle: Duration) -> Self {
+        self.throttle = throttle;
+        self
+    }
+
+    /// Whether panics are emailed, the default is true
+    pub fn panics(mut self, enabled: bool) -> Self {
+        self.panics = enabled;
+        self
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (index, byte)| n | (*byte as u32) << (16 - 8 * index));
+        for index in 0..4 {
+            match index <= chunk.len() {
+                true => out.push(ALPHABET[(n >> (18 - 6 * index) & 63) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+/// Send `command` and check the reply starts with `expected`, returning the reply
+fn command<S: Read + Write>(stream: &mut BufReader<S>, command: Option<&str>, expected: &str) -> io::Result<String> {
+    if let Some(command) = command {
+        stream.get_mut().write_all(command.as_bytes())?;
+        stream.get_mut().write_all(b"\r\n")?;
+        stream.get_mut().flush()?;
+    }
+    let mut reply = String::new();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        reply.push_str(&line);
+        // continued replies have a dash after the code
+        if line.as_bytes().get(3) != Some(&b'-') {
+            break;
+        }
+    }
+    match reply.lines().last().is_some_and(|line| line.starts_with(expected)) {
+        true => Ok(reply),
+        false => Err(io::Error::other(format!("unexpected SMTP reply: {}", reply.trim_end()))),
+    }
+}
+
+/// `Thu, 01 Jan 1970 00:00:00 +0000`
+fn rfc2822(time: SystemTime) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    let days = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 86400;
+    // `YYYY-MM-DDTHH:MM:SS.uuuuuuZ`
+    let timestamp = rfc3339(time);
+    let month = timestamp[5..7].parse::<usize>().unwrap_or(1);
+    format!(
+        "{}, {} {} {} {} +0000",
+        DAYS[(days % 7) as usize],
+        &timestamp[8..10],
+        MONTHS[month - 1],
+        &timestamp[..4],
+        &timestamp[11..19]
+    )
+}
+
+/// The message with its headers, its lines dot-stuffed and the final dot
+fn message(config: &Email, subject: &str, body: &str) -> String {
+    let now = SystemTime::now();
+    let domain = config.from.rsplit_once('@').map_or("localhost", |(_, domain)| domain.trim_end_matches('>'));
+    let mut message = format!(
+        "Date: {}\r\nMessage-ID: <{}.{:016x}@{}>\r\nFrom: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+        rfc2822(now),
+        now.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros(),
+        crate::trace::random(),
+        domain,
+        config.from,
+        config.to.join(", "),
+        subject.replace(['\r', '\n'], " "),
+    );
+    for line in body.lines() {
+        // a leading dot would end the message
+        if line.starts_with('.') {
+            message.push('.');
+        }
+        message.push_str(line);
+        message.push_str("\r\n");
+    }
+    message.push('.');
+    message
+}
+
+/// The commands after the greeting and `EHLO`
+fn transaction<S: Read + Write>(stream: &mut BufReader<S>, config: &Email, subject: &str, body: &str) -> io::Result<()> {
+    if let Some((username, password)) = &config.credentials {
+        let token = base64(format!("\0{}\0{}", username, password).as_bytes());
+        command(stream, Some(&format!("AUTH PLAIN {}", token)), "235")?;
+    }
+    command(stream, Some(&format!("MAIL FROM:<{}>", config.from)), "250")?;
+    for to in &config.to {
+        command(stream, Some(&format!("RCPT TO:<{}>", to)), "250")?;
+    }
+    command(stream, Some("DATA"), "354")?;
+    command(stream, Some(&message(config, subject, body)), "250")?;
+    let _ = command(stream, Some("QUIT"), "221");
+    Ok(())
+}
+
+fn send(config: &Email, subject: &str, body: &str) -> io::Result<()> {
+    let address = config
+        .server
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address to connect to"))?;
+    let stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut stream = BufReader::new(stream);
+    command(&mut stream, None, "220")?;
+    let features = command(&mut stream, Some("EHLO localhost"), "250")?;
+    if config.credentials.is_none() {
+        return transaction(&mut stream, config, subject, body);
+    }
+    // credentials never go over plain TCP
+    if !features.lines().any(|line| line.get(4..).is_some_and(|feature| feature.trim().eq_ignore_ascii_case("STARTTLS"))) {
+        return Err(io::Error::other("the SMTP server does not offer STARTTLS, refusing to send credentials"));
+    }
+    command(&mut stream, Some("STARTTLS"), "220")?;
+    let host = config.server.rsplit_once(':').map_or(config.server.as_str(), |(host, _)| host);
+    let host = rustls::pki_types::ServerName::try_from(host.trim_start_matches('[').trim_end_matches(']').to_string())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let tls = {
+        use rustls_platform_verifier::ConfigVerifierExt;
+        rustls::ClientConfig::with_platform_verifier().map_err(io::Error::other)?
+    };
+    let connection = rustls::ClientConnection::new(Arc::new(tls), host).map_err(io::Error::other)?;
+    let mut stream = BufReader::new(rustls::StreamOwned::new(connection, stream.into_inner()));
+    command(&mut stream, Some("EHLO localhost"), "250")?;
+    transaction(&mut stream, config, subject, body)
+}
+
+enum Message {
+    Email(String, String),
+    Flush(SyncSender<()>),
+}
+
+struct State {
+    recent: VecDeque<String>,
+    sent: Option<SystemTime>,
+    left_out: usize,
+}
+
+struct Inner {
+    config: Email,
+    state: Mutex<State>,
+    sender: SyncSender<Message>,
+}
+
+/// Keeps the recent records and queues the emails for the sending thread
+#[derive(Clone)]
+pub(super) struct EmailClient(Arc<Inner>);
+
+/// The clients emailing panics, the hook is installed once for all of them
+static NOTIFIERS: Mutex<Vec<Weak<Inner>>> = Mutex::new(Vec::new());
+static HOOK: Once = Once::new();
+
+fn send_loop(config: &Email, receiver: Receiver<Message>) {
+    for message in receiver {
+        match message {
+            Message::Email(subject, body) => {
+                let _ = send(config, &subject, &body);
+            }
+            Message::Flush(done) => {
+                let _ = done.send(());
+            }
+        }
+    }
+}
+
+impl EmailClient {
+    pub fn new(config: Email) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(64);
+        let sending = config.clone();
+        thread::Builder::new()
+            .name("errlog-email".to_string())
+            // events of the SMTP exchange must not come back to the logger
+            .spawn(move || tracing::dispatcher::with_default(&tracing::Dispatch::none(), || send_loop(&sending, receiver)))
+            .expect("failed to spawn the errlog email thread");
+        let client = EmailClient(Arc::new(Inner {
+            config,
+            state: Mutex::new(State { recent: VecDeque::new(), sent: None, left_out: 0 }),
+            sender,
+        }));
+        if client.0.config.panics {
+            let mut notifiers = NOTIFIERS.lock().unwrap_or_else(|err| err.into_inner());
+            notifiers.retain(|notifier| notifier.strong_count() > 0);
+            notifiers.push(Arc::downgrade(&client.0));
+            drop(notifiers);
+            HOOK.call_once(|| {
+                let previous = std::panic::take_hook();
+                std::panic::set_hook(Box::new(move |info| {
+                    // waiting for the sending thread from itself would never end
+                    let sending = thread::current().name() == Some("errlog-email");
+                    let notifiers: Vec<EmailClient> = NOTIFIERS
+                        .lock()
+                        .unwrap_or_else(|err| err.into_inner())
+                        .iter()
+                        .filter_map(|notifier| notifier.upgrade().map(EmailClient))
+                        .filter(|_| !sending)
+                        .collect();
+                    if !notifiers.is_empty() {
+                        let subject = format!("panic: {}", crate::panic_message(info.payload()));
+                        let location = info.location().map(|location| format!(" at {}", location)).unwrap_or_default();
+                        for notifier in notifiers {
+                            let body = notifier.body(&format!("{}{}", subject, location));
+                            let _ = notifier.0.sender.send(Message::Email(subject.clone(), body));
+                            // the process may be about to exit, wait for the email
+                            notifier.sync();
+                        }
+                    }
+                    previous(info);
+                }));
+            });
+        }
+        client
+    }
+
+    fn body(&self, tree: &str) -> String {
+        let state = self.0.state.lock().unwrap_or_else(|err| err.into_inner());
+        let mut body = tree.trim_end().to_string();
+        if state.left_out > 0 {
+            body.push_str(&format!("\n\n{} more alerts were throttled since the last email", state.left_out));
+        }
+        if !state.recent.is_empty() {
+            body.push_str("\n\nRecent records:\n");
+            for line in &state.recent {
+                body.push_str(line);
+                body.push('\n');
+            }
+        }
+        body
+    }
+
+    /// Remember the formatted `line` of `record`, and email the record when it is tagged
+    pub fn record(&self, record: &Record, line: &str) {
+        let config = &self.0.config;
+        let tagged = record.level == tracing::Level::ERROR
+            && config.tags.iter().any(|(name, value)| record.fields.iter().any(|field| field.0 == *name && field.1 == *value));
+        if tagged {
+            let throttled = {
+                let mut state = self.0.state.lock().unwrap_or_else(|err| err.into_inner());
+                let throttled = state.sent.is_some_and(|sent| {
+                    record.timestamp.duration_since(sent).unwrap_or_default() < config.throttle
+                });
+                if throttled {
+                    state.left_out += 1;
+                }
+                throttled
+            };
+            if !throttled {
+                let subject = record.message.clone();
+                let body = self.body(&Format::Full.render(record, &Layout { multiline_errors: true, ..Layout::default() }));
+                let mut state = self.0.state.lock().unwrap_or_else(|err| err.into_inner());
+                state.sent = Some(record.timestamp);
+                state.left_out = 0;
+                let _ = self.0.sender.try_send(Message::Email(subject, body));
+            }
+        }
+        let mut state = self.0.state.lock().unwrap_or_else(|err| err.into_inner());
+        if state.recent.len() >= config.context {
+            state.recent.pop_front();
+        }
+        if config.context > 0 {
+            state.recent.push_back(line.trim_end().to_string());
+        }
+    }
+
+    /// Wait until the queued emails were sent or failed
+    pub fn sync(&self) {
+        let (done, wait) = mpsc::sync_channel(1);
+        if self.0.sender.send(Message::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Adding fields to every record
+
+use std::fmt::Display;
+
+/// The fields of a record, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldMap(Vec<(String, String)>);
+
+impl FieldMap {
+    /// Set the field `name`, replacing its value when it exists
+    pub fn insert(&mut self, name: &str, value: impl Display) {
+        let value = value.to_string();
+        match self.0.iter_mut().find(|(field, _)| field == name) {
+            Some((_, old)) => *old = value,
+            None => self.0.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.iter().find(|(field, _)| field == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let index = self.0.iter().position(|(field, _)| field == name)?;
+        Some(self.0.remove(index).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub(super) fn from_vec(fields: Vec<(String, String)>) -> Self {
+        FieldMap(fields)
+    }
+
+    pub(super) fn into_vec(self) -> Vec<(String, String)> {
+        self.0
+    }
+}
+
+/// Adds or rewrites fields of every record, see [Builder::enricher](super::Builder::enricher)
+///
+/// Closures taking a `&mut FieldMap` are enrichers.
+pub trait Enricher: Send + Sync + 'static {
+    fn enrich(&self, fields: &mut FieldMap);
+}
+
+impl<F> Enricher for F
+where
+    F: Fn(&mut FieldMap) + Send + Sync + 'static,
+{
+    fn enrich(&self, fields: &mut FieldMap) {
+        self(fields)
+    }
+}
@@ -0,0 +1,93 @@
+//! Folded stacks of the spans for [Builder::flame](super::Builder::flame)
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context as LayerContext, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// The time spent in each stack of spans, written to `path` as `root;child;leaf <microseconds>`
+/// lines, clones share them
+#[derive(Debug, Clone)]
+pub(super) struct Flame {
+    path: PathBuf,
+    stacks: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+/// How long a span was entered, excluding the time of its children
+#[derive(Default)]
+struct Busy {
+    entered: Option<Instant>,
+    busy: Duration,
+    children: Duration,
+}
+
+impl Flame {
+    pub(super) fn new(path: PathBuf) -> Self {
+        Flame { path, stacks: Arc::default() }
+    }
+
+    /// Replace the file with the stacks sorted by name, which inferno expects
+    pub(super) fn write(&self) {
+        let stacks = self.stacks.lock().unwrap_or_else(|err| err.into_inner());
+        let mut lines: Vec<(&String, &u64)> = stacks.iter().collect();
+        lines.sort();
+        let Ok(file) = File::create(&self.path) else { return };
+        let mut file = BufWriter::new(file);
+        for (stack, micros) in lines {
+            let _ = writeln!(file, "{} {}", stack, micros);
+        }
+        let _ = file.flush();
+    }
+}
+
+impl<S> Layer<S> for Flame
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Busy::default());
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(busy) = extensions.get_mut::<Busy>() {
+            busy.entered = Some(Instant::now());
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut extensions = span.extensions_mut();
+        if let Some(busy) = extensions.get_mut::<Busy>() {
+            if let Some(entered) = busy.entered.take() {
+                busy.busy += entered.elapsed();
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(busy) = span.extensions_mut().remove::<Busy>() else { return };
+        if let Some(parent) = span.parent() {
+            if let Some(parent) = parent.extensions_mut().get_mut::<Busy>() {
+                parent.children += busy.busy;
+            }
+        }
+        let micros = busy.busy.saturating_sub(busy.children).as_micros() as u64;
+        if micros == 0 {
+            return;
+        }
+        let stack: Vec<&str> = span.scope().from_root().map(|span| span.name()).collect();
+        *self.stacks.lock().unwrap_or_else(|err| err.into_inner()).entry(stack.join(";")).or_default() += micros;
+    }
+}
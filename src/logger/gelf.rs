@@ -0,0 +1,158 @@
+//! GELF records and the transports shipping them to Graylog
+
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::UNIX_EPOCH;
+
+use tracing::Level;
+
+use super::{json_string, Record};
+
+/// The largest UDP datagram sent, as recommended by the GELF specification
+const CHUNK_SIZE: usize = 8192;
+const CHUNK_HEADER: usize = 12;
+const MAX_CHUNKS: usize = 128;
+
+fn syslog_level(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        _ => 7,
+    }
+}
+
+/// Append `record` as a GELF 1.1 JSON object from `host`, fields become additional `_` fields
+pub(super) fn render(out: &mut String, record: &Record, host: &str) {
+    let elapsed = record.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+    out.push_str("{\"version\":\"1.1\",\"host\":");
+    json_string(out, host);
+    out.push_str(",\"short_message\":");
+    json_string(out, &record.message);
+    if !record.errors.is_empty() {
+        let mut full = record.message.clone();
+        for cause in record.errors.iter().flat_map(|(_, chain)| chain) {
+            full.push_str("\ncaused by: ");
+            full.push_str(cause);
+        }
+        out.push_str(",\"full_message\":");
+        json_string(out, &full);
+    }
+    out.push_str(&format!(
+        ",\"timestamp\":{}.{:06},\"level\":{}",
+        elapsed.as_secs(),
+        elapsed.subsec_micros(),
+        syslog_level(record.level)
+    ));
+    out.push_str(",\"_target\":");
+    json_string(out, &record.target);
+    if let Some((file, line)) = record.location.as_ref().and_then(|location| location.rsplit_once(':')) {
+        out.push_str(",\"_file\":");
+        json_string(out, file);
+        out.push_str(",\"_line\":");
+        json_string(out, line);
+    }
+    if !record.spans.is_empty() {
+        out.push_str(",\"_spans\":");
+        json_string(out, &record.spans.join(":"));
+    }
+    for (name, value) in &record.fields {
+        // `_id` is reserved by GELF
+        let name = if name == "id" { "__id".to_string() } else { format!("_{}", name) };
+        out.push(',');
+        json_string(out, &name);
+        out.push(':');
+        json_string(out, value);
+    }
+    out.push('}');
+}
+
+enum Transport {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+}
+
+/// A writer sending [Format::Gelf](super::Format::Gelf) records to a Graylog input
+///
+/// Over UDP, records larger than one datagram are split into GELF chunks, up to 128 of them, and
+/// larger records are dropped. Over TCP, records are delimited by a null byte. Each line written
+/// is one record, so the writer can be [batched](super::Builder::batched).
+///
+///     use errlog::logger::{self, Format, GelfWriter};
+///
+///     # let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+///     # let address = server.local_addr().unwrap();
+///     let writer = GelfWriter::udp(address).unwrap();
+///     let _guard = logger::builder().format(Format::Gelf).writer(writer).set_default();
+///     errlog::logmsg!(WARN, "disk almost full");
+///     # #[cfg(not(feature = "silent"))]
+///     # {
+///     # let mut buf = [0; 8192];
+///     # let len = server.recv(&mut buf).unwrap();
+///     # assert!(String::from_utf8_lossy(&buf[..len]).contains("\"short_message\":\"disk almost full\",\"timestamp\""));
+///     # }
+pub struct GelfWriter {
+    transport: Transport,
+    pending: Vec<u8>,
+}
+
+impl GelfWriter {
+    /// Send records as UDP datagrams to `address`
+    pub fn udp(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.connect(address)?;
+        Ok(GelfWriter { transport: Transport::Udp(socket), pending: vec![] })
+    }
+
+    /// Send records over a TCP connection to `address`
+    pub fn tcp(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        Ok(GelfWriter { transport: Transport::Tcp(stream), pending: vec![] })
+    }
+
+    fn send(&mut self, record: &[u8]) -> io::Result<()> {
+        match &mut self.transport {
+            Transport::Tcp(stream) => {
+                stream.write_all(record)?;
+                stream.write_all(&[0])
+            }
+            Transport::Udp(socket) if record.len() <= CHUNK_SIZE => socket.send(record).map(|_| ()),
+            Transport::Udp(socket) => {
+                static MESSAGE_ID: AtomicU64 = AtomicU64::new(0);
+                let chunks: Vec<&[u8]> = record.chunks(CHUNK_SIZE - CHUNK_HEADER).collect();
+                if chunks.len() > MAX_CHUNKS {
+                    return Ok(());
+                }
+                let id = MESSAGE_ID.fetch_add(1, Ordering::Relaxed) ^ ((std::process::id() as u64) << 32);
+                for (index, chunk) in chunks.iter().enumerate() {
+                    let mut datagram = Vec::with_capacity(CHUNK_HEADER + chunk.len());
+                    datagram.extend_from_slice(&[0x1e, 0x0f]);
+                    datagram.extend_from_slice(&id.to_be_bytes());
+                    datagram.extend_from_slice(&[index as u8, chunks.len() as u8]);
+                    datagram.extend_from_slice(chunk);
+                    socket.send(&datagram)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Write for GelfWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        while let Some(end) = self.pending.iter().position(|byte| *byte == b'\n') {
+            let record: Vec<u8> = self.pending.drain(..=end).take(end).collect();
+            self.send(&record)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.transport {
+            Transport::Tcp(stream) => stream.flush(),
+            Transport::Udp(_) => Ok(()),
+        }
+    }
+}
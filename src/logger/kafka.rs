@@ -0,0 +1,259 @@
+//! Publishing JSON records to Kafka
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext};
+use rdkafka::{ClientConfig, ClientContext};
+
+/// One record to publish
+#[derive(Debug, Clone)]
+pub struct KafkaRecord {
+    /// The fingerprint of the event in hex, so repetitions of an event share a partition
+    pub key: String,
+    /// The event in the [Json](super::Format::Json) format, without the trailing newline
+    pub payload: String,
+}
+
+/// The client publishing batches, [RdKafkaProducer] by default, implement it over another Kafka
+/// client or to test the records
+pub trait KafkaProducer: Send + 'static {
+    /// Publish `records` to `topic`, the error means they were not delivered
+    fn produce(&mut self, topic: &str, records: &[KafkaRecord]) -> io::Result<()>;
+}
+
+/// Where and how to publish records to Kafka, see [Builder::kafka](super::Builder::kafka)
+///
+///     use errlog::logger::{self, Kafka};
+///
+///     let _guard = logger::builder().kafka(Kafka::connect("logs", "localhost:9092").unwrap()).set_default();
+///
+/// Another client can publish the records instead:
+///
+///     use std::io;
+///
+///     use errlog::logger::{self, Kafka, KafkaProducer, KafkaRecord};
+///
+///     struct Producer;
+///
+///     impl KafkaProducer for Producer {
+///         fn produce(&mut self, topic: &str, records: &[KafkaRecord]) -> io::Result<()> {
+///             // hand the records to the Kafka client
+///             Ok(())
+///         }
+///     }
+///
+///     let _guard = logger::builder().kafka(Kafka::new("logs", Producer)).set_default();
+///
+/// Records whose delivery failed are written to stderr instead.
+pub struct Kafka {
+    topic: String,
+    producer: Box<dyn KafkaProducer>,
+    capacity: usize,
+    batch: usize,
+    interval: Duration,
+}
+
+impl Kafka {
+    /// Publish to `topic` through `producer`
+    pub fn new(topic: &str, producer: impl KafkaProducer) -> Self {
+        Kafka {
+            topic: topic.to_string(),
+            producer: Box::new(producer),
+            capacity: 8192,
+            batch: 512,
+            interval: Duration::from_millis(500),
+        }
+    }
+
+    /// Publish to `topic` of the comma separated `brokers`, like `localhost:9092`, through an
+    /// [RdKafkaProducer]
+    pub fn connect(topic: &str, brokers: &str) -> io::Result<Self> {
+        Ok(Kafka::new(topic, RdKafkaProducer::new(brokers)?))
+    }
+
+    /// Queue at most `capacity` records, the default is 8192, further records are dropped
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Publish once `size` records are queued, the default is 512
+    pub fn batch(mut self, size: usize) -> Self {
+        self.batch = size.max(1);
+        self
+    }
+
+    /// Publish at least every `interval`, the default is half a second
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+}
+
+/// Counts the records whose delivery failed
+#[derive(Default)]
+struct Deliveries {
+    failed: AtomicUsize,
+}
+
+impl ClientContext for Deliveries {}
+
+impl ProducerContext for Deliveries {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: ()) {
+        if result.is_err() {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A [KafkaProducer] over the `BaseProducer` of rdkafka, a batch fails unless all its records
+/// are acknowledged by the brokers within the [timeout](RdKafkaProducer::timeout)
+pub struct RdKafkaProducer {
+    producer: BaseProducer<Deliveries>,
+    timeout: Duration,
+}
+
+impl RdKafkaProducer {
+    /// Publish to the comma separated `brokers`, like `localhost:9092`
+    pub fn new(brokers: &str) -> io::Result<Self> {
+        RdKafkaProducer::with_settings(brokers, &[])
+    }
+
+    /// Publish to `brokers` with further librdkafka `settings`, such as
+    /// `("security.protocol", "ssl")`
+    pub fn with_settings(brokers: &str, settings: &[(&str, &str)]) -> io::Result<Self> {
+        let mut config = ClientConfig::new();
+        config.set("bootstrap.servers", brokers);
+        for (key, value) in settings {
+            config.set(*key, *value);
+        }
+        let producer = config.create_with_context(Deliveries::default()).map_err(io::Error::other)?;
+        Ok(RdKafkaProducer { producer, timeout: Duration::from_secs(10) })
+    }
+
+    /// How long to wait for a batch to be delivered, the default is 10 seconds
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl KafkaProducer for RdKafkaProducer {
+    fn produce(&mut self, topic: &str, records: &[KafkaRecord]) -> io::Result<()> {
+        let deadline = Instant::now() + self.timeout;
+        let failed = &self.producer.context().failed;
+        failed.store(0, Ordering::Relaxed);
+        for record in records {
+            let mut message = BaseRecord::to(topic).key(&record.key).payload(&record.payload);
+            // the local queue of librdkafka empties as the brokers acknowledge records
+            while let Err((err, back)) = self.producer.send(message) {
+                match err {
+                    KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull) if Instant::now() < deadline => {
+                        self.producer.poll(Duration::from_millis(100));
+                        message = back;
+                    }
+                    err => return Err(io::Error::other(err)),
+                }
+            }
+        }
+        self.producer.flush(deadline.saturating_duration_since(Instant::now())).map_err(io::Error::other)?;
+        match failed.swap(0, Ordering::Relaxed) {
+            0 => Ok(()),
+            failed => Err(io::Error::other(format!("{} records were not delivered", failed))),
+        }
+    }
+}
+
+enum Message {
+    Record(KafkaRecord),
+    Flush(SyncSender<()>),
+}
+
+/// The queue of the thread publishing to Kafka
+#[derive(Clone)]
+pub(super) struct KafkaClient {
+    sender: SyncSender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl KafkaClient {
+    pub fn new(config: Kafka) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(config.capacity.max(1));
+        thread::Builder::new()
+            .name("errlog-kafka".to_string())
+            // events of the Kafka client must not come back to the logger
+            .spawn(move || tracing::dispatcher::with_default(&tracing::Dispatch::none(), || publish_loop(config, receiver)))
+            .expect("failed to spawn the errlog kafka thread");
+        KafkaClient { sender, dropped: Arc::default() }
+    }
+
+    /// Queue the JSON `line` of an event, dropping it when the queue is full
+    pub fn send(&self, fingerprint: u64, line: &str) {
+        let record = KafkaRecord { key: format!("{:016x}", fingerprint), payload: line.trim_end().to_string() };
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(Message::Record(record)) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Wait until everything queued so far was published or written to stderr
+    pub fn sync(&self) {
+        let (done, wait) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+fn publish_loop(mut config: Kafka, receiver: Receiver<Message>) {
+    let mut batch = vec![];
+    let mut deadline = Instant::now() + config.interval;
+    loop {
+        let message = receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        let flush = match message {
+            Ok(Message::Record(record)) => {
+                batch.push(record);
+                batch.len() >= config.batch
+            }
+            Ok(Message::Flush(done)) => {
+                publish(&mut config, &mut batch);
+                let _ = done.send(());
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => {
+                publish(&mut config, &mut batch);
+                return;
+            }
+        };
+        if flush {
+            publish(&mut config, &mut batch);
+            deadline = Instant::now() + config.interval;
+        }
+    }
+}
+
+fn publish(config: &mut Kafka, batch: &mut Vec<KafkaRecord>) {
+    if batch.is_empty() {
+        return;
+    }
+    if let Err(err) = config.producer.produce(&config.topic, batch) {
+        let mut stderr = io::stderr().lock();
+        let _ = writeln!(stderr, "errlog: failed to publish {} records to kafka: {}", batch.len(), err);
+        for record in batch.iter() {
+            let _ = writeln!(stderr, "{}", record.payload);
+        }
+    }
+    batch.clear();
+}
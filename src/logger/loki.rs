@@ -0,0 +1,237 @@
+//! Pushing formatted records to Grafana Loki
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use super::{json_string, Record};
+
+/// The most entries pushed in one request
+const MAX_BATCH: usize = 1024;
+
+/// Where and how to push records to Loki, see [Builder::loki](super::Builder::loki)
+///
+/// Every stream is labelled with `level` and `target`, the static labels and the fields listed
+/// with [label_field](Loki::label_field) when the record has them. Keep labels few and of low
+/// cardinality, as Loki indexes them.
+#[derive(Debug, Clone)]
+pub struct Loki {
+    url: String,
+    labels: Vec<(String, String)>,
+    label_fields: Vec<String>,
+    capacity: usize,
+    interval: Duration,
+    retries: u32,
+}
+
+impl Loki {
+    /// Push to the Loki server at `url`, like `http://localhost:3100`
+    pub fn new(url: &str) -> Self {
+        Loki {
+            url: format!("{}/loki/api/v1/push", url.trim_end_matches('/')),
+            labels: vec![],
+            label_fields: vec![],
+            capacity: 8192,
+            interval: Duration::from_secs(1),
+            retries: 5,
+        }
+    }
+
+    /// Add a label to every stream
+    pub fn label(mut self, name: &str, value: &str) -> Self {
+        self.labels.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Use the field `name` of records as a label
+    pub fn label_field(mut self, name: &str) -> Self {
+        self.label_fields.push(name.to_string());
+        self
+    }
+
+    /// Queue at most `capacity` records, the default is 8192, further records are dropped
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Push at least every `interval`, the default is one second
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Retry a failed push `retries` times with exponential backoff before dropping its records,
+    /// the default is 5
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+}
+
+struct Entry {
+    labels: Vec<(String, String)>,
+    nanos: u128,
+    line: String,
+}
+
+enum Message {
+    Entry(Entry),
+    Flush(SyncSender<()>),
+}
+
+/// The queue of the thread pushing to Loki
+#[derive(Clone)]
+pub(super) struct LokiClient {
+    config: Arc<Loki>,
+    sender: SyncSender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl LokiClient {
+    pub fn new(config: Loki) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(config.capacity.max(1));
+        let config = Arc::new(config);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (pusher, counter) = (config.clone(), dropped.clone());
+        thread::Builder::new()
+            .name("errlog-loki".to_string())
+            // events of the HTTP client must not come back to the logger
+            .spawn(move || {
+                tracing::dispatcher::with_default(&tracing::Dispatch::none(), || push_loop(&pusher, receiver, &counter))
+            })
+            .expect("failed to spawn the errlog loki thread");
+        LokiClient { config, sender, dropped }
+    }
+
+    /// Queue the formatted `line` of `record`, dropping it when the queue is full
+    pub fn send(&self, record: &Record, line: &str) {
+        let mut labels = vec![
+            ("level".to_string(), record.level.as_str().to_lowercase()),
+            ("target".to_string(), record.target.clone()),
+        ];
+        labels.extend(self.config.labels.iter().cloned());
+        for name in &self.config.label_fields {
+            if let Some((_, value)) = record.fields.iter().find(|(field, _)| field == name) {
+                labels.push((name.clone(), value.clone()));
+            }
+        }
+        labels.sort();
+        let nanos = record.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+        let entry = Entry { labels, nanos, line: line.trim_end().to_string() };
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(Message::Entry(entry)) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Wait until everything queued so far was pushed or dropped
+    pub fn sync(&self) {
+        let (done, wait) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+fn push_loop(config: &Loki, receiver: Receiver<Message>, dropped: &AtomicU64) {
+    let client = reqwest::blocking::Client::new();
+    let mut batch = vec![];
+    let mut deadline = Instant::now() + config.interval;
+    loop {
+        let message = receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()));
+        let flush = match message {
+            Ok(Message::Entry(entry)) => {
+                batch.push(entry);
+                batch.len() >= MAX_BATCH
+            }
+            Ok(Message::Flush(done)) => {
+                push(&client, config, &mut batch, dropped);
+                let _ = done.send(());
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => {
+                push(&client, config, &mut batch, dropped);
+                return;
+            }
+        };
+        if flush {
+            push(&client, config, &mut batch, dropped);
+            deadline = Instant::now() + config.interval;
+        }
+    }
+}
+
+/// The body of a push request, entries of the same labels share a stream
+fn body(batch: &[Entry]) -> String {
+    let mut streams: Vec<Vec<&Entry>> = vec![];
+    for entry in batch {
+        match streams.iter_mut().find(|entries| entries[0].labels == entry.labels) {
+            Some(entries) => entries.push(entry),
+            None => streams.push(vec![entry]),
+        }
+    }
+    let mut out = String::from("{\"streams\":[");
+    for (index, entries) in streams.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        out.push_str("{\"stream\":{");
+        for (index, (name, value)) in entries[0].labels.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            json_string(&mut out, name);
+            out.push(':');
+            json_string(&mut out, value);
+        }
+        out.push_str("},\"values\":[");
+        for (index, entry) in entries.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            out.push_str(&format!("[\"{}\",", entry.nanos));
+            json_string(&mut out, &entry.line);
+            out.push(']');
+        }
+        out.push_str("]}");
+    }
+    out.push_str("]}");
+    out
+}
+
+fn push(client: &reqwest::blocking::Client, config: &Loki, batch: &mut Vec<Entry>, dropped: &AtomicU64) {
+    if batch.is_empty() {
+        return;
+    }
+    let body = body(batch);
+    let mut backoff = Duration::from_millis(100);
+    for attempt in 0..=config.retries {
+        if attempt > 0 {
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(Duration::from_secs(10));
+        }
+        let response = client
+            .post(&config.url)
+            .header("Content-Type", "application/json")
+            .body(body.clone())
+            .send();
+        match response {
+            Ok(response) if response.status().is_success() => {
+                batch.clear();
+                return;
+            }
+            // the request itself is wrong, retrying would not help
+            Ok(response) if response.status().is_client_error() && response.status().as_u16() != 429 => break,
+            _ => {}
+        }
+    }
+    dropped.fetch_add(batch.len() as u64, Ordering::Relaxed);
+    batch.clear();
+}
@@ -0,0 +1,287 @@
+//! Publishing warnings and errors to an MQTT broker
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The delivery guarantee of published records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MqttQos {
+    /// Fire and forget
+    #[default]
+    AtMostOnce,
+    /// Wait for the broker to acknowledge each record, publishing it again after a failure
+    AtLeastOnce,
+}
+
+/// Where and how to publish records to MQTT, see [Builder::mqtt](super::Builder::mqtt)
+///
+/// This is a small MQTT 3.1.1 client over plain TCP. Records are buffered while the broker is
+/// unreachable, up to the [capacity](Mqtt::capacity), and the oldest are dropped beyond it.
+#[derive(Debug, Clone)]
+pub struct Mqtt {
+    address: String,
+    topic: String,
+    client_id: String,
+    credentials: Option<(String, String)>,
+    qos: MqttQos,
+    capacity: usize,
+}
+
+impl Mqtt {
+    /// Publish to `topic` on the broker at `address`, like `broker.local:1883`
+    pub fn new(address: &str, topic: &str) -> Self {
+        Mqtt {
+            address: address.to_string(),
+            topic: topic.to_string(),
+            client_id: format!("errlog-{}", std::process::id()),
+            credentials: None,
+            qos: MqttQos::default(),
+            capacity: 1024,
+        }
+    }
+
+    /// The client identifier, `errlog-<pid>` by default
+    pub fn client_id(mut self, client_id: &str) -> Self {
+        self.client_id = client_id.to_string();
+        self
+    }
+
+    pub fn credentials(mut self, username: &str, password: &str) -> Self {
+        self.credentials = Some((username.to_string(), password.to_string()));
+        self
+    }
+
+    pub fn qos(mut self, qos: MqttQos) -> Self {
+        self.qos = qos;
+        self
+    }
+
+    /// Buffer at most `capacity` records, the default is 1024
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+}
+
+/// Append the variable length encoding of `len`
+fn push_len(packet: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if len == 0 {
+            return;
+        }
+    }
+}
+
+fn push_str(packet: &mut Vec<u8>, text: &[u8]) {
+    packet.extend_from_slice(&(text.len() as u16).to_be_bytes());
+    packet.extend_from_slice(text);
+}
+
+fn packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    push_len(&mut packet, body.len());
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn connect(config: &Mqtt) -> io::Result<TcpStream> {
+    let address = config
+        .address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address to connect to"))?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    // protocol name and level, clean session, no keep alive
+    let mut body = vec![0, 4, b'M', b'Q', b'T', b'T', 4, 0x02, 0, 0];
+    push_str(&mut body, config.client_id.as_bytes());
+    if let Some((username, password)) = &config.credentials {
+        body[7] |= 0xc0;
+        push_str(&mut body, username.as_bytes());
+        push_str(&mut body, password.as_bytes());
+    }
+    stream.write_all(&packet(0x10, &body))?;
+    let mut connack = [0; 4];
+    stream.read_exact(&mut connack)?;
+    if connack[0] != 0x20 || connack[3] != 0 {
+        return Err(io::Error::other(format!("the broker refused the connection with code {}", connack[3])));
+    }
+    Ok(stream)
+}
+
+/// `dup` marks a record published again after a failure, with the same `id`
+fn publish(stream: &mut TcpStream, config: &Mqtt, id: u16, dup: bool, payload: &[u8]) -> io::Result<()> {
+    let mut body = vec![];
+    push_str(&mut body, config.topic.as_bytes());
+    if config.qos == MqttQos::AtMostOnce {
+        body.extend_from_slice(payload);
+        return stream.write_all(&packet(0x30, &body));
+    }
+    body.extend_from_slice(&id.to_be_bytes());
+    body.extend_from_slice(payload);
+    stream.write_all(&packet(if dup { 0x3a } else { 0x32 }, &body))?;
+    let mut puback = [0; 4];
+    stream.read_exact(&mut puback)?;
+    if puback[0] != 0x40 || puback[2..] != id.to_be_bytes() {
+        return Err(io::Error::other("unexpected acknowledgement from the broker"));
+    }
+    Ok(())
+}
+
+enum Message {
+    Record(Vec<u8>),
+    Flush(SyncSender<()>),
+}
+
+/// The queue of the thread publishing to MQTT
+#[derive(Clone)]
+pub(super) struct MqttClient {
+    sender: SyncSender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl MqttClient {
+    pub fn new(config: Mqtt) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(config.capacity);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let counter = dropped.clone();
+        thread::Builder::new()
+            .name("errlog-mqtt".to_string())
+            .spawn(move || publish_loop(config, receiver, &counter))
+            .expect("failed to spawn the errlog mqtt thread");
+        MqttClient { sender, dropped }
+    }
+
+    /// Queue the JSON `line` of an event, dropping it when the queue is full
+    pub fn send(&self, line: &str) {
+        let payload = line.trim_end().as_bytes().to_vec();
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(Message::Record(payload)) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Wait until the thread made a last attempt to publish everything queued so far, whatever
+    /// backoff, the records still failing are dropped
+    pub fn sync(&self) {
+        let (done, wait) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+struct Publisher {
+    config: Mqtt,
+    buffer: VecDeque<Vec<u8>>,
+    stream: Option<TcpStream>,
+    retry_at: Instant,
+    backoff: Duration,
+    id: u16,
+    /// Whether publishing the first buffered record failed already
+    retrying: bool,
+}
+
+impl Publisher {
+    /// Publish the buffered records until one fails, once the backoff is over
+    fn drain(&mut self) {
+        if Instant::now() >= self.retry_at {
+            self.attempt();
+        }
+    }
+
+    /// Publish the buffered records until one fails, whatever the backoff
+    fn attempt(&mut self) {
+        while let Some(payload) = self.buffer.front() {
+            let stream = match &mut self.stream {
+                Some(stream) => stream,
+                None => match connect(&self.config) {
+                    Ok(stream) => self.stream.insert(stream),
+                    Err(_) => return self.failed(),
+                },
+            };
+            if !self.retrying {
+                self.id = self.id.wrapping_add(1).max(1);
+            }
+            if publish(stream, &self.config, self.id, self.retrying, payload).is_err() {
+                self.stream = None;
+                self.retrying = self.config.qos == MqttQos::AtLeastOnce;
+                return self.failed();
+            }
+            self.buffer.pop_front();
+            self.retrying = false;
+            self.backoff = Duration::from_millis(100);
+        }
+    }
+
+    /// Make a last attempt, counting the records left as dropped
+    fn finish(&mut self, dropped: &AtomicU64) {
+        self.attempt();
+        dropped.fetch_add(self.buffer.len() as u64, Ordering::Relaxed);
+        self.buffer.clear();
+        self.retrying = false;
+    }
+
+    fn failed(&mut self) {
+        self.retry_at = Instant::now() + self.backoff;
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+fn publish_loop(config: Mqtt, receiver: Receiver<Message>, dropped: &AtomicU64) {
+    let mut publisher = Publisher {
+        config,
+        buffer: VecDeque::new(),
+        stream: None,
+        retry_at: Instant::now(),
+        backoff: Duration::from_millis(100),
+        id: 0,
+        retrying: false,
+    };
+    loop {
+        let wait = match publisher.buffer.is_empty() {
+            true => Duration::from_secs(3600),
+            false => publisher.retry_at.saturating_duration_since(Instant::now()),
+        };
+        match receiver.recv_timeout(wait) {
+            Ok(Message::Record(payload)) => {
+                if publisher.buffer.len() >= publisher.config.capacity {
+                    publisher.buffer.pop_front();
+                    publisher.retrying = false;
+                    dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                publisher.buffer.push_back(payload);
+            }
+            Ok(Message::Flush(done)) => {
+                publisher.finish(dropped);
+                let _ = done.send(());
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                publisher.finish(dropped);
+                return;
+            }
+        }
+        publisher.drain();
+    }
+}
@@ -0,0 +1,210 @@
+//! Writers shipping records over the network
+
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::{UnixDatagram, UnixStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How long connecting may block the logging thread
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+enum Transport {
+    Tcp(Option<TcpStream>),
+    Udp(Option<UdpSocket>),
+    #[cfg(unix)]
+    UnixStream(Option<UnixStream>),
+    #[cfg(unix)]
+    UnixDatagram(Option<UnixDatagram>),
+}
+
+/// A writer sending records to a TCP, UDP or unix socket listener, such as syslog-ng, Vector or
+/// Fluent Bit
+///
+///     use errlog::logger::{self, Format, NetWriter};
+///
+///     # let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+///     # let address = listener.local_addr().unwrap().to_string();
+///     let _guard = logger::builder()
+///         .format(Format::Logfmt)
+///         .writer(NetWriter::tcp(&address))
+///         .set_default();
+///     errlog::logmsg!(WARN, "disk almost full");
+///     # #[cfg(not(feature = "silent"))]
+///     # {
+///     # use std::io::BufRead;
+///     # let (stream, _) = listener.accept().unwrap();
+///     # let line = std::io::BufReader::new(stream).lines().next().unwrap().unwrap();
+///     # assert!(line.contains("msg=\"disk almost full\""));
+///     # }
+///
+/// Over TCP and unix streams records are newline delimited and a slow listener blocks the logging
+/// thread, use a [batched](super::Builder::batched) logger to avoid it. When the connection fails,
+/// the writer reconnects on the next record, and after a failed attempt it drops records for a
+/// backoff growing up to 30 seconds, which the [guard](super::Guard::dropped) counts. Over UDP and
+/// unix datagrams each record is one datagram, without the newline.
+///
+/// Records spanning several lines, such as the ones of [multiline_errors](super::Builder::multiline_errors),
+/// have their line breaks escaped as `\n` by the logger writing to this writer, so that each
+/// record stays one line or one datagram.
+pub struct NetWriter {
+    address: String,
+    transport: Transport,
+    retry_at: Option<Instant>,
+    backoff: Duration,
+    dropped: Arc<AtomicU64>,
+    pending: Vec<u8>,
+}
+
+impl NetWriter {
+    /// Send records over a TCP connection to `address`, like `localhost:5140`
+    pub fn tcp(address: &str) -> Self {
+        NetWriter::new(address, Transport::Tcp(None))
+    }
+
+    /// Send records as UDP datagrams to `address`
+    pub fn udp(address: &str) -> Self {
+        NetWriter::new(address, Transport::Udp(None))
+    }
+
+    /// Send records over a unix stream socket at `path`, such as the one of a sidecar collector
+    #[cfg(unix)]
+    pub fn unix_stream(path: &str) -> Self {
+        NetWriter::new(path, Transport::UnixStream(None))
+    }
+
+    /// Send records as datagrams to the unix socket at `path`, like `/dev/log`
+    #[cfg(unix)]
+    pub fn unix_datagram(path: &str) -> Self {
+        NetWriter::new(path, Transport::UnixDatagram(None))
+    }
+
+    fn new(address: &str, transport: Transport) -> Self {
+        NetWriter {
+            address: address.to_string(),
+            transport,
+            retry_at: None,
+            backoff: Duration::from_millis(100),
+            dropped: Arc::default(),
+            pending: vec![],
+        }
+    }
+
+    /// How many records were dropped while the listener was unreachable
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// The counter of [dropped](NetWriter::dropped), shared with the guard
+    pub(super) fn counter(&self) -> Arc<AtomicU64> {
+        self.dropped.clone()
+    }
+
+    fn connect(&mut self) -> io::Result<()> {
+        match &mut self.transport {
+            #[cfg(unix)]
+            Transport::UnixStream(stream @ None) => {
+                *stream = Some(UnixStream::connect(&self.address)?);
+                return Ok(());
+            }
+            #[cfg(unix)]
+            Transport::UnixDatagram(socket @ None) => {
+                let unbound = UnixDatagram::unbound()?;
+                unbound.connect(&self.address)?;
+                *socket = Some(unbound);
+                return Ok(());
+            }
+            _ => {}
+        }
+        let addresses: Vec<_> = self.address.to_socket_addrs()?.collect();
+        match &mut self.transport {
+            Transport::Tcp(stream @ None) => {
+                let mut last = io::Error::new(io::ErrorKind::NotFound, "no address to connect to");
+                for address in &addresses {
+                    match TcpStream::connect_timeout(address, CONNECT_TIMEOUT) {
+                        Ok(connected) => {
+                            *stream = Some(connected);
+                            return Ok(());
+                        }
+                        Err(err) => last = err,
+                    }
+                }
+                Err(last)
+            }
+            Transport::Udp(socket @ None) => {
+                let bound = UdpSocket::bind(("0.0.0.0", 0))?;
+                bound.connect(&addresses[..])?;
+                *socket = Some(bound);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn send(&mut self, record: &[u8]) -> io::Result<()> {
+        match &mut self.transport {
+            Transport::Tcp(Some(stream)) => stream.write_all(record),
+            Transport::Udp(Some(socket)) => socket.send(record.strip_suffix(b"\n").unwrap_or(record)).map(|_| ()),
+            #[cfg(unix)]
+            Transport::UnixStream(Some(stream)) => stream.write_all(record),
+            #[cfg(unix)]
+            Transport::UnixDatagram(Some(socket)) => {
+                socket.send(record.strip_suffix(b"\n").unwrap_or(record)).map(|_| ())
+            }
+            _ => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+
+    fn disconnect(&mut self) {
+        match &mut self.transport {
+            Transport::Tcp(stream) => *stream = None,
+            Transport::Udp(socket) => *socket = None,
+            #[cfg(unix)]
+            Transport::UnixStream(stream) => *stream = None,
+            #[cfg(unix)]
+            Transport::UnixDatagram(socket) => *socket = None,
+        }
+    }
+
+    /// Write one record, reconnecting once when the connection failed
+    fn write_record(&mut self, record: &[u8]) {
+        if self.retry_at.is_some_and(|at| Instant::now() < at) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        for _ in 0..2 {
+            if self.connect().and_then(|_| self.send(record)).is_ok() {
+                self.retry_at = None;
+                self.backoff = Duration::from_millis(100);
+                return;
+            }
+            self.disconnect();
+        }
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        self.retry_at = Some(Instant::now() + self.backoff);
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+impl Write for NetWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        while let Some(end) = self.pending.iter().position(|byte| *byte == b'\n') {
+            let record: Vec<u8> = self.pending.drain(..=end).collect();
+            self.write_record(&record);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.transport {
+            Transport::Tcp(Some(stream)) => stream.flush(),
+            #[cfg(unix)]
+            Transport::UnixStream(Some(stream)) => stream.flush(),
+            _ => Ok(()),
+        }
+    }
+}
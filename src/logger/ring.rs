@@ -0,0 +1,185 @@
+//! A log file of fixed size written in circles
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"ERRLOGR1";
+/// The magic, the capacity, the cursor and whether the data wrapped, as little endian `u64`s
+const HEADER: u64 = 32;
+/// Written after the last record, the data holds no other one once it wrapped
+const END: u8 = 0;
+
+/// A writer keeping the last records in a preallocated file used as a ring, for devices with
+/// little flash
+///
+///     use errlog::logger::{self, RingWriter};
+///
+///     let path = std::env::temp_dir().join(format!("errlog-{}.ring", std::process::id()));
+///     # let _ = std::fs::remove_file(&path);
+///     let guard = logger::builder().writer(RingWriter::open(&path, 4096).unwrap()).set_default();
+///     for attempt in 0..200 {
+///         errlog::logmsg!(WARN, attempt, "sensor timed out");
+///     }
+///     drop(guard);
+///
+///     // after a reboot
+///     let _guard = logger::builder().writer(RingWriter::open(&path, 4096).unwrap()).set_default();
+///     errlog::logmsg!(INFO, "sensor back");
+///
+///     assert_eq!(std::fs::metadata(&path).unwrap().len(), 32 + 4096);
+///     let log = String::from_utf8(RingWriter::linearize(&path).unwrap()).unwrap();
+///     # std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(32 + 100).unwrap();
+///     # assert_eq!(RingWriter::linearize(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+///     # std::fs::remove_file(&path).unwrap();
+///     # #[cfg(not(feature = "silent"))]
+///     # {
+///     assert!(log.len() <= 4096);
+///     assert!(log.lines().next().unwrap().contains("sensor timed out"));
+///     assert!(log.contains("attempt=199\n"));
+///     assert!(log.ends_with("sensor back\n"));
+///     # }
+///
+/// The file starts with a 32 bytes header, followed by the data, and never grows. It is filled
+/// with zeros when created, and each record is followed by a zero byte marking the end. The
+/// header is only written when flushing and dropping the writer, so that the same block is not
+/// rewritten for each record, and the end is found again by scanning from the cursor of the
+/// header. Opening a file written with the same size resumes at its end, otherwise the file is
+/// reset. Records must not contain zero bytes, and those longer than the size keep their end.
+pub struct RingWriter {
+    file: File,
+    capacity: u64,
+    cursor: u64,
+    wrapped: bool,
+}
+
+fn read_header(file: &mut File) -> io::Result<Option<(u64, u64, bool)>> {
+    let mut header = [0; HEADER as usize];
+    file.seek(SeekFrom::Start(0))?;
+    if file.read_exact(&mut header).is_err() || &header[..8] != MAGIC {
+        return Ok(None);
+    }
+    let number = |index: usize| u64::from_le_bytes(header[index * 8..index * 8 + 8].try_into().unwrap());
+    let (capacity, cursor, wrapped) = (number(1), number(2), number(3) != 0);
+    Ok((cursor < capacity).then_some((capacity, cursor, wrapped)))
+}
+
+/// The data and the position of the end marker at or after `cursor`, and whether the data
+/// wrapped, from the header written last
+fn read_data(file: &mut File, capacity: u64, cursor: u64, wrapped: bool) -> io::Result<(Vec<u8>, u64, bool)> {
+    if HEADER.checked_add(capacity) != Some(file.metadata()?.len()) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "the size of the ring log file does not match its header"));
+    }
+    let mut data = vec![0; capacity as usize];
+    file.seek(SeekFrom::Start(HEADER))?;
+    file.read_exact(&mut data)?;
+    let cursor = cursor as usize;
+    let end = match data[cursor..].iter().position(|byte| *byte == END) {
+        Some(offset) => cursor + offset,
+        None => data[..cursor].iter().position(|byte| *byte == END).unwrap_or(cursor),
+    };
+    // before wrapping, only zeros follow the end
+    let wrapped = wrapped || end < cursor || data[end..].iter().any(|byte| *byte != END);
+    Ok((data, end as u64, wrapped))
+}
+
+impl RingWriter {
+    /// Open or create the ring at `path` holding `size` bytes of records
+    pub fn open(path: impl AsRef<Path>, size: u64) -> io::Result<Self> {
+        if size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "the size of a ring must not be zero"));
+        }
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let resumed = read_header(&mut file)?
+            .filter(|(capacity, _, _)| *capacity == size && file.metadata().is_ok_and(|metadata| metadata.len() == HEADER + size));
+        let mut ring = RingWriter { file, capacity: size, cursor: 0, wrapped: false };
+        match resumed {
+            Some((_, cursor, wrapped)) => {
+                let (_, cursor, wrapped) = read_data(&mut ring.file, size, cursor, wrapped)?;
+                ring.cursor = cursor;
+                ring.wrapped = wrapped;
+            }
+            None => {
+                ring.file.set_len(0)?;
+                ring.write_header()?;
+                // zeros rather than a sparse file, so that the blocks are allocated up front
+                let zeros = vec![0; size.min(64 * 1024) as usize];
+                let mut left = size;
+                while left > 0 {
+                    let len = left.min(zeros.len() as u64);
+                    ring.file.write_all(&zeros[..len as usize])?;
+                    left -= len;
+                }
+                ring.file.sync_all()?;
+            }
+        }
+        Ok(ring)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let mut header = Vec::with_capacity(HEADER as usize);
+        header.extend_from_slice(MAGIC);
+        for number in [self.capacity, self.cursor, self.wrapped as u64] {
+            header.extend_from_slice(&number.to_le_bytes());
+        }
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)
+    }
+
+    /// The records of the ring at `path` from the oldest, without the partly overwritten first one
+    pub fn linearize(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+        let mut file = File::open(path)?;
+        let (capacity, cursor, wrapped) = read_header(&mut file)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a ring log file"))?;
+        let (mut data, cursor, wrapped) = read_data(&mut file, capacity, cursor, wrapped)?;
+        if !wrapped {
+            data.truncate(cursor as usize);
+            return Ok(data);
+        }
+        // the oldest byte follows the end marker
+        data.rotate_left(cursor as usize + 1);
+        data.pop();
+        let start = data.iter().position(|byte| *byte == b'\n').map_or(data.len(), |index| index + 1);
+        Ok(data.split_off(start))
+    }
+}
+
+impl Write for RingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // one byte is left for the end marker
+        let room = self.capacity as usize - 1;
+        let skipped = buf.len().saturating_sub(room);
+        if skipped > 0 {
+            self.cursor = (self.cursor + skipped as u64) % self.capacity;
+            self.wrapped = true;
+        }
+        let mut rest = buf[skipped..].to_vec();
+        rest.push(END);
+        let mut rest = &rest[..];
+        while !rest.is_empty() {
+            let len = rest.len().min((self.capacity - self.cursor) as usize);
+            self.file.seek(SeekFrom::Start(HEADER + self.cursor))?;
+            self.file.write_all(&rest[..len])?;
+            rest = &rest[len..];
+            self.cursor += len as u64;
+            if self.cursor == self.capacity {
+                self.cursor = 0;
+                self.wrapped = true;
+            }
+        }
+        // the marker is overwritten by the next record
+        self.cursor = (self.cursor + self.capacity - 1) % self.capacity;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_header()?;
+        self.file.sync_data()
+    }
+}
+
+impl Drop for RingWriter {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
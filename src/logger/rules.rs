@@ -0,0 +1,165 @@
+//! Rules rewriting or dropping records according to what was logged before
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use tracing::{Event, Level};
+
+/// Identify repetitions of an event by its callsite and its message
+pub(crate) fn fingerprint(event: &Event<'_>, message: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    event.metadata().callsite().hash(&mut hasher);
+    message.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn within(now: SystemTime, then: SystemTime, window: Duration) -> bool {
+    now.duration_since(then).unwrap_or_default() < window
+}
+
+#[derive(Default)]
+struct Seen {
+    times: HashMap<u64, VecDeque<SystemTime>>,
+    /// When the events not seen within the window were last forgotten
+    swept: Option<SystemTime>,
+}
+
+/// Escalate a `WARN` to `ERROR` once it repeats more than `times` within `window`
+pub(crate) struct Escalation {
+    times: usize,
+    window: Duration,
+    seen: Mutex<Seen>,
+}
+
+impl Escalation {
+    pub fn new(times: usize, window: Duration) -> Self {
+        Escalation { times, window, seen: Mutex::default() }
+    }
+
+    /// The repeat count when the occurrence must be escalated, the count starts over afterwards
+    pub fn check(&self, level: Level, fingerprint: u64, now: SystemTime) -> Option<usize> {
+        if level != Level::WARN {
+            return None;
+        }
+        let mut seen = self.seen.lock().unwrap_or_else(|err| err.into_inner());
+        // once per window, so that the other events are not scanned for each warning
+        if seen.swept.is_none_or(|swept| !within(now, swept, self.window)) {
+            seen.times.retain(|_, times| times.back().is_some_and(|last| within(now, *last, self.window)));
+            seen.swept = Some(now);
+        }
+        let times = seen.times.entry(fingerprint).or_default();
+        while times.front().is_some_and(|first| !within(now, *first, self.window)) {
+            times.pop_front();
+        }
+        times.push_back(now);
+        if times.len() > self.times {
+            let count = times.len();
+            times.clear();
+            return Some(count);
+        }
+        None
+    }
+}
+
+/// Occurrences of an event left out by [Dedup]
+pub(crate) struct Repeated {
+    pub level: Level,
+    pub target: String,
+    pub location: Option<String>,
+    pub count: usize,
+}
+
+struct Suppressing {
+    /// The last occurrence, the window starts from
+    last: SystemTime,
+    window: Duration,
+    repeated: Repeated,
+}
+
+impl Suppressing {
+    /// Windows too long for the clock end in a century
+    fn end(&self) -> SystemTime {
+        let century = Duration::from_secs(100 * 365 * 24 * 3600);
+        self.last.checked_add(self.window).unwrap_or(self.last + century)
+    }
+}
+
+#[derive(Default)]
+struct Suppressed {
+    events: HashMap<u64, Suppressing>,
+    /// The events by the end of their window, so that the ended ones are found without a scan
+    ends: BTreeSet<(SystemTime, u64)>,
+}
+
+/// Leave out repetitions of an event within a window sliding with its last occurrence
+pub(crate) struct Dedup {
+    window: Option<Duration>,
+    targets: Vec<(String, Duration)>,
+    seen: Mutex<Suppressed>,
+}
+
+impl Dedup {
+    pub fn new(window: Option<Duration>, targets: Vec<(String, Duration)>) -> Self {
+        Dedup { window, targets, seen: Mutex::default() }
+    }
+
+    /// The window of the most specific target prefix, like `my_crate::db`, or the default one
+    fn window(&self, target: &str) -> Option<Duration> {
+        self.targets
+            .iter()
+            .filter(|(prefix, _)| {
+                target == prefix || target.starts_with(prefix.as_str()) && target[prefix.len()..].starts_with("::")
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, window)| *window)
+            .or(self.window)
+    }
+
+    /// Whether the occurrence must be left out
+    pub fn check(&self, fingerprint: u64, level: Level, target: &str, location: Option<&str>, now: SystemTime) -> bool {
+        let Some(window) = self.window(target) else { return false };
+        let mut seen = self.seen.lock().unwrap_or_else(|err| err.into_inner());
+        let seen = &mut *seen;
+        if let Some(suppressing) = seen.events.get_mut(&fingerprint) {
+            if within(now, suppressing.last, suppressing.window) {
+                // the window slides, it ends once the event stopped repeating for as long
+                seen.ends.remove(&(suppressing.end(), fingerprint));
+                suppressing.last = now;
+                seen.ends.insert((suppressing.end(), fingerprint));
+                suppressing.repeated.count += 1;
+                return true;
+            }
+        }
+        let repeated = Repeated { level, target: target.to_string(), location: location.map(String::from), count: 0 };
+        let suppressing = Suppressing { last: now, window, repeated };
+        seen.ends.insert((suppressing.end(), fingerprint));
+        if let Some(ended) = seen.events.insert(fingerprint, suppressing) {
+            seen.ends.remove(&(ended.end(), fingerprint));
+        }
+        false
+    }
+
+    /// The repetitions of the windows ended at `now`, all of them when `now` is `None`
+    pub fn take_repeated(&self, now: Option<SystemTime>) -> Vec<Repeated> {
+        let mut seen = self.seen.lock().unwrap_or_else(|err| err.into_inner());
+        let mut ended = vec![];
+        while let Some(&(end, fingerprint)) = seen.ends.first() {
+            if now.is_some_and(|now| now < end) {
+                break;
+            }
+            seen.ends.pop_first();
+            ended.push(fingerprint);
+        }
+        let mut repeated: Vec<(SystemTime, Repeated)> = ended
+            .into_iter()
+            .filter_map(|fingerprint| seen.events.remove(&fingerprint))
+            .filter(|suppressing| suppressing.repeated.count > 0)
+            .map(|suppressing| (suppressing.last, suppressing.repeated))
+            .collect();
+        repeated.sort_by_key(|(last, _)| *last);
+        repeated.into_iter().map(|(_, repeated)| repeated).collect()
+    }
+}
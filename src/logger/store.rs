@@ -0,0 +1,257 @@
+//! Keeping recent errors around for an in-app problems view
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// The table of [SqliteStore], for other [ErrorStore] implementations over SQLite
+pub const SQLITE_SCHEMA: &str = "\
+CREATE TABLE IF NOT EXISTS errors (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    message TEXT NOT NULL,
+    chain TEXT NOT NULL,
+    code TEXT,
+    fingerprint TEXT NOT NULL,
+    version TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS errors_timestamp ON errors (timestamp);
+CREATE INDEX IF NOT EXISTS errors_code ON errors (code);
+";
+
+/// An `ERROR` record kept by an [ErrorStore]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredError {
+    pub timestamp: SystemTime,
+    pub message: String,
+    /// The messages of the error fields, outermost first
+    pub chain: Vec<String>,
+    /// The `code` field
+    pub code: Option<String>,
+    /// Identifies repetitions of the same event, in hex
+    pub fingerprint: String,
+    /// The version of the application, see [Builder::store](super::Builder::store)
+    pub version: String,
+}
+
+/// Where the logger keeps `ERROR` records, see [Builder::store](super::Builder::store)
+///
+/// Use the [MemoryStore], the `SqliteStore` with the `sqlite` feature, or implement it over the
+/// database of the application.
+pub trait ErrorStore: Send + Sync + 'static {
+    fn insert(&self, error: StoredError);
+
+    /// Forget the errors older than `before`
+    fn prune(&self, before: SystemTime);
+
+    /// The latest `limit` errors, newest first
+    fn recent(&self, limit: usize) -> Vec<StoredError>;
+
+    /// The latest `limit` errors with the code `code`, newest first
+    fn with_code(&self, code: &str, limit: usize) -> Vec<StoredError> {
+        self.recent(usize::MAX).into_iter().filter(|error| error.code.as_deref() == Some(code)).take(limit).collect()
+    }
+
+    /// The errors since `after`, newest first
+    fn since(&self, after: SystemTime) -> Vec<StoredError> {
+        self.recent(usize::MAX).into_iter().take_while(|error| error.timestamp >= after).collect()
+    }
+}
+
+/// An [ErrorStore] keeping at most a number of errors in memory, clones share the same errors
+///
+///     use std::time::Duration;
+///
+///     use errlog::logger::{self, ErrorStore, MemoryStore};
+///
+///     let store = MemoryStore::new(100);
+///     let _guard = logger::builder()
+///         .store(store.clone(), Duration::from_secs(7 * 24 * 3600))
+///         .set_default();
+///     errlog::logmsg!(ERROR, code = "E042", "disk full");
+///
+///     # #[cfg(not(feature = "silent"))]
+///     assert_eq!(store.with_code("E042", 10)[0].message, "disk full");
+#[derive(Debug, Clone)]
+pub struct MemoryStore {
+    capacity: usize,
+    errors: Arc<Mutex<VecDeque<StoredError>>>,
+}
+
+impl MemoryStore {
+    pub fn new(capacity: usize) -> Self {
+        MemoryStore { capacity, errors: Arc::default() }
+    }
+}
+
+impl ErrorStore for MemoryStore {
+    fn insert(&self, error: StoredError) {
+        let mut errors = self.errors.lock().unwrap_or_else(|err| err.into_inner());
+        if errors.len() >= self.capacity {
+            errors.pop_front();
+        }
+        errors.push_back(error);
+    }
+
+    fn prune(&self, before: SystemTime) {
+        self.errors.lock().unwrap_or_else(|err| err.into_inner()).retain(|error| error.timestamp >= before);
+    }
+
+    fn recent(&self, limit: usize) -> Vec<StoredError> {
+        let errors = self.errors.lock().unwrap_or_else(|err| err.into_inner());
+        errors.iter().rev().take(limit).cloned().collect()
+    }
+}
+
+/// An [ErrorStore] in a SQLite database with the [SQLITE_SCHEMA] table
+///
+///     use std::time::{Duration, SystemTime};
+///
+///     use errlog::logger::{self, ErrorStore, SqliteStore};
+///
+///     let path = std::env::temp_dir().join(format!("errlog-{}.sqlite", std::process::id()));
+///     let store = SqliteStore::open(&path).unwrap();
+///     let _guard = logger::builder()
+///         .store(store.clone(), Duration::from_secs(7 * 24 * 3600))
+///         .set_default();
+///     errlog::logmsg!(ERROR, code = "E042", "disk full");
+///
+///     # #[cfg(not(feature = "silent"))]
+///     # {
+///     assert_eq!(store.with_code("E042", 10)[0].message, "disk full");
+///     assert_eq!(store.since(SystemTime::now() - Duration::from_secs(60)).len(), 1);
+///     # }
+///     # std::fs::remove_file(&path).unwrap();
+///
+/// Clones share the same connection. Failed statements are ignored, since the logger cannot log
+/// them.
+#[cfg(feature = "sqlite")]
+#[derive(Clone)]
+pub struct SqliteStore {
+    connection: Arc<Mutex<rusqlite::Connection>>,
+}
+
+#[cfg(feature = "sqlite")]
+fn micros(time: SystemTime) -> i64 {
+    time.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_micros() as i64
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    /// Open or create the database at `path` and its table
+    pub fn open(path: impl AsRef<std::path::Path>) -> crate::Result<Self> {
+        use crate::Context;
+
+        let path = path.as_ref();
+        let connection = rusqlite::Connection::open(path)
+            .context(format!("failed to open the error store {}", path.display()))?;
+        SqliteStore::new(connection)
+    }
+
+    /// A database living as long as the store
+    pub fn in_memory() -> crate::Result<Self> {
+        use crate::Context;
+
+        SqliteStore::new(rusqlite::Connection::open_in_memory().context("failed to open an in-memory error store")?)
+    }
+
+    fn new(connection: rusqlite::Connection) -> crate::Result<Self> {
+        use crate::Context;
+
+        connection.execute_batch(SQLITE_SCHEMA).context("failed to create the errors table")?;
+        Ok(SqliteStore { connection: Arc::new(Mutex::new(connection)) })
+    }
+
+    fn query(&self, filter: &str, params: impl rusqlite::Params) -> Vec<StoredError> {
+        let connection = self.connection.lock().unwrap_or_else(|err| err.into_inner());
+        let sql = format!(
+            "SELECT timestamp, message, chain, code, fingerprint, version FROM errors {} ORDER BY timestamp DESC, id DESC LIMIT ?",
+            filter
+        );
+        let Ok(mut statement) = connection.prepare(&sql) else { return vec![] };
+        let rows = statement.query_map(params, |row| {
+            let chain: String = row.get(2)?;
+            Ok(StoredError {
+                timestamp: std::time::UNIX_EPOCH + Duration::from_micros(row.get::<_, i64>(0)?.max(0) as u64),
+                message: row.get(1)?,
+                chain: chain.lines().map(String::from).collect(),
+                code: row.get(3)?,
+                fingerprint: row.get(4)?,
+                version: row.get(5)?,
+            })
+        });
+        rows.map(|rows| rows.filter_map(|row| row.ok()).collect()).unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl ErrorStore for SqliteStore {
+    fn insert(&self, error: StoredError) {
+        let connection = self.connection.lock().unwrap_or_else(|err| err.into_inner());
+        let _ = connection.execute(
+            "INSERT INTO errors (timestamp, message, chain, code, fingerprint, version) VALUES (?, ?, ?, ?, ?, ?)",
+            rusqlite::params![
+                micros(error.timestamp),
+                error.message,
+                error.chain.join("\n"),
+                error.code,
+                error.fingerprint,
+                error.version
+            ],
+        );
+    }
+
+    fn prune(&self, before: SystemTime) {
+        let connection = self.connection.lock().unwrap_or_else(|err| err.into_inner());
+        let _ = connection.execute("DELETE FROM errors WHERE timestamp < ?", [micros(before)]);
+    }
+
+    fn recent(&self, limit: usize) -> Vec<StoredError> {
+        self.query("", [limit.min(i64::MAX as usize) as i64])
+    }
+
+    fn with_code(&self, code: &str, limit: usize) -> Vec<StoredError> {
+        self.query("WHERE code = ?", rusqlite::params![code, limit.min(i64::MAX as usize) as i64])
+    }
+
+    fn since(&self, after: SystemTime) -> Vec<StoredError> {
+        self.query("WHERE timestamp >= ?", [micros(after), i64::MAX])
+    }
+}
+
+/// How often the store is pruned
+const PRUNE_EVERY: Duration = Duration::from_secs(60);
+
+/// Inserts `ERROR` records and prunes the store now and then
+pub(super) struct Storing {
+    store: Box<dyn ErrorStore>,
+    retention: Duration,
+    version: String,
+    pruned: Mutex<Option<SystemTime>>,
+}
+
+impl Storing {
+    pub fn new(store: Box<dyn ErrorStore>, retention: Duration, version: String) -> Self {
+        Storing { store, retention, version, pruned: Mutex::new(None) }
+    }
+
+    pub fn insert(&self, record: &super::Record, fingerprint: u64) {
+        let now = record.timestamp;
+        let mut pruned = self.pruned.lock().unwrap_or_else(|err| err.into_inner());
+        if pruned.is_none_or(|pruned| now.duration_since(pruned).unwrap_or_default() >= PRUNE_EVERY) {
+            *pruned = Some(now);
+            if let Some(before) = now.checked_sub(self.retention) {
+                self.store.prune(before);
+            }
+        }
+        drop(pruned);
+        self.store.insert(StoredError {
+            timestamp: now,
+            message: record.message.clone(),
+            chain: record.errors.iter().flat_map(|(_, chain)| chain.iter().cloned()).collect(),
+            code: record.fields.iter().find(|(name, _)| name == "code").map(|(_, code)| code.clone()),
+            fingerprint: format!("{:016x}", fingerprint),
+            version: self.version.clone(),
+        });
+    }
+}
@@ -0,0 +1,113 @@
+//! Summing up how long spans took, for [Builder::span_summary](super::Builder::span_summary)
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use tracing::span::{Attributes, Id};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::layer::{Context as LayerContext, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::{Deterministic, Format, Layout, Record, Sink};
+use crate::clock::Clock;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Stats {
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+/// The durations of the closed spans by name, clones share them
+#[derive(Debug, Clone, Default)]
+pub(super) struct Timings(Arc<Mutex<HashMap<&'static str, Stats>>>);
+
+/// When the span was created
+struct Opened(Instant);
+
+impl Timings {
+    /// The table of the spans by decreasing total time, `None` without any
+    pub(super) fn summary(&self) -> Option<String> {
+        let stats = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        if stats.is_empty() {
+            return None;
+        }
+        let mut rows: Vec<(&&str, &Stats)> = stats.iter().collect();
+        rows.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(b.0)));
+        let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0).max("span".len());
+        let mut table = format!(
+            "span timings\n{:<width$} {:>8} {:>12} {:>12} {:>12}",
+            "span",
+            "count",
+            "total",
+            "mean",
+            "max",
+            width = width
+        );
+        for (name, stats) in rows {
+            let mean = Duration::from_nanos((stats.total.as_nanos() / stats.count.max(1) as u128) as u64);
+            table.push_str(&format!(
+                "\n{:<width$} {:>8} {:>12} {:>12} {:>12}",
+                name,
+                stats.count,
+                format!("{:.1?}", stats.total),
+                format!("{:.1?}", mean),
+                format!("{:.1?}", stats.max),
+                width = width
+            ));
+        }
+        Some(table)
+    }
+}
+
+impl<S> Layer<S> for Timings
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _: &Attributes<'_>, id: &Id, ctx: LayerContext<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Opened(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: LayerContext<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let Some(elapsed) = span.extensions().get::<Opened>().map(|opened| opened.0.elapsed()) else {
+            return;
+        };
+        let mut stats = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        let stats = stats.entry(span.name()).or_default();
+        stats.count += 1;
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+    }
+}
+
+/// Writes the table of [Timings] when the guard is dropped
+pub(super) struct Summary {
+    pub(super) timings: Timings,
+    pub(super) format: Format,
+    pub(super) layout: Layout,
+    pub(super) clock: Arc<dyn Clock>,
+    pub(super) deterministic: Deterministic,
+    pub(super) sink: Sink,
+}
+
+impl Summary {
+    pub(super) fn write(&self) {
+        let Some(table) = self.timings.summary() else { return };
+        let record = Record {
+            timestamp: if self.deterministic.is_on() { UNIX_EPOCH } else { self.clock.now() },
+            level: Level::INFO,
+            target: "errlog".to_string(),
+            spans: vec![],
+            message: table,
+            fields: vec![],
+            errors: vec![],
+            error_locations: vec![],
+            location: None,
+        };
+        self.sink.write(self.format.render(&record, &Layout { locations: false, ..self.layout.clone() }).as_bytes());
+    }
+}
@@ -0,0 +1,197 @@
+//! Posting chat alerts for errors
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use super::{json_string, Record};
+
+/// Where and when to post alerts, see [Builder::webhook](super::Builder::webhook)
+///
+/// `ERROR` records matching one of the filters, or all of them without filters, are collected
+/// for an [interval](Webhook::interval) and posted in one request. An event is not posted again
+/// before its [cool-down](Webhook::cooldown) ends. Records are dropped when the queue is full or
+/// their post fails, the [guard](super::Guard::dropped) counts them.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: String,
+    template: String,
+    targets: Vec<String>,
+    codes: Vec<String>,
+    tags: Vec<(String, String)>,
+    cooldown: Duration,
+    interval: Duration,
+}
+
+impl Webhook {
+    /// Post `template` to `url`, where `{text}` is replaced by the formatted records as a JSON
+    /// string and `{count}` by their number
+    pub fn new(url: &str, template: &str) -> Self {
+        Webhook {
+            url: url.to_string(),
+            template: template.to_string(),
+            targets: vec![],
+            codes: vec![],
+            tags: vec![],
+            cooldown: Duration::from_secs(300),
+            interval: Duration::from_secs(10),
+        }
+    }
+
+    /// Post to a Slack incoming webhook
+    pub fn slack(url: &str) -> Self {
+        Webhook::new(url, r#"{"text":{text}}"#)
+    }
+
+    /// Post to a Discord webhook
+    pub fn discord(url: &str) -> Self {
+        Webhook::new(url, r#"{"content":{text}}"#)
+    }
+
+    /// Alert on records of `target` and its children
+    pub fn target(mut self, target: &str) -> Self {
+        self.targets.push(target.to_string());
+        self
+    }
+
+    /// Alert on records with the `code` field `code`
+    pub fn code(mut self, code: &str) -> Self {
+        self.codes.push(code.to_string());
+        self
+    }
+
+    /// Alert on records with the field `name` set to `value`, like `alert = "oncall"`
+    pub fn tag(mut self, name: &str, value: &str) -> Self {
+        self.tags.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// How long the same event is not posted again, the default is 5 minutes
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// How long records are collected before posting them, the default is 10 seconds, which is
+    /// also the shortest time between two posts
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    fn matches(&self, record: &Record) -> bool {
+        if self.targets.is_empty() && self.codes.is_empty() && self.tags.is_empty() {
+            return true;
+        }
+        let field = |name: &str| record.fields.iter().find(|(field, _)| field == name).map(|(_, value)| value);
+        self.targets.iter().any(|target| {
+            record.target == *target || record.target.strip_prefix(target.as_str()).is_some_and(|rest| rest.starts_with("::"))
+        }) || self.codes.iter().any(|code| field("code") == Some(code))
+            || self.tags.iter().any(|(name, value)| field(name) == Some(value))
+    }
+
+    /// The payload for `lines`
+    fn payload(&self, lines: &[String]) -> String {
+        let mut text = String::new();
+        json_string(&mut text, &lines.join("\n"));
+        self.template.replace("{count}", &lines.len().to_string()).replace("{text}", &text)
+    }
+}
+
+enum Message {
+    Line(String),
+    Flush(SyncSender<()>),
+}
+
+/// The queue of the thread posting alerts
+pub(super) struct WebhookClient {
+    config: Webhook,
+    sender: SyncSender<Message>,
+    posted: Mutex<HashMap<u64, SystemTime>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl WebhookClient {
+    pub fn new(config: Webhook) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(1024);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (poster, counter) = (config.clone(), dropped.clone());
+        thread::Builder::new()
+            .name("errlog-webhook".to_string())
+            // events of the HTTP client must not come back to the logger
+            .spawn(move || tracing::dispatcher::with_default(&tracing::Dispatch::none(), || post_loop(&poster, receiver, &counter)))
+            .expect("failed to spawn the errlog webhook thread");
+        WebhookClient { config, sender, posted: Mutex::new(HashMap::new()), dropped }
+    }
+
+    /// Queue the formatted `line` of `record` when it matches and its event is not cooling down
+    pub fn send(&self, record: &Record, fingerprint: u64, line: &str) {
+        if record.level != tracing::Level::ERROR || !self.config.matches(record) {
+            return;
+        }
+        let now = record.timestamp;
+        let mut posted = self.posted.lock().unwrap_or_else(|err| err.into_inner());
+        posted.retain(|_, at| now.duration_since(*at).unwrap_or_default() < self.config.cooldown);
+        if posted.contains_key(&fingerprint) {
+            return;
+        }
+        posted.insert(fingerprint, now);
+        if self.sender.try_send(Message::Line(line.trim_end().to_string())).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// How many records were left out because the queue was full or their post failed
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Wait until the collected records were posted
+    pub fn sync(&self) {
+        let (done, wait) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+    }
+}
+
+fn post_loop(config: &Webhook, receiver: Receiver<Message>, dropped: &AtomicU64) {
+    let client = reqwest::blocking::Client::new();
+    let post = |lines: &mut Vec<String>| {
+        if lines.is_empty() {
+            return;
+        }
+        let response = client
+            .post(&config.url)
+            .header("Content-Type", "application/json")
+            .body(config.payload(lines))
+            .send();
+        if !response.is_ok_and(|response| response.status().is_success()) {
+            dropped.fetch_add(lines.len() as u64, Ordering::Relaxed);
+        }
+        lines.clear();
+    };
+    let mut lines = vec![];
+    let mut deadline: Option<Instant> = None;
+    loop {
+        let wait = deadline.map_or(Duration::from_secs(3600), |at| at.saturating_duration_since(Instant::now()));
+        match receiver.recv_timeout(wait) {
+            Ok(Message::Line(line)) => {
+                lines.push(line);
+                deadline.get_or_insert_with(|| Instant::now() + config.interval);
+            }
+            Ok(Message::Flush(done)) => {
+                post(&mut lines);
+                let _ = done.send(());
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                post(&mut lines);
+                deadline = None;
+            }
+            Err(RecvTimeoutError::Disconnected) => return post(&mut lines),
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Memory usage of the process, to log next to the operations which change it
+//!
+//!     use errlog::logger::{self, MemoryWriter};
+//!     use errlog::logmem;
+//!
+//!     let output = MemoryWriter::new();
+//!     let _guard = logger::builder().writer(output.clone()).set_default();
+//!     let index: Vec<u64> = (0..100_000).collect();
+//!     logmem!(INFO, "after index build");
+//!     # drop(index);
+//!     # #[cfg(all(target_os = "linux", not(feature = "silent")))]
+//!     assert!(output.contents().contains("after index build (rss "));
+//!
+//! The resident set sizes are read from `/proc/self` on Linux and are unknown on other
+//! platforms. The heap size is only known with the [CountingAlloc] as the global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+static HEAP: AtomicU64 = AtomicU64::new(0);
+static COUNTING: AtomicBool = AtomicBool::new(false);
+
+/// A global allocator counting the bytes allocated through it
+///
+///     #[global_allocator]
+///     static ALLOC: errlog::mem::CountingAlloc = errlog::mem::CountingAlloc::system();
+pub struct CountingAlloc<A = System>(A);
+
+impl CountingAlloc {
+    pub const fn system() -> Self {
+        CountingAlloc(System)
+    }
+}
+
+impl<A> CountingAlloc<A> {
+    /// Count the allocations of `alloc`
+    pub const fn new(alloc: A) -> Self {
+        CountingAlloc(alloc)
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAlloc<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.0.alloc(layout);
+        if !ptr.is_null() {
+            COUNTING.store(true, Ordering::Relaxed);
+            HEAP.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.dealloc(ptr, layout);
+        HEAP.fetch_sub(layout.size() as u64, Ordering::Relaxed);
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.0.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            COUNTING.store(true, Ordering::Relaxed);
+            HEAP.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = self.0.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            HEAP.fetch_add(new_size as u64, Ordering::Relaxed);
+            HEAP.fetch_sub(layout.size() as u64, Ordering::Relaxed);
+        }
+        new
+    }
+}
+
+/// Memory usage of the process in bytes, `None` when unknown on this platform
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryStats {
+    /// The resident set size
+    pub rss: Option<u64>,
+    /// The highest resident set size so far
+    pub peak_rss: Option<u64>,
+    /// The bytes allocated through the [CountingAlloc]
+    pub heap: Option<u64>,
+}
+
+impl MemoryStats {
+    pub fn current() -> Self {
+        let (rss, peak_rss) = resident();
+        let heap = COUNTING.load(Ordering::Relaxed).then(|| HEAP.load(Ordering::Relaxed));
+        MemoryStats { rss, peak_rss, heap }
+    }
+
+    /// Like `rss 12.3 MiB, peak 15.0 MiB, heap 4.1 MiB`, without the unknown values
+    pub fn summary(&self) -> String {
+        let values = [("rss", self.rss), ("peak", self.peak_rss), ("heap", self.heap)];
+        values
+            .iter()
+            .filter_map(|(name, bytes)| bytes.map(|bytes| format!("{} {:.1} MiB", name, bytes as f64 / (1024.0 * 1024.0))))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn resident() -> (Option<u64>, Option<u64>) {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+    // `VmRSS:     1234 kB`
+    let kib = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            .map(|kib| kib * 1024)
+    };
+    (kib("VmRSS:"), kib("VmHWM:"))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident() -> (Option<u64>, Option<u64>) {
+    (None, None)
+}
+
+/// Log a message with the [MemoryStats] of the process
+///
+/// The message is followed by the [summary](MemoryStats::summary) in parentheses and the event
+/// carries the known values as the `rss`, `peak_rss` and `heap` fields, in bytes.
+#[macro_export]
+macro_rules! logmem {
+    ($level:ident, $($arg:tt)+) => {
+        if !$crate::__private::SILENT {
+            let stats = $crate::mem::MemoryStats::current();
+            $crate::logmsg!(
+                $level,
+                rss = stats.rss,
+                peak_rss = stats.peak_rss,
+                heap = stats.heap,
+                "{} ({})",
+                format_args!($($arg)+),
+                stats.summary()
+            );
+        }
+    };
+}
@@ -0,0 +1,102 @@
+//! String metadata attached to errors
+
+use std::fmt;
+
+use crate::{Error, Result};
+
+/// The metadata of an error, see [MetaExt]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Meta(pub Vec<(String, String)>);
+
+impl fmt::Display for Meta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !crate::location::is_shown() {
+            return Ok(());
+        }
+        write!(f, "{}", MARKER)?;
+        for (key, value) in &self.0 {
+            write!(f, "{}{}{}{}", key, SEPARATOR, value, END)?;
+        }
+        Ok(())
+    }
+}
+
+impl Meta {
+    fn set(&mut self, key: &str, value: String) {
+        match self.0.iter_mut().find(|(name, _)| name == key) {
+            Some(entry) => entry.1 = value,
+            None => self.0.push((key.to_string(), value)),
+        }
+    }
+}
+
+/// Attach `key = value` strings to the error of a result
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::{MetaExt, Result};
+///
+///     fn import(table: &str, rows: usize) -> Result<()> {
+///         Err(errlog::Error::msg("constraint violated"))
+///             .with_meta("table", table)
+///             .with_meta("rows", rows.to_string())
+///     }
+///
+///     let err = import("users", 3).unwrap_err();
+///     assert_eq!(errlog::meta(&err), [("table".to_string(), "users".to_string()), ("rows".to_string(), "3".to_string())]);
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///     errlog::logmsg!(ERROR, error = errlog::as_dyn_error(&err), "import failed");
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("table=users rows=3"));
+///
+/// An error holds one map, setting a key again replaces its value. The map is shown in the
+/// `Metadata` section of [pretty](crate::report::pretty) reports, carried by
+/// [ErrorReport](crate::ErrorReport), and added as fields by the [logger](crate::logger) when
+/// the error is recorded with [as_dyn_error](crate::as_dyn_error).
+pub trait MetaExt<T> {
+    fn with_meta(self, key: &str, value: impl Into<String>) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> MetaExt<T> for std::result::Result<T, E> {
+    fn with_meta(self, key: &str, value: impl Into<String>) -> Result<T> {
+        self.map_err(|err| {
+            let mut err = err.into();
+            match err.downcast_mut::<Meta>() {
+                Some(meta) => {
+                    meta.set(key, value.into());
+                    err
+                }
+                None => crate::__private::context(err, Meta(vec![(key.to_string(), value.into())])),
+            }
+        })
+    }
+}
+
+/// The metadata attached to the error, empty without any
+pub fn meta(err: &Error) -> &[(String, String)] {
+    err.downcast_ref::<Meta>().map(|meta| meta.0.as_slice()).unwrap_or_default()
+}
+
+/// Starts the rendering of metadata inside [shown](crate::location::shown), followed by the
+/// entries, each ending with [END], with [SEPARATOR] between the key and the value
+pub(crate) const MARKER: char = '\u{2}';
+const SEPARATOR: char = '\u{1f}';
+const END: char = '\u{1e}';
+
+/// The metadata of the errors in `chain`, read from their rendering, so that it is found in any
+/// `dyn Error` of the chain, on any thread
+pub(crate) fn from_chain<'a>(chain: impl Iterator<Item = &'a (dyn std::error::Error + 'static)>) -> Vec<(String, String)> {
+    crate::location::shown(|| {
+        chain
+            .filter_map(|cause| cause.to_string().strip_prefix(MARKER).map(String::from))
+            .flat_map(|entries| {
+                entries
+                    .split(END)
+                    .filter_map(|entry| entry.split_once(SEPARATOR))
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    })
+}
@@ -0,0 +1,19 @@
+//! Everything needed to wrap and log errors, in one import
+//!
+//!     use errlog::prelude::*;
+//!
+//!     fn load(filepath: &str) -> Result<String> {
+//!         let content = wraperr!(std::fs::read_to_string(filepath), "failed to load {}", filepath)?;
+//!         logmsg!(INFO, "loaded {}", filepath);
+//!         Ok(content)
+//!     }
+//!
+//!     assert_err_chain!(load("/path/to/config"), ["No such file"]);
+
+pub use crate::{Attach, Class, ClassExt, Code, Context, Error, ErrorCode, HelpExt, Location, MetaExt, Result, Severity, StatusExt};
+pub use crate::{assert_err_chain, bail_with_level, ensure_or_log, logmsg, logvariant, match_io_kind, matcherr, or_log_default};
+pub use crate::{logdbg, loginspect, logprogress, redact, soft_assert, soft_assert_eq, warnbail, wraperr};
+pub use crate::{instrument_err, log_slow, retry};
+
+#[cfg(feature = "reqwest")]
+pub use crate::http::{HttpResultExt, RequestBuilderExt};
@@ -0,0 +1,102 @@
+//! PyO3 integration for mixed Rust/Python applications
+//!
+//! - [PyError] converts errlog errors into Python exceptions with `?`
+//! - [PythonLogLayer] routes tracing events (hence `logmsg!` and `wraperr!`) into Python's
+//!   `logging` module
+
+use std::fmt;
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context as LayerContext, Layer};
+
+use crate::fields::FieldVisitor;
+use crate::Error;
+
+/// Convert the error into a Python `RuntimeError`
+///
+/// The exception's `args` are `(message, frames, code)`, where message is the whole chain on one
+/// line, frames are the messages of the chain (outermost first) and code is the error code or
+/// `None`.
+pub fn to_pyerr(err: &Error) -> PyErr {
+    let frames = crate::chain_messages(err);
+    let message = frames.join(": ");
+    PyRuntimeError::new_err((message, frames, crate::error_code(err)))
+}
+
+/// An error converting into `PyErr`, use it as the error type of `#[pyfunction]`s
+///
+///     use errlog::python::PyError;
+///     use pyo3::prelude::*;
+///
+///     #[pyfunction]
+///     fn load(path: &str) -> Result<String, PyError> {
+///         Ok(std::fs::read_to_string(path)?)
+///     }
+///
+/// `From<errlog::Error> for PyErr` cannot be implemented here since neither type belongs to
+/// errlog, this newtype is the bridge.
+pub struct PyError(pub Error);
+
+impl<E: Into<Error>> From<E> for PyError {
+    fn from(err: E) -> Self {
+        PyError(err.into())
+    }
+}
+
+impl From<PyError> for PyErr {
+    fn from(err: PyError) -> PyErr {
+        to_pyerr(&err.0)
+    }
+}
+
+impl fmt::Debug for PyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+/// A tracing layer forwarding every event to `logging.getLogger(target).log(level, message)`
+///
+/// The levels map to `logging.DEBUG` (for both `TRACE` and `DEBUG`), `INFO`, `WARNING` and
+/// `ERROR`, and the event fields are appended to the message as `key=value`.
+pub struct PythonLogLayer;
+
+impl<S: Subscriber> Layer<S> for PythonLogLayer {
+    fn on_event(&self, event: &Event<'_>, _: LayerContext<'_, S>) {
+        let metadata = event.metadata();
+        let level = match *metadata.level() {
+            Level::TRACE | Level::DEBUG => 10,
+            Level::INFO => 20,
+            Level::WARN => 30,
+            Level::ERROR => 40,
+        };
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let mut message = visitor.message;
+        for (name, value) in visitor.fields {
+            message.push_str(&format!(" {}={}", name, value));
+        }
+        let target = metadata.target().replace("::", ".");
+        Python::attach(|py| {
+            let result = PyModule::import(py, "logging")
+                .and_then(|logging| logging.call_method1("getLogger", (target,)))
+                .and_then(|logger| logger.call_method1("log", (level, message)));
+            if let Err(err) = result {
+                err.print(py);
+            }
+        });
+    }
+}
+
+/// Install [PythonLogLayer] as the global subscriber
+///
+/// Call it from the `#[pymodule]` initializer, then configure handlers and levels on the Python
+/// side as usual.
+pub fn init_python_logging() {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let subscriber = tracing_subscriber::registry().with(PythonLogLayer);
+    let _ = tracing::subscriber::set_global_default(subscriber);
+}
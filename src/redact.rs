@@ -0,0 +1,79 @@
+//! Masking secrets at the call site, see [redact](crate::redact!)
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static VISIBLE: AtomicUsize = AtomicUsize::new(4);
+
+/// How many trailing characters [redact](crate::redact!) keeps when not given, 4 by default
+pub fn set_visible(chars: usize) {
+    VISIBLE.store(chars, Ordering::Relaxed);
+}
+
+/// A value rendered as `***` followed by its last characters, by `Display` and `Debug` alike
+pub struct Redacted<T> {
+    value: T,
+    visible: Option<usize>,
+}
+
+impl<T: fmt::Display> Redacted<T> {
+    pub fn new(value: T, visible: Option<usize>) -> Self {
+        Redacted { value, visible }
+    }
+
+    /// The value, for the code which needs the secret itself
+    pub fn expose(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let visible = self.visible.unwrap_or_else(|| VISIBLE.load(Ordering::Relaxed));
+        let value = self.value.to_string();
+        let chars = value.chars().count();
+        // short values would be mostly revealed
+        if visible == 0 || chars < visible * 2 {
+            return f.write_str("***");
+        }
+        let tail: String = value.chars().skip(chars - visible).collect();
+        write!(f, "***{}", tail)
+    }
+}
+
+impl<T: fmt::Display> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Mask a value in messages and errors, keeping its last characters to tell values apart
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::{redact, wraperr, Context, Result};
+///
+///     fn login(token: &str) -> Result<()> {
+///         let refused: Result<()> = Err(errlog::Error::msg("401 Unauthorized"));
+///         wraperr!(refused, "auth failed for {}", redact!(token))
+///             .with_context(|| format!("token {} rejected", redact!(token, visible = 2)))
+///     }
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///     let err = login("sk-live-51f0abcd").unwrap_err();
+///     errlog::assert_err_chain!(Err::<(), _>(err), ["token ***cd rejected"]);
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("auth failed for ***abcd"));
+///     assert!(!output.contents().contains("sk-live"));
+///
+/// The number of characters kept is 4 by default, see [set_visible](crate::redact::set_visible),
+/// and nothing is kept from values shorter than twice that number.
+#[macro_export]
+macro_rules! redact {
+    ($value:expr $(,)?) => {
+        $crate::redact::Redacted::new($value, ::core::option::Option::None)
+    };
+    ($value:expr, visible = $visible:expr $(,)?) => {
+        $crate::redact::Redacted::new($value, ::core::option::Option::Some($visible))
+    };
+}
@@ -0,0 +1,504 @@
+//! Error reports, both structured ([ErrorReport]) and pretty, section based ones for binaries
+//!
+//! A pretty report is made of the sections `Error`, `Locations`, `Source`, `Help`, `Span trace`,
+//! `Backtrace` and `Environment`, empty sections are omitted. Colors are used when stderr is a terminal and
+//! `NO_COLOR` is not set.
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::fmt::{self, Write};
+use std::io::IsTerminal;
+use std::panic::{self, PanicHookInfo};
+use std::sync::Once;
+
+use tracing_error::{SpanTrace, SpanTraceStatus};
+
+use crate::logger::Deterministic;
+use crate::{Error, Snippet};
+
+/// A structured snapshot of an error, ready to be serialized
+#[derive(Debug, Clone)]
+pub struct ErrorReport {
+    /// The outermost non-empty message
+    pub message: String,
+    /// The remaining non-empty messages of the chain, outermost first
+    pub causes: Vec<String>,
+    /// The error code, see [error_code](crate::error_code)
+    pub code: Option<&'static str>,
+    /// The title of the error code, see [ErrorCode::message](crate::ErrorCode::message)
+    pub title: Option<&'static str>,
+    /// The suggested HTTP status
+    pub status: Option<u16>,
+    /// URI identifying this occurrence, such as the request path
+    pub instance: Option<String>,
+    /// See [HelpExt::help](crate::HelpExt::help)
+    pub help: Option<String>,
+    /// See [HelpExt::doc_url](crate::HelpExt::doc_url)
+    pub doc_url: Option<String>,
+    /// See [HelpExt::public_msg](crate::HelpExt::public_msg)
+    pub public_message: Option<String>,
+    /// See [with_system](ErrorReport::with_system)
+    pub system: Option<SystemSnapshot>,
+    /// See [MetaExt](crate::MetaExt)
+    pub meta: Vec<(String, String)>,
+}
+
+impl ErrorReport {
+    pub fn new(err: &Error) -> Self {
+        let mut messages = crate::chain_messages(err).into_iter();
+        let coded = crate::find_error_code(err);
+        ErrorReport {
+            message: messages.next().unwrap_or_default(),
+            causes: messages.collect(),
+            code: crate::error_code(err),
+            title: coded.map(|code| code.message()),
+            status: crate::http_status(err),
+            instance: None,
+            help: crate::help(err).map(String::from),
+            doc_url: crate::doc_url(err).map(String::from),
+            public_message: crate::public_message(err).map(String::from),
+            system: None,
+            meta: crate::meta(err).to_vec(),
+        }
+    }
+
+    /// Capture the [system](SystemSnapshot) the error happened on, for reproducibility
+    pub fn with_system(mut self) -> Self {
+        self.system = Some(SystemSnapshot::capture());
+        self
+    }
+
+    /// Set the URI of this occurrence
+    pub fn with_instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Render the report as RFC 7807 `application/problem+json`
+    ///
+    /// `type` is the code appended to `base_uri` (or `about:blank` without code), `title` is the
+    /// code message or the reason phrase of the status, `status` defaults to 500 and `detail` is
+    /// the [public message](crate::public_message), or else the outermost message, so make sure
+    /// it is fit for clients
+    ///
+    ///     use errlog::{Context, ErrorCode, ErrorReport};
+    ///
+    ///     #[derive(Debug, ErrorCode)]
+    ///     enum UserError {
+    ///         #[code("E1001", http = 404, msg = "user not found")]
+    ///         NotFound,
+    ///     }
+    ///
+    ///     let err = errlog::Error::new(UserError::NotFound);
+    ///     let err = Err::<(), _>(err).context("no user with id 42").unwrap_err();
+    ///     let problem = ErrorReport::new(&err)
+    ///         .with_instance("/users/42")
+    ///         .to_problem_json("https://example.com/errors");
+    ///     assert_eq!(problem["type"], "https://example.com/errors/E1001");
+    ///     assert_eq!(problem["title"], "user not found");
+    ///     assert_eq!(problem["status"], 404);
+    ///     assert_eq!(problem["detail"], "no user with id 42");
+    ///     assert_eq!(problem["instance"], "/users/42");
+    ///     assert_eq!(problem["code"], "E1001");
+    #[cfg(feature = "json")]
+    pub fn to_problem_json(&self, base_uri: &str) -> serde_json::Value {
+        let status = self.status.unwrap_or(500);
+        let typ = match self.code {
+            Some(code) => format!("{}/{}", base_uri.trim_end_matches('/'), code),
+            None => "about:blank".to_string(),
+        };
+        let title = self.title.or_else(|| reason_phrase(status)).unwrap_or("Error");
+        let mut problem = serde_json::json!({
+            "type": typ,
+            "title": title,
+            "status": status,
+            "detail": self.public_message.as_ref().unwrap_or(&self.message),
+        });
+        if let Some(instance) = &self.instance {
+            problem["instance"] = instance.as_str().into();
+        }
+        if let Some(code) = self.code {
+            problem["code"] = code.into();
+        }
+        if let Some(help) = &self.help {
+            problem["help"] = help.as_str().into();
+        }
+        if let Some(doc_url) = &self.doc_url {
+            problem["doc_url"] = doc_url.as_str().into();
+        }
+        if !self.meta.is_empty() {
+            let meta: serde_json::Map<String, serde_json::Value> =
+                self.meta.iter().map(|(key, value)| (key.clone(), value.as_str().into())).collect();
+            problem["meta"] = meta.into();
+        }
+        problem
+    }
+}
+
+/// The reason phrase of common HTTP statuses
+#[cfg(feature = "json")]
+fn reason_phrase(status: u16) -> Option<&'static str> {
+    let phrase = match status {
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        410 => "Gone",
+        412 => "Precondition Failed",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => return None,
+    };
+    Some(phrase)
+}
+
+struct Section {
+    title: &'static str,
+    lines: Vec<String>,
+}
+
+/// Everything shown in a report
+struct Report<'a> {
+    headline: String,
+    causes: Vec<String>,
+    locations: Vec<String>,
+    meta: Vec<String>,
+    snippet: Option<&'a Snippet>,
+    help: Vec<String>,
+    span_trace: Option<&'a SpanTrace>,
+    backtrace: Option<&'a Backtrace>,
+}
+
+fn use_color() -> bool {
+    std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal()
+}
+
+fn paint(text: &str, ansi: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", ansi, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Environment variables worth reporting, by name or by prefix when ending with `_`
+const REPORTED_VARS: &[&str] = &["RUST_", "ERRLOG_", "LANG", "LC_ALL", "TZ"];
+
+/// Parts of variable names whose values are hidden
+const SECRET_VARS: &[&str] = &["SECRET", "TOKEN", "PASSWORD", "PASSWD", "KEY", "AUTH", "CREDENTIAL"];
+
+/// The system a failure happened on, shown in the `Environment` section of reports
+///
+///     let system = errlog::report::SystemSnapshot::capture();
+///     assert_eq!(system.arch, std::env::consts::ARCH);
+///     assert!(system.cpus >= 1);
+///
+/// The environment holds the `RUST_*` and `ERRLOG_*` variables, `LANG`, `LC_ALL` and `TZ`, with
+/// the values of names looking like secrets replaced by `[redacted]`, and the arguments are
+/// redacted the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemSnapshot {
+    pub os: &'static str,
+    /// The distribution and kernel release, when known
+    pub os_version: Option<String>,
+    pub arch: &'static str,
+    pub cpus: usize,
+    /// Total and available memory in bytes, when known
+    pub memory: Option<(u64, u64)>,
+    pub env: Vec<(String, String)>,
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+}
+
+/// Whether the name of a variable or an option looks like it holds a secret
+fn is_secret(name: &str) -> bool {
+    let name = name.to_uppercase();
+    SECRET_VARS.iter().any(|secret| name.contains(secret))
+}
+
+/// The arguments of the process, with the values of options looking like secrets, such as
+/// `--password=...` or `--token ...`, replaced by `[redacted]` and the [scrub](crate::scrub)
+/// rules applied
+///
+/// Arguments which are not valid Unicode are decoded lossily, since this runs in panic hooks.
+pub(crate) fn args() -> Vec<String> {
+    let mut args = vec![];
+    let mut secret_next = false;
+    for arg in std::env::args_os() {
+        let mut arg = arg.to_string_lossy().into_owned();
+        if std::mem::take(&mut secret_next) {
+            arg = "[redacted]".to_string();
+        } else if arg.starts_with('-') {
+            match arg.split_once('=') {
+                Some((name, _)) if is_secret(name) => arg = format!("{}=[redacted]", name),
+                Some(_) => {}
+                None => secret_next = is_secret(&arg),
+            }
+        }
+        args.push(crate::scrub(arg));
+    }
+    args
+}
+
+impl SystemSnapshot {
+    pub fn capture() -> Self {
+        // by name first, the values of other variables may not be valid Unicode
+        let mut env: Vec<(String, String)> = std::env::vars_os()
+            .filter_map(|(name, value)| {
+                let name = name.into_string().ok()?;
+                let reported =
+                    REPORTED_VARS.iter().any(|var| if var.ends_with('_') { name.starts_with(var) } else { &name == var });
+                reported.then_some((name, value))
+            })
+            .map(|(name, value)| match is_secret(&name) {
+                true => (name, "[redacted]".to_string()),
+                false => (name, value.to_string_lossy().into_owned()),
+            })
+            .collect();
+        env.sort();
+        SystemSnapshot {
+            os: std::env::consts::OS,
+            os_version: os_version(),
+            arch: std::env::consts::ARCH,
+            cpus: std::thread::available_parallelism().map_or(1, |cpus| cpus.get()),
+            memory: memory(),
+            env,
+            args: args(),
+            cwd: std::env::current_dir().ok().map(|cwd| cwd.display().to_string()),
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("os: {} {}", self.os, self.arch)];
+        if let Some(version) = &self.os_version {
+            lines.push(format!("os version: {}", version));
+        }
+        lines.push(format!("cpus: {}", self.cpus));
+        if let Some((total, available)) = self.memory {
+            lines.push(format!("memory: {} MiB available of {} MiB", available >> 20, total >> 20));
+        }
+        lines.push(format!("args: {:?}", self.args));
+        if let Some(cwd) = &self.cwd {
+            lines.push(format!("cwd: {}", cwd));
+        }
+        lines.extend(self.env.iter().map(|(name, value)| format!("{}={}", name, value)));
+        lines
+    }
+}
+
+fn os_version() -> Option<String> {
+    let release = std::fs::read_to_string("/etc/os-release").ok().and_then(|release| {
+        let name = release.lines().find_map(|line| line.strip_prefix("PRETTY_NAME="))?;
+        Some(name.trim_matches('"').to_string())
+    });
+    let kernel = std::fs::read_to_string("/proc/sys/kernel/osrelease").ok().map(|kernel| kernel.trim().to_string());
+    match (release, kernel) {
+        (Some(release), Some(kernel)) => Some(format!("{} (kernel {})", release, kernel)),
+        (release, kernel) => release.or(kernel),
+    }
+}
+
+fn memory() -> Option<(u64, u64)> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let field = |name: &str| -> Option<u64> {
+        let line = meminfo.lines().find(|line| line.starts_with(name))?;
+        let kib: u64 = line[name.len()..].trim().trim_end_matches("kB").trim().parse().ok()?;
+        Some(kib * 1024)
+    };
+    Some((field("MemTotal:")?, field("MemAvailable:")?))
+}
+
+impl Report<'_> {
+    fn render(&self, f: &mut impl Write, color: bool) -> fmt::Result {
+        let deterministic = Deterministic::current();
+        let mut sections = vec![];
+        let mut error = vec![self.headline.clone()];
+        error.extend(
+            self.causes
+                .iter()
+                .enumerate()
+                .map(|(i, cause)| format!("{}: {}", paint(&format!("{:>3}", i), "2", color), cause)),
+        );
+        sections.push(Section { title: "Error", lines: error });
+        sections.push(Section { title: "Locations", lines: self.locations.clone() });
+        sections.push(Section { title: "Metadata", lines: self.meta.clone() });
+        if let Some(snippet) = self.snippet {
+            sections.push(Section { title: "Source", lines: snippet.render() });
+        }
+        sections.push(Section { title: "Help", lines: self.help.clone() });
+        if let Some(trace) = self.span_trace.filter(|trace| trace.status() == SpanTraceStatus::CAPTURED) {
+            sections.push(Section {
+                title: "Span trace",
+                lines: trace.to_string().lines().map(|line| line.to_string()).collect(),
+            });
+        }
+        let backtrace = self.backtrace.filter(|bt| !deterministic.is_on() && bt.status() == BacktraceStatus::Captured);
+        if let Some(backtrace) = backtrace {
+            sections.push(Section {
+                title: "Backtrace",
+                lines: backtrace.to_string().lines().map(|line| line.to_string()).collect(),
+            });
+        }
+        if !deterministic.is_on() {
+            sections.push(Section { title: "Environment", lines: SystemSnapshot::capture().lines() });
+        }
+
+        for section in sections.iter().filter(|section| !section.lines.is_empty()) {
+            writeln!(f, "{}", paint(&format!("━━ {} ━━", section.title), "1;31", color))?;
+            for line in &section.lines {
+                writeln!(f, "  {}", deterministic.normalize(line))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+
+    fn to_string(&self, color: bool) -> String {
+        let mut out = String::new();
+        let _ = self.render(&mut out, color);
+        out
+    }
+}
+
+fn help_lines(err: &Error) -> Vec<String> {
+    let help = crate::help(err).map(|help| format!("help: {}", help));
+    let doc_url = crate::doc_url(err).map(|url| format!("docs: {}", url));
+    help.into_iter().chain(doc_url).collect()
+}
+
+/// Render the error as a pretty report
+///
+///     use errlog::{wraperr, Result};
+///
+///     fn load() -> Result<String> {
+///         wraperr!(std::fs::read_to_string("/path/to/config"), "failed to load config")
+///     }
+///
+///     let report = errlog::report::pretty(&load().unwrap_err());
+///     assert!(report.contains("No such file"));
+///
+/// The [public message](crate::public_message) of the error, if any, is the headline followed
+/// by the whole chain. A [Snippet] attached to the error is shown in the `Source` section, the
+/// backtrace section is shown when the error captured one (see `RUST_BACKTRACE`), and the report
+/// is stabilized for snapshots in [deterministic](crate::logger::Deterministic) mode.
+pub fn pretty(err: &Error) -> String {
+    let mut messages = crate::chain_messages(err).into_iter();
+    let headline = match crate::public_message(err) {
+        Some(public) => public.to_string(),
+        None => messages.next().unwrap_or_default(),
+    };
+    #[cfg(feature = "anyhow")]
+    let backtrace = Some(err.backtrace());
+    #[cfg(feature = "eyre")]
+    let backtrace = err.handler().downcast_ref::<PrettyHandler>().map(|handler| &handler.backtrace);
+    #[cfg(feature = "eyre")]
+    let span_trace = err.handler().downcast_ref::<PrettyHandler>().map(|handler| &handler.span_trace);
+    #[cfg(feature = "anyhow")]
+    let span_trace = None;
+    let report = Report {
+        headline,
+        causes: messages.collect(),
+        locations: crate::location(err).map(|location| format!("{:?}", location)).into_iter().collect(),
+        meta: crate::meta(err).iter().map(|(key, value)| format!("{}: {}", key, value)).collect(),
+        snippet: err.downcast_ref::<Snippet>(),
+        help: help_lines(err),
+        span_trace,
+        backtrace,
+    };
+    report.to_string(use_color())
+}
+
+/// Report handler installed into eyre, capturing where and in which spans the report is created
+#[cfg(feature = "eyre")]
+struct PrettyHandler {
+    location: Option<&'static std::panic::Location<'static>>,
+    span_trace: SpanTrace,
+    backtrace: Backtrace,
+}
+
+#[cfg(feature = "eyre")]
+impl eyre::EyreHandler for PrettyHandler {
+    fn debug(&self, error: &(dyn std::error::Error + 'static), f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut messages = crate::limited_messages(std::iter::successors(Some(error), |err| err.source())).into_iter();
+        let headline = messages.next().unwrap_or_default();
+        let report = Report {
+            headline,
+            causes: messages.collect(),
+            locations: self.location.map(|location| location.to_string()).into_iter().collect(),
+            meta: vec![],
+            snippet: None,
+            help: vec![],
+            span_trace: Some(&self.span_trace),
+            backtrace: Some(&self.backtrace),
+        };
+        report.render(f, use_color())
+    }
+
+    fn track_caller(&mut self, location: &'static std::panic::Location<'static>) {
+        self.location = Some(location);
+    }
+}
+
+fn panic_report(info: &PanicHookInfo<'_>) -> String {
+    let message = crate::panic_message(info.payload());
+    let thread = std::thread::current();
+    let headline = format!("thread '{}' panicked: {}", thread.name().unwrap_or("<unnamed>"), message);
+    let locations = info.location().map(|location| location.to_string()).into_iter().collect();
+    let span_trace = SpanTrace::capture();
+    let backtrace = Backtrace::capture();
+    let report = Report {
+        headline,
+        causes: vec![],
+        locations,
+        meta: vec![],
+        snippet: None,
+        help: vec![],
+        span_trace: Some(&span_trace),
+        backtrace: Some(&backtrace),
+    };
+    report.to_string(use_color())
+}
+
+/// Install the panic hook and the error report hook printing pretty reports
+///
+///     errlog::install_pretty_reports();
+///
+/// Panics are printed as a report to stderr, and the [fatal hooks](crate::on_fatal) run when the
+/// main thread panics, except for the ones [caught](crate::catch_unwind_log) and logged. The panic
+/// hook installed before, the default one included, still runs after the report, and calling this
+/// again keeps the first hook. With the `eyre` feature, the report hook of eyre is
+/// installed too, so returning an error from `main` prints the same report. anyhow has no hook,
+/// print errors with [pretty] instead.
+///
+/// The span trace section requires `tracing_error::ErrorLayer` in the subscriber.
+pub fn install_pretty_reports() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !crate::unwind::catching() {
+                eprint!("{}", panic_report(info));
+                if std::thread::current().name() == Some("main") {
+                    crate::run_fatal_hooks();
+                }
+            }
+            previous(info);
+        }));
+    });
+    #[cfg(feature = "eyre")]
+    let _ = eyre::set_hook(Box::new(|_| {
+        Box::new(PrettyHandler {
+            location: None,
+            span_trace: SpanTrace::capture(),
+            backtrace: Backtrace::capture(),
+        })
+    }));
+}
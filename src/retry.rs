@@ -0,0 +1,292 @@
+//! Retry support for fallible operations
+
+use std::fmt;
+#[cfg(feature = "tokio")]
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::{Class, Error, Result};
+
+/// How long to wait before the next attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Wait the same delay after every failed attempt
+    Fixed(Duration),
+    /// Start from the delay and double it after every failed attempt
+    Exponential(Duration),
+}
+
+impl Backoff {
+    /// The delay after the given failed attempt, counting from 1
+    ///
+    ///     use errlog::retry::Backoff;
+    ///     use std::time::Duration;
+    ///
+    ///     let backoff = Backoff::Exponential(Duration::from_millis(100));
+    ///     assert_eq!(backoff.delay(1), Duration::from_millis(100));
+    ///     assert_eq!(backoff.delay(3), Duration::from_millis(400));
+    pub fn delay(&self, attempt: u32) -> Duration {
+        match *self {
+            Backoff::Fixed(delay) => delay,
+            Backoff::Exponential(base) => {
+                let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+                base.saturating_mul(factor)
+            }
+        }
+    }
+}
+
+/// Shorthands for the delays of a [Policy], such as `200.ms()`
+pub trait DurationExt {
+    fn ms(self) -> Duration;
+    fn secs(self) -> Duration;
+}
+
+impl DurationExt for u64 {
+    fn ms(self) -> Duration {
+        Duration::from_millis(self)
+    }
+
+    fn secs(self) -> Duration {
+        Duration::from_secs(self)
+    }
+}
+
+/// How many attempts [retry_with_log] makes and how long it waits between them
+///
+///     use errlog::retry::{DurationExt, Policy};
+///
+///     let policy = Policy::exponential(10, 100.ms()).max_delay(1.secs());
+///     assert_eq!(policy.delay(3), 400.ms());
+///     assert_eq!(policy.delay(8), 1.secs());
+///
+///     let policy = Policy::exponential(10, 100.ms()).with_jitter().budget(30.secs());
+///     assert!((200.ms()..=400.ms()).contains(&policy.delay(3)));
+#[derive(Debug, Clone, Copy)]
+pub struct Policy {
+    /// How many attempts in total, at least 1
+    pub attempts: u32,
+    pub backoff: Backoff,
+    /// Pick every delay at random between half and all of it
+    pub jitter: bool,
+    /// The longest delay between two attempts
+    pub max_delay: Option<Duration>,
+    /// Give up when the next attempt would start later than this after the first one
+    pub budget: Option<Duration>,
+    /// Which errors are retried, by default the ones not [classified](crate::classify) as
+    /// [Permanent](crate::Class::Permanent)
+    pub retry_if: Option<fn(&Error) -> bool>,
+}
+
+impl Policy {
+    /// Wait the same delay after every failed attempt
+    pub fn fixed(attempts: u32, delay: Duration) -> Self {
+        Policy::new(attempts, Backoff::Fixed(delay))
+    }
+
+    /// Start from the delay and double it after every failed attempt
+    pub fn exponential(attempts: u32, base: Duration) -> Self {
+        Policy::new(attempts, Backoff::Exponential(base))
+    }
+
+    /// Make `attempts` attempts with the `backoff`
+    pub fn new(attempts: u32, backoff: Backoff) -> Self {
+        Policy { attempts: attempts.max(1), backoff, jitter: false, max_delay: None, budget: None, retry_if: None }
+    }
+
+    /// Spread the delays, so that clients failing together do not retry together
+    pub fn with_jitter(mut self) -> Self {
+        self.jitter = true;
+        self
+    }
+
+    /// Cap every delay to `max_delay`
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    /// Only retry the errors for which `retry_if` is true
+    pub fn retry_if(mut self, retry_if: fn(&Error) -> bool) -> Self {
+        self.retry_if = Some(retry_if);
+        self
+    }
+
+    /// Stop retrying once `budget` has elapsed since the first attempt
+    pub fn budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// The delay after the given failed attempt, counting from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let mut delay = self.backoff.delay(attempt);
+        if let Some(max_delay) = self.max_delay {
+            delay = delay.min(max_delay);
+        }
+        if self.jitter {
+            let half = delay / 2;
+            let spread = (delay - half).as_nanos() as u64;
+            delay = half + Duration::from_nanos(crate::trace::random() % spread.saturating_add(1));
+        }
+        delay
+    }
+}
+
+/// The summary context of the error returned by [retry_with_log], keeping the errors of the
+/// earlier attempts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attempts {
+    /// How many attempts were made
+    pub count: u32,
+    /// The chains of the errors of the attempts before the last one, oldest first
+    pub errors: Vec<String>,
+}
+
+impl fmt::Display for Attempts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed after {} attempt(s)", self.count)
+    }
+}
+
+/// The summary of the attempts made for the error, see [retry_with_log]
+pub fn attempts(err: &Error) -> Option<&Attempts> {
+    err.downcast_ref::<Attempts>()
+}
+
+/// Run the operation until it succeeds or the policy gives up, logging every failed attempt
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::retry::{self, DurationExt, Policy};
+///     use errlog::{Class, ClassExt};
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///
+///     let mut calls = 0;
+///     let err = errlog::retry_with_log(Policy::fixed(3, 1.ms()), || {
+///         calls += 1;
+///         Err::<(), _>(errlog::Error::msg(format!("busy #{}", calls)))
+///     })
+///     .unwrap_err();
+///     assert_eq!(err.to_string(), "failed after 3 attempt(s)");
+///     assert_eq!(retry::attempts(&err).unwrap().errors, ["busy #1", "busy #2"]);
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("attempt 2/3 failed, retrying in 1ms: busy #2"));
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("delay_ms=1 elapsed_ms="));
+///
+///     let err = errlog::retry_with_log(Policy::fixed(3, 1.ms()), || {
+///         Err::<(), _>(errlog::Error::msg("card declined")).with_class(Class::Permanent)
+///     })
+///     .unwrap_err();
+///     assert_eq!(retry::attempts(&err).unwrap().count, 1);
+///
+/// Errors [classified](crate::classify) as [Permanent](crate::Class::Permanent), or rejected by
+/// [retry_if](Policy::retry_if), are not retried, nor are they once the [budget](Policy::budget)
+/// would be exceeded. Every failed attempt but the
+/// last is logged at `WARN` with the location of the call and the `attempt`, `delay_ms` and
+/// `elapsed_ms` fields, and the last error is returned wrapped with [Attempts].
+#[track_caller]
+pub fn retry_with_log<T, F>(policy: Policy, mut op: F) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let location = std::panic::Location::caller();
+    let started = Instant::now();
+    let mut errors = vec![];
+    loop {
+        let err = match op() {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+        match next_delay(&policy, location, started, &mut errors, err) {
+            Ok(delay) => std::thread::sleep(delay),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// The async version of [retry_with_log], sleeping through tokio
+///
+///     use errlog::retry::{DurationExt, Policy};
+///
+///     # #[tokio::main(flavor = "current_thread")]
+///     # async fn main() {
+///     let mut calls = 0;
+///     let value = errlog::retry_with_log_async(Policy::exponential(3, 1.ms()), || {
+///         calls += 1;
+///         let calls = calls;
+///         async move {
+///             match calls {
+///                 1 => Err(errlog::Error::msg("busy")),
+///                 _ => Ok(calls),
+///             }
+///         }
+///     })
+///     .await
+///     .unwrap();
+///     assert_eq!(value, 2);
+///     # }
+#[cfg(feature = "tokio")]
+#[track_caller]
+pub fn retry_with_log_async<T, F, Fut>(policy: Policy, mut op: F) -> impl Future<Output = Result<T>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let location = std::panic::Location::caller();
+    async move {
+        let started = Instant::now();
+        let mut errors = vec![];
+        loop {
+            let err = match op().await {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            match next_delay(&policy, location, started, &mut errors, err) {
+                Ok(delay) => tokio::time::sleep(delay).await,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Log the failed attempt and tell how long to wait, or the final error when giving up
+fn next_delay(
+    policy: &Policy,
+    location: &std::panic::Location<'_>,
+    started: Instant,
+    errors: &mut Vec<String>,
+    err: Error,
+) -> Result<Duration> {
+    let attempt = errors.len() as u32 + 1;
+    let delay = policy.delay(attempt);
+    let elapsed = started.elapsed();
+    let over_budget = policy.budget.is_some_and(|budget| elapsed + delay > budget);
+    let retryable = match policy.retry_if {
+        Some(retry_if) => retry_if(&err),
+        None => crate::classify(&err) != Class::Permanent,
+    };
+    if attempt >= policy.attempts || over_budget || !retryable {
+        let errors = std::mem::take(errors);
+        return Err(crate::__private::context(err, Attempts { count: attempt, errors }));
+    }
+    let chain = crate::__private::chain_string(&err);
+    if !crate::__private::SILENT {
+        tracing::warn!(
+            attempt,
+            delay_ms = delay.as_millis() as u64,
+            elapsed_ms = elapsed.as_millis() as u64,
+            "{}:{} => attempt {}/{} failed, retrying in {:?}: {}",
+            location.file(),
+            location.line(),
+            attempt,
+            policy.attempts,
+            delay,
+            chain,
+        );
+    }
+    errors.push(chain);
+    Ok(delay)
+}
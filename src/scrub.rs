@@ -0,0 +1,135 @@
+//! Scrubbing personal data from rendered error chains
+//!
+//!     use errlog::scrub::{self, Scrubber};
+//!     use errlog::Context;
+//!
+//!     let scrubber = Scrubber::new().emails().credit_cards().ips();
+//!     scrub::install(scrubber.pattern(r"user-\d+", "[user]").unwrap());
+//!
+//!     let charge = "charge of 4111 1111 1111 1111 for bob@example.com from 10.0.0.7 failed";
+//!     let err = Err::<(), _>(errlog::Error::msg(charge)).context("order of user-42 failed").unwrap_err();
+//!     let report = errlog::ErrorReport::new(&err);
+//!     assert_eq!(report.message, "order of [user] failed");
+//!     assert_eq!(report.causes[0], "charge of [card] for [email] from [ip] failed");
+//!
+//! The installed scrubber applies to the messages rendered by errlog: the error fields of the
+//! [logger](crate::logger), the [pretty](crate::report::pretty) reports, the
+//! [ErrorReport](crate::ErrorReport) and the chains of the macros. It does not change the
+//! errors themselves, nor the messages of events.
+
+use std::borrow::Cow;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::RwLock;
+
+use regex::{Captures, Regex};
+
+use crate::Result;
+
+enum Check {
+    Always,
+    Luhn,
+    Ip,
+}
+
+struct Rule {
+    regex: Regex,
+    replacement: String,
+    check: Check,
+}
+
+/// The patterns replaced in rendered messages, see [install]
+#[derive(Default)]
+pub struct Scrubber {
+    rules: Vec<Rule>,
+}
+
+impl Scrubber {
+    pub fn new() -> Self {
+        Scrubber::default()
+    }
+
+    /// Replace email addresses by `[email]`
+    pub fn emails(self) -> Self {
+        self.rule(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}", "[email]", Check::Always)
+    }
+
+    /// Replace card numbers passing the Luhn check by `[card]`
+    pub fn credit_cards(self) -> Self {
+        self.rule(r"\b(?:\d[ -]?){12,18}\d\b", "[card]", Check::Luhn)
+    }
+
+    /// Replace IPv4 and IPv6 addresses by `[ip]`
+    pub fn ips(self) -> Self {
+        self.rule(r"\b(?:\d{1,3}\.){3}\d{1,3}\b|[0-9A-Fa-f]{0,4}(?::[0-9A-Fa-f]{0,4}){2,7}", "[ip]", Check::Ip)
+    }
+
+    /// Replace the matches of `regex` by `replacement`, which may refer to groups like `$1`
+    pub fn pattern(mut self, regex: &str, replacement: &str) -> Result<Self> {
+        let regex = Regex::new(regex).map_err(|err| crate::Error::msg(format!("invalid scrub pattern: {}", err)))?;
+        self.rules.push(Rule { regex, replacement: replacement.to_string(), check: Check::Always });
+        Ok(self)
+    }
+
+    fn rule(mut self, regex: &str, replacement: &str, check: Check) -> Self {
+        let regex = Regex::new(regex).expect("invalid built-in scrub pattern");
+        self.rules.push(Rule { regex, replacement: replacement.to_string(), check });
+        self
+    }
+
+    /// `text` with every rule applied in order
+    pub fn scrub<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for rule in &self.rules {
+            let replaced = rule.regex.replace_all(&text, |captures: &Captures<'_>| {
+                let found = &captures[0];
+                let matches = match rule.check {
+                    Check::Always => true,
+                    Check::Luhn => luhn(found),
+                    Check::Ip => found.parse::<Ipv4Addr>().is_ok() || found.parse::<Ipv6Addr>().is_ok(),
+                };
+                match matches {
+                    true => {
+                        let mut replacement = String::new();
+                        captures.expand(&rule.replacement, &mut replacement);
+                        replacement
+                    }
+                    false => found.to_string(),
+                }
+            });
+            if let Cow::Owned(replaced) = replaced {
+                text = Cow::Owned(replaced);
+            }
+        }
+        text
+    }
+}
+
+fn luhn(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| match index % 2 {
+            1 if digit * 2 > 9 => digit * 2 - 9,
+            1 => digit * 2,
+            _ => *digit,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+static SCRUBBER: RwLock<Option<Scrubber>> = RwLock::new(None);
+
+/// Scrub the messages rendered by errlog with `scrubber` from now on
+pub fn install(scrubber: Scrubber) {
+    *SCRUBBER.write().unwrap_or_else(|err| err.into_inner()) = Some(scrubber);
+}
+
+/// `text` scrubbed by the installed scrubber, if any
+pub(crate) fn apply(text: String) -> String {
+    match &*SCRUBBER.read().unwrap_or_else(|err| err.into_inner()) {
+        Some(scrubber) => scrubber.scrub(&text).into_owned(),
+        None => text,
+    }
+}
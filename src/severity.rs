@@ -0,0 +1,25 @@
+//! The severity an error was raised with
+
+use std::fmt;
+
+use tracing::Level;
+
+use crate::Error;
+
+/// The level an error was raised with, attached by [bail_with_level](crate::bail_with_level)
+///
+/// Top-level handlers read it back with [severity] to tell expected, benign failures (`WARN` and
+/// below) from real ones. Like [Code](crate::Code), it renders as an empty string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Severity(pub Level);
+
+impl fmt::Display for Severity {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// The outermost severity attached to the error
+pub fn severity(err: &Error) -> Option<Level> {
+    err.downcast_ref::<Severity>().map(|severity| severity.0)
+}
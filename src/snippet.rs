@@ -0,0 +1,71 @@
+//! Source snippets shown by the pretty reports
+
+use std::fmt;
+use std::path::PathBuf;
+
+/// A position in a source file with a label, attached as a context
+///
+/// [pretty](crate::report::pretty) reads the file back when rendering and shows the offending
+/// line with a caret under the column
+///
+///     use errlog::{Context, Result};
+///
+///     fn parse_port(path: &str, text: &str) -> Result<u16> {
+///         text.trim()
+///             .parse()
+///             .context(errlog::snippet(path, 1, 1, "expected a port number"))
+///     }
+///
+///     let err = parse_port("/path/to/port.conf", "abc").unwrap_err();
+///     assert_eq!(err.to_string(), "expected a port number at /path/to/port.conf:1:1");
+///
+/// Lines and columns are 1-based, columns count characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub path: PathBuf,
+    pub line: usize,
+    pub col: usize,
+    pub label: String,
+}
+
+/// Build a [Snippet]
+pub fn snippet(path: impl Into<PathBuf>, line: usize, col: usize, label: impl Into<String>) -> Snippet {
+    Snippet { path: path.into(), line, col, label: label.into() }
+}
+
+impl fmt::Display for Snippet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}:{}:{}", self.label, self.path.display(), self.line, self.col)
+    }
+}
+
+impl Snippet {
+    /// Render the location, the source line and the caret, the source is omitted when the file
+    /// cannot be read
+    ///
+    /// ```text
+    /// --> config.toml:3:8
+    ///   |
+    /// 3 | port = abc
+    ///   |        ^ expected a port number
+    /// ```
+    pub fn render(&self) -> Vec<String> {
+        let mut lines = vec![format!("--> {}:{}:{}", self.path.display(), self.line, self.col)];
+        let source = std::fs::read_to_string(&self.path).ok();
+        let Some(text) = source.as_deref().and_then(|text| text.lines().nth(self.line.saturating_sub(1))) else {
+            lines.push(format!("= {}", self.label));
+            return lines;
+        };
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let padding: String = text
+            .chars()
+            .take(self.col.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        lines.push(format!("{} |", gutter));
+        lines.push(format!("{} | {}", number, text));
+        lines.push(format!("{} | {}^ {}", gutter, padding, self.label));
+        lines
+    }
+}
@@ -0,0 +1,59 @@
+//! The tracing span an error was wrapped in
+
+use std::fmt;
+
+use crate::Error;
+
+/// The span which was current when [wraperr](crate::wraperr) wrapped the error
+///
+/// wraperr also adds it to its event as the `span` field, `name#id`, so the error logged again
+/// by a top-level handler, outside of the span, can be matched to the request it came from
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::{wraperr, Result};
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///
+///     fn handle() -> Result<()> {
+///         let _span = errlog::tracing::info_span!("handle").entered();
+///         wraperr!(Err(errlog::Error::msg("boom")))
+///     }
+///
+///     let span = errlog::error_span(&handle().unwrap_err()).unwrap();
+///     assert_eq!(span.name, "handle");
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains(&format!("span={}", span.field())));
+///
+/// Like [Code](crate::Code), it renders as an empty string. Span ids are only unique among the
+/// spans open at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorSpan {
+    pub id: u64,
+    pub name: &'static str,
+}
+
+impl ErrorSpan {
+    /// The current span, if any
+    pub fn current() -> Option<Self> {
+        let span = tracing::Span::current();
+        let id = span.id()?;
+        Some(ErrorSpan { id: id.into_u64(), name: span.metadata()?.name() })
+    }
+
+    /// `name#id`, as in the `span` field
+    pub fn field(&self) -> String {
+        format!("{}#{}", self.name, self.id)
+    }
+}
+
+impl fmt::Display for ErrorSpan {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// The span of the outermost [wraperr](crate::wraperr) of the error which ran in one
+pub fn error_span(err: &Error) -> Option<ErrorSpan> {
+    err.downcast_ref::<ErrorSpan>().copied()
+}
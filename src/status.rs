@@ -0,0 +1,45 @@
+//! HTTP statuses suggested by errors
+
+use std::fmt;
+
+use crate::{Error, Result};
+
+/// The HTTP status suggested for the response, see [StatusExt]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HttpStatus(pub u16);
+
+impl fmt::Display for HttpStatus {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+/// Suggest the HTTP status of the response from deep in the stack
+///
+///     use errlog::{Result, StatusExt};
+///
+///     fn reserve(seat: u32) -> Result<()> {
+///         Err(errlog::Error::msg(format!("seat {} is taken", seat))).with_status(409u16)
+///     }
+///
+///     assert_eq!(errlog::http_status(&reserve(7).unwrap_err()), Some(409));
+///
+/// It accepts the `StatusCode` of the http crate as well. The web integrations, the gRPC codes
+/// and [ErrorReport](crate::ErrorReport) honor it over the status of the
+/// [ErrorCode](crate::ErrorCode).
+pub trait StatusExt<T> {
+    fn with_status(self, status: impl Into<u16>) -> Result<T>;
+}
+
+impl<T, E: Into<Error>> StatusExt<T> for std::result::Result<T, E> {
+    fn with_status(self, status: impl Into<u16>) -> Result<T> {
+        self.map_err(|err| crate::__private::context(err.into(), HttpStatus(status.into())))
+    }
+}
+
+/// The outermost status attached with [StatusExt], or the one of the error code
+pub fn http_status(err: &Error) -> Option<u16> {
+    err.downcast_ref::<HttpStatus>()
+        .map(|status| status.0)
+        .or_else(|| crate::find_error_code(err).and_then(|code| code.http_status()))
+}
@@ -0,0 +1,234 @@
+//! Helpers for tokio tasks
+
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::task::{JoinError, JoinHandle};
+
+use crate::{Error, Panicked, Result};
+
+/// The error of a task which panicked, keeping the panic payload
+pub struct TaskPanic {
+    message: String,
+    payload: Mutex<Option<Box<dyn Any + Send>>>,
+}
+
+impl TaskPanic {
+    /// The panic message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Take the panic payload, for example to resume the panic with `std::panic::resume_unwind`
+    pub fn take_payload(&self) -> Option<Box<dyn Any + Send>> {
+        self.payload.lock().ok().and_then(|mut payload| payload.take())
+    }
+}
+
+impl fmt::Debug for TaskPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskPanic").field("message", &self.message).finish()
+    }
+}
+
+impl fmt::Display for TaskPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "task panicked: {}", self.message)
+    }
+}
+
+impl std::error::Error for TaskPanic {}
+
+/// Flatten the result of awaiting a `JoinHandle`
+///
+/// An error returned by the task is passed through, while a panic (or a cancellation) becomes an
+/// error logged at `ERROR` with the location of the `flatten` call. The panic payload is kept in
+/// [TaskPanic]
+///
+///     use errlog::task::{flatten, TaskPanic};
+///
+///     # #[tokio::main(flavor = "current_thread")]
+///     # async fn main() {
+///     let handle = tokio::spawn(async { errlog::Result::<u32>::Ok(42) });
+///     assert_eq!(flatten(handle.await).unwrap(), 42);
+///
+///     let handle = tokio::spawn(async {
+///         if true {
+///             panic!("boom");
+///         }
+///         errlog::Result::<u32>::Ok(42)
+///     });
+///     let err = flatten(handle.await).unwrap_err();
+///     assert_eq!(err.downcast_ref::<TaskPanic>().unwrap().message(), "boom");
+///     # }
+#[track_caller]
+pub fn flatten<T>(result: std::result::Result<Result<T>, JoinError>) -> Result<T> {
+    let err = match result {
+        Ok(result) => return result,
+        Err(err) => err,
+    };
+    let location = Location::caller();
+    let err = if err.is_panic() {
+        let payload = err.into_panic();
+        Error::new(TaskPanic {
+            message: crate::panic_message(payload.as_ref()),
+            payload: Mutex::new(Some(payload)),
+        })
+    } else {
+        Error::new(err)
+    };
+    if !crate::__private::SILENT {
+        tracing::error!("{}:{} => {}", location.file(), location.line(), err);
+    }
+    Err(err)
+}
+
+/// Await the future for at most `timeout`, turning the elapse into a logged error
+///
+///     use std::time::Duration;
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::Class;
+///
+///     # #[tokio::main(flavor = "current_thread")]
+///     # async fn main() {
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///
+///     let fetch = async {
+///         tokio::time::sleep(Duration::from_secs(60)).await;
+///         errlog::Result::<u32>::Ok(42)
+///     };
+///     let err = errlog::with_timeout_log(Duration::from_millis(10), "fetch manifest", fetch).await.unwrap_err();
+///     assert_eq!(err.to_string(), "fetch manifest timed out after 10ms");
+///     assert_eq!(errlog::classify(&err), Class::Transient);
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("fetch manifest timed out after 10ms"));
+///     # }
+///
+/// The error is logged at `ERROR` with the location of the call and the `timeout_ms` field. Its
+/// source is tokio's `Elapsed`, so it is [classified](crate::classify) as transient and retried
+/// by [retry_with_log_async](crate::retry_with_log_async).
+#[track_caller]
+pub fn with_timeout_log<T, F>(timeout: Duration, label: &str, fut: F) -> impl Future<Output = Result<T>>
+where
+    F: Future<Output = Result<T>>,
+{
+    let location = Location::caller();
+    let label = label.to_string();
+    async move {
+        match tokio::time::timeout(timeout, fut).await {
+            Ok(result) => result,
+            Err(elapsed) => {
+                let err = crate::__private::context(
+                    Error::new(elapsed),
+                    format!("{} timed out after {:?}", label, timeout),
+                );
+                if !crate::__private::SILENT {
+                    tracing::error!(
+                        timeout_ms = timeout.as_millis() as u64,
+                        "{}:{} => {}",
+                        location.file(),
+                        location.line(),
+                        err,
+                    );
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+static FAILED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The names of the tasks spawned by [spawn_logged] which failed so far, for health checks
+pub fn failed_tasks() -> Vec<String> {
+    FAILED.lock().unwrap_or_else(|err| err.into_inner()).clone()
+}
+
+/// Polls the inner future, catching its panics
+struct CatchUnwind<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for CatchUnwind<F> {
+    type Output = std::result::Result<F::Output, Panicked>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let fut = self.0.as_mut();
+        match crate::unwind::catch(|| fut.poll(cx)) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(panicked) => Poll::Ready(Err(panicked)),
+        }
+    }
+}
+
+/// Spawn a tokio task whose failure is logged rather than left in its `JoinHandle`
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::Panicked;
+///
+///     # #[tokio::main(flavor = "current_thread")]
+///     # async fn main() {
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///
+///     let handle = errlog::spawn_logged("indexer", async { Err::<(), _>(errlog::Error::msg("disk full")) });
+///     assert_eq!(handle.await.unwrap().unwrap_err().to_string(), "disk full");
+///
+///     let handle = errlog::spawn_logged("thumbnailer", async {
+///         if true {
+///             panic!("bad image");
+///         }
+///         errlog::Result::<()>::Ok(())
+///     });
+///     let err = handle.await.unwrap().unwrap_err();
+///     assert_eq!(err.downcast_ref::<Panicked>().unwrap().message(), "bad image");
+///
+///     assert_eq!(errlog::task::failed_tasks(), ["indexer", "thumbnailer"]);
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("task indexer failed: disk full"));
+///     # }
+///
+/// The error returned or the panic is logged at `ERROR` with the location of the spawn call and
+/// the `task` field, through the subscriber of the spawning thread, and the name is added to
+/// [failed_tasks]. The panic is returned as a [Panicked] error instead of being printed.
+#[track_caller]
+pub fn spawn_logged<T, F>(name: &str, fut: F) -> JoinHandle<Result<T>>
+where
+    T: Send + 'static,
+    F: Future<Output = Result<T>> + Send + 'static,
+{
+    let location = Location::caller();
+    let name = name.to_string();
+    let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+    tokio::spawn(async move {
+        let err = match CatchUnwind(Box::pin(fut)).await {
+            Ok(Ok(value)) => return Ok(value),
+            Ok(Err(err)) => err,
+            Err(panicked) => Error::new(panicked),
+        };
+        if !crate::__private::SILENT {
+            tracing::dispatcher::with_default(&dispatch, || {
+                tracing::error!(
+                    task = name.as_str(),
+                    "{}:{} => task {} failed: {}",
+                    location.file(),
+                    location.line(),
+                    name,
+                    crate::__private::chain_string(&err),
+                );
+            });
+        }
+        let mut failed = FAILED.lock().unwrap_or_else(|err| err.into_inner());
+        if !failed.contains(&name) {
+            failed.push(name);
+        }
+        Err(err)
+    })
+}
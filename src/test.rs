@@ -0,0 +1,190 @@
+//! Capture tracing events in tests
+//!
+//!     use errlog::{logmsg, wraperr, Result};
+//!
+//!     fn connect() -> Result<()> {
+//!         let err = std::io::Error::from(std::io::ErrorKind::TimedOut);
+//!         wraperr!(Err::<(), _>(err), "connection timeout after {}s", 5)
+//!     }
+//!
+//!     let capture = errlog::test::capture();
+//!     logmsg!(INFO, "connecting");
+//!     let _ = connect();
+//!
+//!     let events = capture.events();
+//!     assert_eq!(events.len(), 2);
+//!     assert_eq!(events.errors().len(), 1);
+//!     assert_eq!(events.matching("timeout").len(), 1);
+//!     assert_eq!(events[0].message, "connecting");
+//!
+//! The subscriber is the default of the current thread until the [Capture] is dropped, instrument
+//! futures with [Capture::instrument] to capture the events of tasks running on other threads.
+
+use std::fmt;
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+use tracing::instrument::{WithDispatch, WithSubscriber};
+use tracing::subscriber::DefaultGuard;
+use tracing::{Dispatch, Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context as LayerContext, Layer, SubscriberExt};
+
+use crate::fields::FieldVisitor;
+
+/// A recorded event
+#[derive(Debug, Clone)]
+pub struct CapturedEvent {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    /// The fields other than the message, in order, formatted with `Debug` unless they are strings
+    pub fields: Vec<(String, String)>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl CapturedEvent {
+    /// The value of the field `name`
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+impl fmt::Display for CapturedEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:>5} {}: {}", self.level, self.target, self.message)?;
+        for (name, value) in &self.fields {
+            write!(f, " {}={}", name, value)?;
+        }
+        if let (Some(file), Some(line)) = (&self.file, self.line) {
+            write!(f, " ({}:{})", file, line)?;
+        }
+        Ok(())
+    }
+}
+
+/// A list of recorded events, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct Events(Vec<CapturedEvent>);
+
+impl Events {
+    fn filter(&self, f: impl Fn(&CapturedEvent) -> bool) -> Events {
+        Events(self.0.iter().filter(|event| f(event)).cloned().collect())
+    }
+
+    /// The events at `level`
+    pub fn at_level(&self, level: Level) -> Events {
+        self.filter(|event| event.level == level)
+    }
+
+    /// The `ERROR` events
+    pub fn errors(&self) -> Events {
+        self.at_level(Level::ERROR)
+    }
+
+    /// The `WARN` events
+    pub fn warnings(&self) -> Events {
+        self.at_level(Level::WARN)
+    }
+
+    /// The events whose message or field values contain `pattern`
+    pub fn matching(&self, pattern: &str) -> Events {
+        self.filter(|event| {
+            event.message.contains(pattern) || event.fields.iter().any(|(_, value)| value.contains(pattern))
+        })
+    }
+
+    /// The events whose target starts with `target`
+    pub fn with_target(&self, target: &str) -> Events {
+        self.filter(|event| event.target.starts_with(target))
+    }
+}
+
+impl Deref for Events {
+    type Target = [CapturedEvent];
+
+    fn deref(&self) -> &[CapturedEvent] {
+        &self.0
+    }
+}
+
+impl IntoIterator for Events {
+    type Item = CapturedEvent;
+    type IntoIter = std::vec::IntoIter<CapturedEvent>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+struct CaptureLayer {
+    events: Arc<Mutex<Vec<CapturedEvent>>>,
+}
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _: LayerContext<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let captured = CapturedEvent {
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            message: visitor.message,
+            fields: visitor.fields,
+            file: metadata.file().map(|file| file.to_string()),
+            line: metadata.line(),
+        };
+        self.events.lock().unwrap_or_else(|err| err.into_inner()).push(captured);
+    }
+}
+
+/// A capturing subscriber, see [capture]
+pub struct Capture {
+    events: Arc<Mutex<Vec<CapturedEvent>>>,
+    dispatch: Dispatch,
+    _guard: DefaultGuard,
+}
+
+impl Capture {
+    /// The events recorded so far
+    pub fn events(&self) -> Events {
+        Events(self.events.lock().unwrap_or_else(|err| err.into_inner()).clone())
+    }
+
+    /// Forget the events recorded so far
+    pub fn clear(&self) {
+        self.events.lock().unwrap_or_else(|err| err.into_inner()).clear();
+    }
+
+    /// Record the events of `future` wherever it is polled
+    ///
+    ///     # #[tokio::main(flavor = "current_thread")]
+    ///     # async fn main() {
+    ///     let capture = errlog::test::capture();
+    ///     let task = capture.instrument(async { errlog::logmsg!(WARN, "slow request") });
+    ///     tokio::spawn(task).await.unwrap();
+    ///     assert_eq!(capture.events().warnings().len(), 1);
+    ///     # }
+    pub fn instrument<F: Future>(&self, future: F) -> WithDispatch<F> {
+        future.with_subscriber(self.dispatch.clone())
+    }
+
+    /// The dispatcher of the capturing subscriber
+    pub fn dispatch(&self) -> &Dispatch {
+        &self.dispatch
+    }
+}
+
+/// Install a capturing subscriber as the default of the current thread, see the [module](self)
+/// documentation
+pub fn capture() -> Capture {
+    let events = Arc::new(Mutex::new(vec![]));
+    let subscriber = tracing_subscriber::registry().with(CaptureLayer { events: events.clone() });
+    let dispatch = Dispatch::new(subscriber);
+    let guard = tracing::dispatcher::set_default(&dispatch);
+    Capture { events, dispatch, _guard: guard }
+}
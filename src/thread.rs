@@ -0,0 +1,105 @@
+//! Named threads whose panics go through tracing
+//!
+//!     use errlog::logger::{self, MemoryWriter};
+//!     use errlog::Panicked;
+//!
+//!     let output = MemoryWriter::new();
+//!     let _guard = logger::builder().writer(output.clone()).set_default();
+//!
+//!     let worker = errlog::thread::spawn_named("worker-3", || 6 * 7);
+//!     assert_eq!(worker.join().unwrap(), 42);
+//!
+//!     let worker = errlog::thread::spawn_named("worker-4", || -> u32 { panic!("queue poisoned") });
+//!     let err = worker.join().unwrap_err();
+//!     assert_eq!(err.downcast_ref::<Panicked>().unwrap().message(), "queue poisoned");
+//!     # #[cfg(not(feature = "silent"))]
+//!     assert!(output.contents().contains("thread worker-3 started"));
+//!     # #[cfg(not(feature = "silent"))]
+//!     assert!(output.contents().contains("thread worker-4 panicked at"));
+//!
+//! The thread runs with the subscriber and the [context](crate::context) of the spawning thread.
+//! Its start and stop are logged at `INFO`, its panic at `ERROR` with the backtrace when enabled
+//! by `RUST_BACKTRACE`, all with the `thread` field. The panic is not printed by the panic hook.
+
+use std::panic::Location;
+use std::thread;
+use std::time::Instant;
+
+use crate::{Error, Result};
+
+/// The handle of a thread spawned by [spawn_named]
+#[derive(Debug)]
+pub struct JoinHandle<T> {
+    inner: thread::JoinHandle<Result<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Wait for the thread, its panic being a [Panicked](crate::Panicked) error
+    pub fn join(self) -> Result<T> {
+        match self.inner.join() {
+            Ok(result) => result,
+            // the panic is caught in the thread, unless the logging itself panicked
+            Err(payload) => Err(Error::msg(crate::panic_message(payload.as_ref()))),
+        }
+    }
+
+    pub fn thread(&self) -> &thread::Thread {
+        self.inner.thread()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.inner.is_finished()
+    }
+}
+
+/// Spawn a thread named `name` running `f`, see the [module](self) documentation
+///
+/// Like `std::thread::spawn`, it panics if the thread cannot be spawned.
+#[track_caller]
+pub fn spawn_named<F, T>(name: &str, f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let location = Location::caller();
+    let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+    let snapshot = crate::context::snapshot();
+    let name = name.to_string();
+    let inner = thread::Builder::new()
+        .name(name.clone())
+        .spawn(move || {
+            tracing::dispatcher::with_default(&dispatch, || {
+                let _context = crate::context::attach(snapshot);
+                run(&name, location, f)
+            })
+        })
+        .expect("failed to spawn thread");
+    JoinHandle { inner }
+}
+
+fn run<F, T>(name: &str, location: &Location<'_>, f: F) -> Result<T>
+where
+    F: FnOnce() -> T,
+{
+    let silent = crate::__private::SILENT;
+    if !silent {
+        tracing::info!(thread = name, "{}:{} => thread {} started", location.file(), location.line(), name);
+    }
+    let started = Instant::now();
+    let panicked = match crate::unwind::catch(f) {
+        Ok(value) => {
+            if !silent {
+                tracing::info!(thread = name, "thread {} stopped after {:?}", name, started.elapsed());
+            }
+            return Ok(value);
+        }
+        Err(panicked) => panicked,
+    };
+    if !silent {
+        match panicked.backtrace() {
+            Some(backtrace) => tracing::error!(thread = name, "thread {} {}\n{}", name, panicked, backtrace),
+            None => tracing::error!(thread = name, "thread {} {}", name, panicked),
+        }
+    }
+    Err(crate::context::wrap(Error::new(panicked)))
+}
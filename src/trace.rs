@@ -0,0 +1,182 @@
+//! W3C `traceparent` propagation without an OpenTelemetry SDK
+//!
+//!     use std::collections::HashMap;
+//!
+//!     use errlog::logger::{self, MemoryWriter};
+//!     use errlog::trace;
+//!
+//!     let output = MemoryWriter::new();
+//!     let _guard = logger::builder().writer(output.clone()).set_default();
+//!     let mut incoming = HashMap::new();
+//!     incoming.insert(
+//!         "traceparent".to_string(),
+//!         "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01".to_string(),
+//!     );
+//!
+//!     let parent = trace::extract(&incoming).unwrap();
+//!     let _span = parent.child().enter();
+//!     errlog::logmsg!(INFO, "handling the request");
+//!
+//!     let mut outgoing = HashMap::new();
+//!     trace::inject(&mut outgoing);
+//!     assert!(outgoing["traceparent"].starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+//!     # #[cfg(not(feature = "silent"))]
+//!     assert!(output.contents().contains("trace_id=4bf92f3577b34da6a3ce929d0e0e4736 span_id="));
+//!
+//! While the guard of [TraceContext::enter] lives, the `trace_id` and `span_id` fields are added to
+//! the events and errors of the thread like any other [context](crate::context) entry.
+
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(feature = "axum")]
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+#[cfg(all(feature = "reqwest", not(feature = "axum")))]
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::context::{self, ContextGuard, Snapshot};
+
+/// The header carrying the trace context
+pub const TRACEPARENT: &str = "traceparent";
+
+/// Headers the trace context is read from and written to
+pub trait Headers {
+    fn header(&self, name: &str) -> Option<&str>;
+    fn set_header(&mut self, name: &str, value: String);
+}
+
+impl Headers for HashMap<String, String> {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    fn set_header(&mut self, name: &str, value: String) {
+        self.insert(name.to_string(), value);
+    }
+}
+
+#[cfg(any(feature = "axum", feature = "reqwest"))]
+impl Headers for HeaderMap {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    fn set_header(&mut self, name: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (name.parse::<HeaderName>(), HeaderValue::from_str(&value)) {
+            self.insert(name, value);
+        }
+    }
+}
+
+/// A position in a distributed trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 32 lowercase hex digits
+    pub trace_id: String,
+    /// 16 lowercase hex digits
+    pub span_id: String,
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Start a new trace
+    pub fn new() -> Self {
+        TraceContext {
+            trace_id: format!("{:016x}{:016x}", random(), random()),
+            span_id: format!("{:016x}", random()),
+            sampled: true,
+        }
+    }
+
+    /// A new span of the same trace, like the work of this service for an incoming request
+    pub fn child(&self) -> Self {
+        TraceContext { trace_id: self.trace_id.clone(), span_id: format!("{:016x}", random()), sampled: self.sampled }
+    }
+
+    /// Parse a `traceparent` value
+    pub fn parse(value: &str) -> Option<Self> {
+        let parts: Vec<&str> = value.trim().split('-').collect();
+        let hex = |part: &str, len: usize| part.len() == len && part.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'));
+        let [version, trace_id, span_id, flags, ..] = parts[..] else {
+            return None;
+        };
+        // later versions may append fields, version 00 may not
+        if !hex(version, 2) || version == "ff" || (version == "00" && parts.len() != 4) {
+            return None;
+        }
+        if !hex(trace_id, 32) || !hex(span_id, 16) || !hex(flags, 2) {
+            return None;
+        }
+        if trace_id.bytes().all(|c| c == b'0') || span_id.bytes().all(|c| c == b'0') {
+            return None;
+        }
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        Some(TraceContext { trace_id: trace_id.to_string(), span_id: span_id.to_string(), sampled: flags & 1 == 1 })
+    }
+
+    /// The `traceparent` value
+    pub fn traceparent(&self) -> String {
+        format!("00-{}-{}-{:02x}", self.trace_id, self.span_id, self.sampled as u8)
+    }
+
+    /// Make this the trace context of the thread until the guard is dropped
+    pub fn enter(&self) -> ContextGuard {
+        context::attach(Snapshot(vec![
+            ("trace_id".to_string(), self.trace_id.clone()),
+            ("span_id".to_string(), self.span_id.clone()),
+        ]))
+    }
+}
+
+impl Default for TraceContext {
+    fn default() -> Self {
+        TraceContext::new()
+    }
+}
+
+pub(crate) fn random() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
+
+/// The trace context of the `traceparent` header, if any and valid
+pub fn extract(headers: &impl Headers) -> Option<TraceContext> {
+    headers.header(TRACEPARENT).and_then(TraceContext::parse)
+}
+
+/// The trace context the thread [entered](TraceContext::enter), if any
+///
+/// Its sampled flag is always set, errlog records every event.
+pub fn current() -> Option<TraceContext> {
+    let entries = context::entries();
+    let entry = |name: &str| entries.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+    Some(TraceContext { trace_id: entry("trace_id")?, span_id: entry("span_id")?, sampled: true })
+}
+
+/// An id to correlate what clients see with the server side logs, the trace id of the [current]
+/// trace context or else a random one
+///
+///     use errlog::trace::TraceContext;
+///
+///     assert_eq!(errlog::correlation_id().len(), 16);
+///     assert_ne!(errlog::correlation_id(), errlog::correlation_id());
+///
+///     let trace = TraceContext::new();
+///     let _span = trace.enter();
+///     assert_eq!(errlog::correlation_id(), trace.trace_id);
+pub fn correlation_id() -> String {
+    match current() {
+        Some(trace) => trace.trace_id,
+        None => format!("{:016x}", random()),
+    }
+}
+
+/// Set the `traceparent` header to the [current] trace context, if any
+pub fn inject(headers: &mut impl Headers) {
+    if let Some(trace) = current() {
+        headers.set_header(TRACEPARENT, trace.traceparent());
+    }
+}
@@ -0,0 +1,126 @@
+//! Turning panics into errors
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe, Location};
+use std::sync::Once;
+
+use crate::{Error, Result};
+
+/// The error of a closure which panicked under [catch_unwind_log]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Panicked {
+    message: String,
+    location: Option<String>,
+    backtrace: Option<String>,
+}
+
+impl Panicked {
+    /// The panic message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Where the panic happened, as `file:line:column`
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// The backtrace of the panic, when enabled by `RUST_BACKTRACE`
+    pub fn backtrace(&self) -> Option<&str> {
+        self.backtrace.as_deref()
+    }
+}
+
+impl fmt::Display for Panicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "panicked at {}: {}", location, self.message),
+            None => write!(f, "panicked: {}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Panicked {}
+
+thread_local! {
+    /// How many [catch_unwind_log] calls the thread is running
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+    /// The location and the backtrace of the last panic caught on the thread
+    static LAST: RefCell<(Option<String>, Option<String>)> = const { RefCell::new((None, None)) };
+}
+
+/// Whether the thread is running a [catch_unwind_log], whose panics are logged instead of printed
+pub(crate) fn catching() -> bool {
+    CATCHING.with(Cell::get) > 0
+}
+
+/// Chain a panic hook recording the location and the backtrace of caught panics, which are logged instead of
+/// printed
+fn install_hook() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !catching() {
+                return previous(info);
+            }
+            let location = info.location().map(|location| location.to_string());
+            let backtrace = Backtrace::capture();
+            let backtrace = match backtrace.status() {
+                BacktraceStatus::Captured => Some(backtrace.to_string()),
+                _ => None,
+            };
+            LAST.with(|last| *last.borrow_mut() = (location, backtrace));
+        }));
+    });
+}
+
+/// Run the closure, turning a panic into an error logged at `ERROR`
+///
+///     use errlog::logger::{self, MemoryWriter};
+///     use errlog::Panicked;
+///
+///     let output = MemoryWriter::new();
+///     let _guard = logger::builder().writer(output.clone()).set_default();
+///
+///     let _plugin = errlog::context::push("plugin", "thumbnails");
+///     let err = errlog::catch_unwind_log(|| -> u32 { panic!("index out of range") }).unwrap_err();
+///     let panicked = err.downcast_ref::<Panicked>().unwrap();
+///     assert_eq!(panicked.message(), "index out of range");
+///     assert!(panicked.location().unwrap().contains(".rs:"));
+///     # #[cfg(not(feature = "silent"))]
+///     assert!(output.contents().contains("plugin=thumbnails"));
+///
+///     assert_eq!(errlog::catch_unwind_log(|| 42).unwrap(), 42);
+///
+/// The event carries the location of the call and the [context](crate::context) entries, like
+/// the other events, and the error carries the entries as well. The panic is not printed by the
+/// panic hook, which is chained on first use. The closure is assumed to be unwind safe, so the
+/// state it mutates may be left inconsistent by the panic.
+#[track_caller]
+pub fn catch_unwind_log<T, F: FnOnce() -> T>(f: F) -> Result<T> {
+    let location = Location::caller();
+    let panicked = match catch(f) {
+        Ok(value) => return Ok(value),
+        Err(panicked) => panicked,
+    };
+    let err = crate::context::wrap(Error::new(panicked));
+    if !crate::__private::SILENT {
+        tracing::error!("{}:{} => {}", location.file(), location.line(), err);
+    }
+    Err(err)
+}
+
+/// Run the closure, catching a panic without printing it
+pub(crate) fn catch<T, F: FnOnce() -> T>(f: F) -> std::result::Result<T, Panicked> {
+    install_hook();
+    CATCHING.with(|catching| catching.set(catching.get() + 1));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|catching| catching.set(catching.get() - 1));
+    result.map_err(|payload| {
+        let (location, backtrace) = LAST.with(|last| last.take());
+        Panicked { message: crate::panic_message(payload.as_ref()), location, backtrace }
+    })
+}
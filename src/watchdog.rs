@@ -0,0 +1,140 @@
+//! Warnings for scopes running longer than expected
+//!
+//!     use std::time::Duration;
+//!
+//!     use errlog::logger::{self, MemoryWriter};
+//!
+//!     let output = MemoryWriter::new();
+//!     let _guard = logger::builder().writer(output.clone()).set_default();
+//!     {
+//!         let _watchdog = errlog::watchdog(Duration::from_millis(10), "handle_request");
+//!         std::thread::sleep(Duration::from_millis(100));
+//!     }
+//!
+//!     # #[cfg(not(feature = "silent"))]
+//!     assert!(output.contents().contains("handle_request still running after"));
+//!     # #[cfg(not(feature = "silent"))]
+//!     assert!(output.contents().contains("handle_request finished after"));
+//!
+//! Unlike a timeout, the scope is not interrupted, the warning is logged while it is stalled.
+//! All watchdogs share one thread named `errlog-watchdog`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tracing::Dispatch;
+
+struct Entry {
+    id: u64,
+    name: String,
+    started: Instant,
+    deadline: Instant,
+    dispatch: Dispatch,
+}
+
+#[derive(Default)]
+struct Timer {
+    entries: Mutex<Vec<Entry>>,
+    wake: Condvar,
+}
+
+fn timer() -> &'static Timer {
+    static TIMER: OnceLock<Timer> = OnceLock::new();
+    TIMER.get_or_init(|| {
+        // the thread waits for the initialization to finish
+        thread::Builder::new()
+            .name("errlog-watchdog".to_string())
+            .spawn(|| watch(TIMER.get_or_init(Timer::default)))
+            .expect("failed to spawn the errlog watchdog thread");
+        Timer::default()
+    })
+}
+
+fn watch(timer: &Timer) {
+    let mut entries = timer.entries.lock().unwrap_or_else(|err| err.into_inner());
+    loop {
+        let now = Instant::now();
+        let next = entries.iter().map(|entry| entry.deadline).min();
+        match next {
+            Some(deadline) if deadline <= now => {
+                let index = entries.iter().position(|entry| entry.deadline == deadline).unwrap_or_default();
+                let entry = entries.swap_remove(index);
+                // under the lock, so the guard dropped meanwhile finds it in one list or the other
+                FIRED.lock().unwrap_or_else(|err| err.into_inner()).push(entry.id);
+                drop(entries);
+                tracing::dispatcher::with_default(&entry.dispatch, || {
+                    crate::logmsg!(
+                        WARN,
+                        watchdog = entry.name.as_str(),
+                        "{} still running after {:.1?}",
+                        entry.name,
+                        entry.started.elapsed()
+                    );
+                });
+                entries = timer.entries.lock().unwrap_or_else(|err| err.into_inner());
+            }
+            Some(deadline) => {
+                entries = timer.wake.wait_timeout(entries, deadline - now).unwrap_or_else(|err| err.into_inner()).0;
+            }
+            None => entries = timer.wake.wait(entries).unwrap_or_else(|err| err.into_inner()),
+        }
+    }
+}
+
+/// The ids of the watchdogs which warned and are not dropped yet
+static FIRED: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+
+/// Logs the final duration when dropped after the warning, see [watchdog]
+#[must_use = "the watchdog stops when the guard is dropped"]
+pub struct Watchdog {
+    id: u64,
+    name: String,
+    started: Instant,
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        let timer = timer();
+        let mut entries = timer.entries.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(index) = entries.iter().position(|entry| entry.id == self.id) {
+            entries.swap_remove(index);
+            return;
+        }
+        drop(entries);
+        let mut fired = FIRED.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(index) = fired.iter().position(|id| *id == self.id) {
+            fired.swap_remove(index);
+            drop(fired);
+            crate::logmsg!(
+                INFO,
+                watchdog = self.name.as_str(),
+                "{} finished after {:.1?}",
+                self.name,
+                self.started.elapsed()
+            );
+        }
+    }
+}
+
+/// Log a `WARN` if the guard is not dropped within `deadline`, and the total duration at `INFO`
+/// when it is dropped after that
+///
+/// Both events carry the `watchdog` field set to `name` and go to the subscriber which is the
+/// default of the calling thread.
+pub fn watchdog(deadline: Duration, name: &str) -> Watchdog {
+    static IDS: AtomicU64 = AtomicU64::new(0);
+    let id = IDS.fetch_add(1, Ordering::Relaxed);
+    let started = Instant::now();
+    let timer = timer();
+    timer.entries.lock().unwrap_or_else(|err| err.into_inner()).push(Entry {
+        id,
+        name: name.to_string(),
+        started,
+        deadline: started + deadline,
+        dispatch: tracing::dispatcher::get_default(|dispatch| dispatch.clone()),
+    });
+    timer.wake.notify_one();
+    Watchdog { id, name: name.to_string(), started }
+}
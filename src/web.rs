@@ -0,0 +1,141 @@
+//! Web framework integrations for axum and actix-web, let handlers return errlog errors with `?`
+//!
+//! [AppError] wraps an anyhow error, when it is turned into a response it logs the full chain at
+//! `ERROR`, picks the HTTP status attached with [StatusExt](crate::StatusExt) or of the error
+//! code (see [ErrorCode](crate::ErrorCode)) or falls back to 500, and returns a JSON body which
+//! never contains internal details
+//!
+//! ```json
+//! {"code": "E1001", "message": "user not found"}
+//! ```
+
+use std::fmt;
+use std::panic::Location;
+
+use crate::Error;
+
+/// An error returned by web handlers, build it from any error with `?`
+///
+///     use errlog::web::AppError;
+///
+///     async fn handler() -> Result<String, AppError> {
+///         let content = std::fs::read_to_string("/path/to/file")?;
+///         Ok(content)
+///     }
+///
+/// The location where the error is converted (the `?` in the handler) is kept and logged with the
+/// chain.
+pub struct AppError {
+    error: Error,
+    location: &'static Location<'static>,
+}
+
+impl<E: Into<Error>> From<E> for AppError {
+    #[track_caller]
+    fn from(err: E) -> Self {
+        AppError {
+            error: err.into(),
+            location: Location::caller(),
+        }
+    }
+}
+
+impl fmt::Debug for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.error, f)
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&crate::__private::chain_string(&self.error))
+    }
+}
+
+impl AppError {
+    /// The wrapped error
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Where the error was converted into `AppError`
+    pub fn location(&self) -> &'static Location<'static> {
+        self.location
+    }
+
+    /// Unwrap the error
+    pub fn into_inner(self) -> Error {
+        self.error
+    }
+
+    /// The HTTP status of the response, see [http_status](crate::http_status)
+    pub fn status(&self) -> u16 {
+        crate::http_status(&self.error).unwrap_or(500)
+    }
+
+    /// The sanitized JSON body of the response
+    ///
+    /// Only [public messages](crate::public_message) and messages from
+    /// [ErrorCode](crate::ErrorCode) are shown to clients, any other error renders as a generic
+    /// message.
+    pub fn body(&self) -> serde_json::Value {
+        let message = match (crate::public_message(&self.error), crate::find_error_code(&self.error)) {
+            (Some(message), _) => message,
+            (None, Some(code)) => code.message(),
+            (None, None) => "internal server error",
+        };
+        serde_json::json!({
+            "code": crate::error_code(&self.error),
+            "message": message,
+        })
+    }
+
+    /// Log the full chain at `ERROR`
+    pub fn log(&self) {
+        if !crate::__private::SILENT {
+            tracing::error!(
+                code = crate::error_code(&self.error),
+                status = self.status(),
+                location = %self.location,
+                "{}",
+                self,
+            );
+        }
+    }
+}
+
+#[cfg(feature = "axum")]
+mod axum_impl {
+    use axum::http::StatusCode;
+    use axum::response::{IntoResponse, Response};
+    use axum::Json;
+
+    use super::AppError;
+
+    impl IntoResponse for AppError {
+        fn into_response(self) -> Response {
+            self.log();
+            let status = StatusCode::from_u16(self.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            (status, Json(self.body())).into_response()
+        }
+    }
+}
+
+#[cfg(feature = "actix")]
+mod actix_impl {
+    use actix_web::http::StatusCode;
+    use actix_web::{HttpResponse, ResponseError};
+
+    use super::AppError;
+
+    impl ResponseError for AppError {
+        fn status_code(&self) -> StatusCode {
+            StatusCode::from_u16(self.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+
+        fn error_response(&self) -> HttpResponse {
+            self.log();
+            HttpResponse::build(self.status_code()).json(self.body())
+        }
+    }
+}